@@ -1,5 +1,8 @@
 use itertools::Itertools;
-use overthrow_types::{Info, PlayerId, PlayerView};
+use jiff::Timestamp;
+use overthrow_types::{
+    ChallengeResult, ChatMessage, Claim, Difficulty, Info, Leave, LobbyState, PlayerId, PlayerView,
+};
 use ratatui::{
     Frame,
     layout::{Constraint, Layout, Rect},
@@ -12,7 +15,39 @@ use uuid::Uuid;
 
 use crate::tui::{State, UiState};
 
-fn draw_info_view(player_id: PlayerId, info: &Info, area: Rect, f: &mut Frame) {
+// how many chat messages are shown under the lobby
+const LOBBY_CHAT_LINES: usize = 3;
+
+// e.g. "Duke x2, Captain x1 (1 caught bluffing)"
+fn claims_summary(claims: &[Claim]) -> String {
+    if claims.is_empty() {
+        return "None".to_owned();
+    }
+
+    let counts = claims
+        .iter()
+        .counts_by(|claim| claim.card)
+        .into_iter()
+        .sorted_by_key(|(card, count)| (std::cmp::Reverse(*count), card.to_string()))
+        .map(|(card, count)| format!("{card} x{count}"))
+        .join(", ");
+
+    let caught = claims
+        .iter()
+        .filter(|claim| claim.challenge == Some(ChallengeResult::Bluffed))
+        .count();
+    let contradicted = claims.iter().filter(|claim| claim.contradicted).count();
+    match (caught, contradicted) {
+        (0, 0) => counts,
+        (caught, 0) => format!("{counts} ({caught} caught bluffing)"),
+        (0, contradicted) => format!("{counts} ({contradicted} contradicted)"),
+        (caught, contradicted) => {
+            format!("{counts} ({caught} caught bluffing, {contradicted} contradicted)")
+        }
+    }
+}
+
+fn draw_info_view(player_id: PlayerId, info: &Info, ui_state: &UiState, area: Rect, f: &mut Frame) {
     // have each player view take an equal amount of space
     let player_count = info.player_views.len();
     let view_constraints = (0..player_count).map(|_| Constraint::Ratio(1, player_count as u32));
@@ -37,22 +72,43 @@ fn draw_info_view(player_id: PlayerId, info: &Info, area: Rect, f: &mut Frame) {
 
     // create blocks for each player view
     for (area, (id, view)) in player_info_areas.iter().zip(player_views) {
-        let title = Line::from(format!("Player {id}"));
+        let title = if ui_state.left.contains(id) {
+            Line::from(format!("Player {id} (left)"))
+        } else if ui_state.disconnected.contains(id) {
+            Line::from(format!("Player {id} (disconnected)"))
+        } else {
+            Line::from(format!("Player {id}"))
+        };
         let (view, title) = match view {
             PlayerView::Other {
                 name,
                 coins,
                 revealed_cards,
+                claims,
             } => {
                 let view = format!(
-                    "Name: {}\nCoins: {}\nCards: {:?}",
-                    name, coins, revealed_cards
+                    "Name: {}\nCoins: {}\nCards: {:?}\nClaims: {}",
+                    name,
+                    coins,
+                    revealed_cards,
+                    claims_summary(claims)
                 );
 
                 (view, title)
             }
-            PlayerView::Me { name, coins, hand } => {
-                let view = format!("Name: {}\nCoins: {}\nHand: {:?}", name, coins, hand);
+            PlayerView::Me {
+                name,
+                coins,
+                hand,
+                claims,
+            } => {
+                let view = format!(
+                    "Name: {}\nCoins: {}\nHand: {:?}\nClaims: {}",
+                    name,
+                    coins,
+                    hand,
+                    claims_summary(claims)
+                );
 
                 (view, title.underlined())
             }
@@ -68,7 +124,10 @@ fn draw_input_view(game_id: Uuid, ui_state: &mut UiState, area: Rect, f: &mut Fr
     let kind = choices
         .map(|c| c.kind())
         .unwrap_or("Waiting for choices...");
-    let block = Block::bordered().title_top(format!("Input (Game ID: {game_id}) => {kind}"));
+    let paused = if ui_state.paused { " (paused)" } else { "" };
+    let block = Block::bordered()
+        .title_top(format!("Input (Game ID: {game_id}){paused} => {kind}"))
+        .title_bottom(waiting_for(ui_state));
     let items = choices.map(|c| c.choices()).unwrap_or_default();
 
     let list = List::new(items).block(block).highlight_symbol(">> ");
@@ -76,6 +135,53 @@ fn draw_input_view(game_id: Uuid, ui_state: &mut UiState, area: Rect, f: &mut Fr
     f.render_stateful_widget(list, area, &mut ui_state.state);
 }
 
+// e.g. "Dave: nice block"
+fn chat_line(chat: &ChatMessage) -> Line<'static> {
+    Line::from(format!("{}: {}", chat.name, chat.text))
+}
+
+// the message being written, or how to start writing one
+fn typing_line(ui_state: &UiState) -> Line<'static> {
+    match &ui_state.typing {
+        Some(typing) => Line::from(format!("> {typing}_")),
+        None => Line::from("t: chat").italic(),
+    }
+}
+
+fn draw_chat_view(ui_state: &UiState, area: Rect, f: &mut Frame) {
+    // as much of the latest chat as fits, under the borders
+    let shown = usize::from(area.height.saturating_sub(2));
+    let lines = ui_state.chat[ui_state.chat.len().saturating_sub(shown)..]
+        .iter()
+        .map(chat_line)
+        .collect_vec();
+    let block = Block::bordered()
+        .title_top("Chat")
+        .title_bottom(typing_line(ui_state));
+
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+// e.g. "Waiting for Player 2, Player 5 (7s left)"
+fn waiting_for(ui_state: &UiState) -> String {
+    if ui_state.waiting.is_empty() {
+        return String::new();
+    }
+
+    let players = ui_state
+        .waiting
+        .iter()
+        .map(|id| format!("Player {id}"))
+        .join(", ");
+    match ui_state.deadline {
+        Some(deadline) => {
+            let left = deadline.duration_since(Timestamp::now()).as_secs().max(0);
+            format!("Waiting for {players} ({left}s left)")
+        }
+        None => format!("Waiting for {players}"),
+    }
+}
+
 pub fn game_view(state: &State, ui_state: &mut UiState, f: &mut Frame) {
     if let State::InGame {
         game_id,
@@ -83,18 +189,54 @@ pub fn game_view(state: &State, ui_state: &mut UiState, f: &mut Frame) {
         info,
     } = state
     {
-        // split screen into 2/3 for player views, and 1/3 for input box
+        // split screen into 2/3 for player views, and 1/3 for input box and chat
         let layout = Layout::vertical([Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)]);
-        let [info_area, input_area] = layout.split(f.area())[..]
+        let [info_area, bottom_area] = layout.split(f.area())[..]
+            .try_into()
+            .expect("Two constraints provided");
+        let layout = Layout::horizontal([Constraint::Ratio(3, 5), Constraint::Ratio(2, 5)]);
+        let [input_area, chat_area] = layout.split(bottom_area)[..]
             .try_into()
             .expect("Two constraints provided");
 
-        draw_info_view(*player_id, info, info_area, f);
+        draw_info_view(*player_id, info, ui_state, info_area, f);
         draw_input_view(*game_id, ui_state, input_area, f);
+        draw_chat_view(ui_state, chat_area, f);
     }
 }
 
-pub fn splash_page(state: &State, f: &mut Frame) {
+// e.g. "Dave (host, you): ready"
+fn member_lines(lobby: &LobbyState, me: Option<Uuid>) -> Vec<Line<'static>> {
+    lobby
+        .members
+        .iter()
+        .map(|member| {
+            let bot = member.bot.map(|difficulty| match difficulty {
+                Difficulty::Easy => "easy bot",
+                Difficulty::Medium => "medium bot",
+                Difficulty::Hard => "hard bot",
+            });
+            let tags = [
+                bot,
+                (member.id == lobby.host).then_some("host"),
+                (Some(member.id) == me).then_some("you"),
+            ]
+            .into_iter()
+            .flatten()
+            .join(", ");
+            let tags = if tags.is_empty() {
+                tags
+            } else {
+                format!(" ({tags})")
+            };
+            let ready = if member.ready { "ready" } else { "not ready" };
+
+            Line::from(format!("{}{tags}: {ready}", member.name))
+        })
+        .collect()
+}
+
+pub fn splash_page(state: &State, ui_state: &UiState, f: &mut Frame) {
     if matches!(state, State::Connecting | State::InLobby { .. }) {
         let logo = BigText::builder()
             .pixel_size(PixelSize::Full)
@@ -102,30 +244,100 @@ pub fn splash_page(state: &State, f: &mut Frame) {
             .centered()
             .build();
 
+        let lobby = match state {
+            State::InLobby {
+                lobby: Some(lobby),
+                member,
+                ..
+            } => Some((lobby, *member)),
+            _ => None,
+        };
+        // a line for the timers and each member, then one for controls and one for errors, then the
+        // latest chat and one for writing it
+        let chat_lines = ui_state.chat.len().min(LOBBY_CHAT_LINES);
+        let lobby_height = lobby.map_or(0, |(lobby, _)| {
+            (lobby.members.len() + chat_lines) as u16 + 4
+        });
+
         // centre logo within the buffer
         const LOGO_HEIGHT: u16 = 7;
         let area = f.area();
         let y = (area.height / 2 - LOGO_HEIGHT / 2) - 2;
-        let area = Rect::new(area.x, y, area.width, LOGO_HEIGHT + 3);
+        let area =
+            Rect::new(area.x, y, area.width, LOGO_HEIGHT + 3 + lobby_height).intersection(f.area());
         let layout = Layout::vertical([
             Constraint::Fill(1),
             Constraint::Length(1),
             Constraint::Length(1),
+            Constraint::Length(lobby_height),
         ]);
-        let [logo_area, game_id_area, loading] = layout.split(area)[..]
+        let [logo_area, game_id_area, loading, lobby_area] = layout.split(area)[..]
             .try_into()
-            .expect("Specified 3 constraints");
+            .expect("Specified 4 constraints");
 
         let loading_text = Paragraph::new("Waiting for game to start...").centered();
 
         f.render_widget(logo, logo_area);
 
-        if let State::InLobby { game_id } = state {
-            let game_id_text = Paragraph::new(format!("Game ID: {game_id}")).centered();
-            f.render_widget(game_id_text, game_id_area);
-            f.render_widget(loading_text, loading);
-        } else {
-            f.render_widget(loading_text, game_id_area);
+        match (state, lobby) {
+            (_, Some((lobby, member))) => {
+                let undo = if lobby.undo {
+                    " (turns can be taken back)"
+                } else {
+                    ""
+                };
+                let omniscient = if lobby.omniscient {
+                    " (spectators see every hand)"
+                } else {
+                    ""
+                };
+                // quick matches start by themselves, with bots in any empty seats
+                let fill = lobby
+                    .fill_at
+                    .map(|fill_at| {
+                        let left = fill_at.duration_since(Timestamp::now()).as_secs().max(0);
+                        format!(" (quick match, starts in {left}s)")
+                    })
+                    .unwrap_or_default();
+                let code_text =
+                    Paragraph::new(format!("Join code: {}{undo}{omniscient}{fill}", lobby.code))
+                        .centered();
+                f.render_widget(code_text, game_id_area);
+                f.render_widget(loading_text, loading);
+
+                let controls = if Some(lobby.host) == member {
+                    "r: toggle ready, s: start game, e/m/h: add easy/medium/hard bot, x: remove bot, q: leave"
+                } else {
+                    "r: toggle ready, q: leave"
+                };
+                let error = ui_state
+                    .error
+                    .as_ref()
+                    .map(|e| e.to_string())
+                    .unwrap_or_default();
+                let leave = match lobby.leave {
+                    Leave::Resign => "players who leave resign",
+                    Leave::Bot => "bots play for players who leave",
+                };
+                let timers = format!(
+                    "{}s to react, {}s to act, {}s to choose cards, {leave}",
+                    lobby.reaction_time, lobby.action_time, lobby.choice_time
+                );
+                let mut lines = vec![Line::from(timers).italic()];
+                lines.extend(member_lines(lobby, member));
+                lines.push(Line::from(controls).italic());
+                lines.push(Line::from(error).red());
+                let chat = &ui_state.chat[ui_state.chat.len() - chat_lines..];
+                lines.extend(chat.iter().map(chat_line));
+                lines.push(typing_line(ui_state));
+                f.render_widget(Paragraph::new(lines).centered(), lobby_area);
+            }
+            (State::InLobby { game_id, .. }, None) => {
+                let game_id_text = Paragraph::new(format!("Game ID: {game_id}")).centered();
+                f.render_widget(game_id_text, game_id_area);
+                f.render_widget(loading_text, loading);
+            }
+            _ => f.render_widget(loading_text, game_id_area),
         }
     }
 }
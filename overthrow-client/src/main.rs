@@ -1,5 +1,3 @@
-use std::process::exit;
-
 use clap::Parser;
 use tokio::sync::mpsc::{self};
 use tokio_tungstenite::tungstenite::http::Uri;
@@ -16,6 +14,36 @@ struct Args {
 
     #[arg(short, long, default_value_t = 3000)]
     port: u16,
+
+    // allow turns to be taken back, when creating a lobby
+    #[arg(short, long)]
+    undo: bool,
+
+    // join code of the lobby to join, otherwise a new lobby is created
+    #[arg(short, long)]
+    join: Option<String>,
+
+    // join whichever quick match is waiting for players, which fills up with bots after a while
+    #[arg(long, conflicts_with = "join")]
+    quick: bool,
+
+    // what other players see us as
+    #[arg(short, long)]
+    name: Option<String>,
+
+    // seconds to react, choose an action and choose cards in, when creating a lobby
+    #[arg(long)]
+    reaction_time: Option<u32>,
+
+    #[arg(long)]
+    action_time: Option<u32>,
+
+    #[arg(long)]
+    choice_time: Option<u32>,
+
+    // whether players who leave mid-game resign or have a bot play for them, when creating a lobby
+    #[arg(long, value_parser = ["resign", "bot"])]
+    leave: Option<String>,
 }
 
 #[tokio::main]
@@ -32,10 +60,35 @@ async fn main() {
         .and_then(|uri| uri.host())
         .unwrap_or("localhost");
 
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    query.append_pair("undo", &args.undo.to_string());
+    if let Some(code) = &args.join {
+        query.append_pair("code", code);
+    }
+    if args.quick {
+        query.append_pair("quick", "true");
+    }
+    if let Some(name) = &args.name {
+        query.append_pair("name", name);
+    }
+    if let Some(leave) = &args.leave {
+        query.append_pair("leave", leave);
+    }
+    let timers = [
+        ("reaction_time", args.reaction_time),
+        ("action_time", args.action_time),
+        ("choice_time", args.choice_time),
+    ];
+    for (key, time) in timers {
+        if let Some(time) = time {
+            query.append_pair(key, &time.to_string());
+        }
+    }
+
     let address = Uri::builder()
         .scheme("ws")
         .authority(format!("{host}:{port}"))
-        .path_and_query("/websocket")
+        .path_and_query(format!("/websocket?{}", query.finish()))
         .build()
         .expect("Should be valid host and port");
 
@@ -44,7 +97,7 @@ async fn main() {
         server::client_message_handler(address, server_sender, server_receiver).await
     });
 
-    tui::ui(local_sender, local_receiver).await;
+    tui::ui(local_sender, local_receiver, args.undo).await;
 
     // wait for server task to exit gracefully
     handle.await.unwrap().unwrap();
@@ -1,39 +1,46 @@
-use overthrow_types::{Action, Blocks, Card, Challenge, Reaction};
+use itertools::Itertools;
+use overthrow_types::{Action, Blocks, Card, Challenge, PlayerId, Reaction, ReactionStage};
 use overthrow_types::{Block, ClientResponse};
 use ratatui::text::Text;
 
 #[derive(Debug)]
 pub enum Choices {
-    Actions(Vec<Action>),
-    Reactions(Vec<Reaction>),
+    // along with whether the last turn can be taken back
+    Actions(Vec<Action>, bool),
+    Reactions(Vec<Reaction>, ReactionStage),
     Blocks(Blocks),
     Challenge(Challenge),
     Victim([Card; 2]),
     OneFromThree([Card; 3]),
     TwoFromFour([Card; 4]),
+    // another player asked to take back the last turn
+    Undo(PlayerId),
 }
 
 impl Choices {
     pub fn kind(&self) -> &'static str {
         match self {
             Choices::Actions(..) => "Choose action",
-            Choices::Reactions(..) => "Choose reaction",
+            Choices::Reactions(_, ReactionStage::All) => "Choose reaction",
+            Choices::Reactions(_, ReactionStage::Challenges) => "Challenge action",
+            Choices::Reactions(_, ReactionStage::Blocks) => "Choose block",
             Choices::Blocks(..) => "Choose block",
             Choices::Challenge(..) => "Choose challenge",
             Choices::Victim(..) => "Choose victim card",
             Choices::OneFromThree(..) => "Exchange card",
             Choices::TwoFromFour(..) => "Exchange cards",
+            Choices::Undo(..) => "Allow undo",
         }
     }
 
     // select item from list in UI
     pub fn selection_to_response(&self, index: usize) -> ClientResponse {
         let response = match self {
-            Choices::Actions(actions) => {
-                let action = actions[index].clone();
-                Some(ClientResponse::Act(action))
-            }
-            Choices::Reactions(reactions) => {
+            Choices::Actions(actions, undo) => match actions.get(index) {
+                Some(action) => Some(ClientResponse::Act(action.clone())),
+                None => undo.then_some(ClientResponse::Undo),
+            },
+            Choices::Reactions(reactions, _) => {
                 reactions.get(index).cloned().map(ClientResponse::React)
             }
             Choices::Blocks(blocks) => match blocks {
@@ -52,12 +59,20 @@ impl Choices {
             Choices::OneFromThree(cards) => {
                 cards.get(index).copied().map(ClientResponse::ExchangeOne)
             }
-            Choices::TwoFromFour(..) => todo!(),
+            Choices::TwoFromFour(cards) => Choices::pairs(cards)
+                .nth(index)
+                .map(ClientResponse::ExchangeTwo),
+            Choices::Undo(..) => Some(ClientResponse::AllowUndo(index == 0)),
         };
 
         response.unwrap_or(ClientResponse::Pass)
     }
 
+    // every pair of cards that can be kept from an exchange
+    fn pairs(cards: &[Card; 4]) -> impl Iterator<Item = [Card; 2]> + '_ {
+        cards.iter().tuple_combinations().map(|(c1, c2)| [*c1, *c2])
+    }
+
     fn block(block: &Block) -> Text<'static> {
         let actor = block.actor();
         let claim = block.claim();
@@ -74,7 +89,7 @@ impl Choices {
     pub fn choices(&self) -> Vec<Text<'_>> {
         use std::iter;
         match self {
-            Choices::Actions(actions) => actions
+            Choices::Actions(actions, undo) => actions
                 .iter()
                 .map(|action| {
                     let kind = action.kind();
@@ -85,8 +100,9 @@ impl Choices {
 
                     Text::raw(format!("As {claim}: {kind}"))
                 })
+                .chain(undo.then(|| Text::raw("Undo last turn")))
                 .collect(),
-            Choices::Reactions(reactions) => reactions
+            Choices::Reactions(reactions, _) => reactions
                 .iter()
                 .map(|reaction| match reaction {
                     Reaction::Challenge(challenge) => Choices::challenge(challenge),
@@ -105,7 +121,13 @@ impl Choices {
             }
             Choices::Victim(cards) => cards.map(|c| Text::raw(format!("Card: {c}"))).into(),
             Choices::OneFromThree(cards) => cards.map(|c| Text::raw(format!("Card: {c}"))).into(),
-            Choices::TwoFromFour(_) => todo!(),
+            Choices::TwoFromFour(cards) => Choices::pairs(cards)
+                .map(|[c1, c2]| Text::raw(format!("Cards: {c1} and {c2}")))
+                .collect(),
+            Choices::Undo(requester) => vec![
+                Text::raw(format!("Let Player {requester} take back the last turn")),
+                Text::raw("Decline"),
+            ],
         }
     }
 }
@@ -1,17 +1,25 @@
 use futures::SinkExt;
 use futures::StreamExt;
-use overthrow_types::{ClientMessage, ClientResponse};
+use overthrow_types::{ClientError, ClientMessage, ClientResponse};
 use serde_json::from_str;
 use serde_json::to_string;
+use std::time::Duration;
 use tokio::{
     select,
     sync::mpsc::{Receiver, Sender},
+    time::sleep,
 };
 use tokio_tungstenite as ws;
 use tokio_tungstenite::tungstenite::http::Uri;
+use uuid::Uuid;
 use ws::tungstenite::Error;
 use ws::tungstenite::Message as WsMessage;
 
+// the server keeps a seat for a minute after its connection is lost, so we keep trying for about
+// that long
+const RECONNECT_ATTEMPTS: usize = 30;
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
 #[derive(Debug)]
 pub enum LocalMessage {
     Response(ClientResponse),
@@ -22,20 +30,47 @@ fn wrap_message(msg: ClientResponse) -> WsMessage {
     WsMessage::text(to_string(&msg).expect("Serialization should never fail"))
 }
 
+// same server, but taking back the seat token was given out for
+fn resume_address(address: &Uri, token: Uuid) -> Uri {
+    let mut parts = address.clone().into_parts();
+    parts.path_and_query = Some(
+        format!("/websocket?resume={token}")
+            .parse()
+            .expect("Should be a valid path"),
+    );
+    Uri::from_parts(parts).expect("Should be a valid address")
+}
+
 // this basically just acts as a relay to the server so the main task doesn't get stuck
 pub async fn client_message_handler(
     address: Uri,
-    local_sender: Sender<ClientMessage>,
+    local_sender: Sender<Result<ClientMessage, ClientError>>,
     mut local_receiver: Receiver<LocalMessage>,
 ) -> Result<(), Error> {
-    let (mut server_sender, mut server_receiver) = ws::connect_async(address).await?.0.split();
+    let (mut server_sender, mut server_receiver) = ws::connect_async(&address).await?.0.split();
+    // for getting our seat back if the connection drops mid-game
+    let mut token = None;
 
     loop {
         select! {
-            Some(Ok(msg)) = server_receiver.next() => {
+            msg = server_receiver.next() => {
+                let Some(Ok(msg)) = msg else {
+                    // the connection dropped without being closed, so try to get back in
+                    let Some(token) = token else { break };
+                    let Some(socket) = reconnect(resume_address(&address, token)).await else { break };
+                    (server_sender, server_receiver) = socket.split();
+                    continue;
+                };
                 // must be text, if not, then server is done
-                let WsMessage::Text(msg) = msg else { todo!() };
-                let msg: ClientMessage = from_str(&msg).expect("Server always sends well formed responses");
+                let WsMessage::Text(msg) = msg else { break };
+                // errors are sent in place of messages, like when a lobby can't be joined
+                let msg = match from_str::<ClientMessage>(&msg) {
+                    Ok(msg) => Ok(msg),
+                    Err(_) => Err(from_str(&msg).expect("Server always sends well formed responses")),
+                };
+                if let Ok(ClientMessage::ResumeToken(resume)) = msg {
+                    token = Some(resume);
+                }
 
                 // send ClientMessage to main task
                 let Ok(()) = local_sender.send(msg).await else { break };
@@ -43,8 +78,8 @@ pub async fn client_message_handler(
             Some(msg) = local_receiver.recv() => {
                 match msg {
                     LocalMessage::Response(res) => {
-                        // send ClientResponse to server
-                        let Ok(()) = server_sender.send(wrap_message(res)).await else { break };
+                        // a failed send shows up as the connection dropping, which is handled above
+                        let _ = server_sender.send(wrap_message(res)).await;
                     },
                     LocalMessage::Quit => break,
                 }
@@ -53,13 +88,25 @@ pub async fn client_message_handler(
         }
     }
 
-    // should close WebSocket gracefully
-    server_receiver
+    // should close WebSocket gracefully (unless the server already has)
+    let _ = server_receiver
         .reunite(server_sender)
         .expect("Should reunite")
         .close(None)
-        .await
-        .expect("Should close");
+        .await;
 
     Ok(())
 }
+
+async fn reconnect(
+    address: Uri,
+) -> Option<ws::WebSocketStream<ws::MaybeTlsStream<tokio::net::TcpStream>>> {
+    for _ in 0..RECONNECT_ATTEMPTS {
+        sleep(RECONNECT_DELAY).await;
+        if let Ok((socket, _)) = ws::connect_async(&address).await {
+            return Some(socket);
+        }
+    }
+
+    None
+}
@@ -9,25 +9,87 @@ use crossterm::event::EventStream;
 use crossterm::event::KeyCode;
 use crossterm::event::KeyEvent;
 use futures::StreamExt;
-use overthrow_types::{ClientMessage, Info, PlayerId, Summary};
+use jiff::Timestamp;
+use overthrow_types::{
+    ChatMessage, ClientError, ClientMessage, ClientResponse, Commitment, Difficulty, Info,
+    LobbyState, PlayerId, Reveal, Summary,
+};
 use ratatui::Frame;
 use ratatui::widgets::ListState;
 use tokio::time::interval;
 use tokio::{select, sync::mpsc};
 use uuid::Uuid;
 
+// how many chat messages are kept around to be shown
+const CHAT_HISTORY: usize = 50;
+
 #[derive(Debug)]
 pub struct Context<'a> {
     pub sender: &'a mut mpsc::Sender<LocalMessage>,
     pub player_id: &'a mut Option<PlayerId>,
+    pub shuffle: &'a mut Shuffle,
     pub state: &'a mut State,
     pub ui_state: &'a mut UiState,
+    // whether the lobby allows taking back turns
+    pub undo: bool,
+}
+
+// commitment to the shuffle seed and its reveal, checked against each other once the game is over
+#[derive(Debug, Default)]
+pub struct Shuffle {
+    pub commitment: Option<Commitment>,
+    pub reveal: Option<Reveal>,
+    // what we mixed into the seed
+    pub entropy: Option<String>,
+}
+
+impl Shuffle {
+    // the deals themselves aren't checked, as that takes every decision in the game. Replaying the
+    // game from the seed does that
+    fn report(&self) {
+        let (Some(commitment), Some(reveal)) = (&self.commitment, &self.reveal) else {
+            return;
+        };
+
+        let seed = match reveal.verify(commitment) {
+            Ok(seed) => seed,
+            Err(e) => return println!("{e}, the deals may have been rigged"),
+        };
+        // the server picks which entropy goes into the seed, so it could have left ours out after
+        // seeing it
+        match &self.entropy {
+            Some(entropy) if !reveal.entropy.contains(entropy) => println!(
+                "Our entropy wasn't mixed into the shuffle seed {seed}, so the server could have chosen the deals"
+            ),
+            Some(_) => println!(
+                "Shuffle seed {seed} matches commitment {commitment} and includes our entropy, replay the game from it to check the deals"
+            ),
+            None => println!(
+                "Shuffle seed {seed} matches commitment {commitment}, replay the game from it to check the deals"
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Default)]
 pub struct UiState {
     pub items: Option<Choices>,
     pub state: ListState,
+    // players the game is waiting on, and when their time runs out
+    pub waiting: Vec<PlayerId>,
+    pub deadline: Option<Timestamp>,
+    // players who lost their connection and haven't come back yet
+    pub disconnected: Vec<PlayerId>,
+    // players who didn't come back in time
+    pub left: Vec<PlayerId>,
+    // last error from the server, until the next key press
+    pub error: Option<ClientError>,
+    // latest chat, oldest first
+    pub chat: Vec<ChatMessage>,
+    // chat message being written, if any
+    pub typing: Option<String>,
+    // the server's operator stopped the game
+    pub paused: bool,
 }
 
 impl UiState {
@@ -41,6 +103,17 @@ impl UiState {
         self.items = None;
         self.state = ListState::default();
     }
+
+    pub fn wait_for(&mut self, deciders: Vec<PlayerId>, deadline: Option<Timestamp>) {
+        self.waiting = deciders;
+        self.deadline = deadline;
+    }
+
+    pub fn chatted(&mut self, chat: ChatMessage) {
+        self.chat.push(chat);
+        let extra = self.chat.len().saturating_sub(CHAT_HISTORY);
+        self.chat.drain(..extra);
+    }
 }
 
 #[derive(Debug)]
@@ -48,6 +121,8 @@ pub enum State {
     Connecting,
     InLobby {
         game_id: Uuid,
+        member: Option<Uuid>,
+        lobby: Option<LobbyState>,
     },
     InGame {
         game_id: Uuid,
@@ -58,7 +133,8 @@ pub enum State {
 
 pub async fn ui(
     mut sender: mpsc::Sender<LocalMessage>,
-    mut receiver: mpsc::Receiver<ClientMessage>,
+    mut receiver: mpsc::Receiver<Result<ClientMessage, ClientError>>,
+    undo: bool,
 ) {
     // for terminal events
     let mut term_events = EventStream::new();
@@ -70,58 +146,70 @@ pub async fn ui(
 
     // state
     let mut player_id = None;
+    let mut shuffle = Shuffle::default();
     let mut state = State::Connecting;
     let mut ui_state = UiState::default();
 
     term.clear().expect("Should be able to clear");
 
-    loop {
+    let phase = loop {
         let ctx = Context {
             sender: &mut sender,
             player_id: &mut player_id,
+            shuffle: &mut shuffle,
             state: &mut state,
             ui_state: &mut ui_state,
+            undo,
         };
         let state = select! {
             biased;
             Some(Ok(event)) = term_events.next() => handle_term_event(event, ctx),
-            Some(msg) = receiver.recv() => handle_server_event(msg, ctx),
+            Some(msg) = receiver.recv() => match msg {
+                Ok(msg) => handle_server_event(msg, ctx),
+                Err(e) => handle_server_error(e, ctx),
+            },
             _ = interval.tick() => {
                 term.draw(|f| draw(&state, &mut ui_state, f)).expect("Drawing should not fail");
                 continue;
             },
-            else => break,
+            else => break GamePhase::Cancelled,
         };
 
-        if let GamePhase::End(_) | GamePhase::Cancelled = state {
-            break;
+        if !matches!(state, GamePhase::Continue) {
+            break state;
         }
-    }
+    };
 
-    sender
-        .send(LocalMessage::Quit)
-        .await
-        .expect("Should be able to send quit message");
+    // the server might have already closed the connection
+    let _ = sender.send(LocalMessage::Quit).await;
 
     ratatui::restore();
+    match phase {
+        GamePhase::Rejected(e @ ClientError::Kicked) => println!("{e}"),
+        GamePhase::Rejected(e) => println!("Couldn't join: {e}"),
+        _ => {}
+    }
+    shuffle.report();
 }
 
 fn draw(state: &State, ui_state: &mut UiState, f: &mut Frame) {
-    draw::splash_page(state, f);
+    draw::splash_page(state, ui_state, f);
     draw::game_view(state, ui_state, f);
 }
 
 #[derive(Debug)]
 enum GamePhase {
-    End(Summary),
+    End(#[allow(dead_code)] Summary),
     Cancelled,
+    // the lobby couldn't be joined, our seat taken back, or we were kicked
+    Rejected(ClientError),
     Continue,
 }
 
 fn update_info(info: Info, ctx: Context) {
     match ctx.state {
         State::Connecting => unreachable!("Info should never be sent before game_id"),
-        State::InLobby { game_id } => {
+        State::InLobby { game_id, .. } => {
             let game_id = *game_id;
             let player_id = ctx.player_id.expect("Should have player_id before info");
 
@@ -139,28 +227,95 @@ fn handle_server_event(msg: ClientMessage, ctx: Context) -> GamePhase {
     use ClientMessage as Msg;
     match msg {
         Msg::PlayerId(player_id) => *ctx.player_id = Some(player_id),
-        Msg::GameId(game_id) => *ctx.state = State::InLobby { game_id },
+        // sent again when we get our seat back after losing the connection
+        Msg::GameId(id) if matches!(ctx.state, State::InGame { game_id, .. } if *game_id == id) => {
+        }
+        Msg::GameId(game_id) => {
+            *ctx.state = State::InLobby {
+                game_id,
+                member: None,
+                lobby: None,
+            }
+        }
+        Msg::MemberId(id) => {
+            if let State::InLobby { member, .. } = ctx.state {
+                *member = Some(id);
+            }
+        }
+        Msg::Lobby(state) => {
+            if let State::InLobby { lobby, .. } = ctx.state {
+                *lobby = Some(state);
+            }
+        }
+        // kept by the connection to the server, for reconnecting
+        Msg::ResumeToken(_) => {}
+        Msg::Commitment(commitment) => {
+            ctx.shuffle.commitment = Some(commitment);
+            // mix some of our own entropy into the seed, only once as the commitment is sent again
+            // when we get our seat back
+            if ctx.shuffle.entropy.is_none() {
+                let entropy = Uuid::now_v7().simple().to_string();
+                ctx.shuffle.entropy = Some(entropy.clone());
+                ctx.sender
+                    .try_send(LocalMessage::Response(ClientResponse::Entropy(entropy)))
+                    .expect("Should always have capacity");
+            }
+        }
+        Msg::Reveal(reveal) => ctx.shuffle.reveal = Some(reveal),
         Msg::Info(info) => update_info(info, ctx),
         Msg::End(summary) => return GamePhase::End(summary),
         Msg::GameCancelled => return GamePhase::Cancelled,
         // setting and resetting ui state
-        Msg::Outcome(outcome) => ctx.ui_state.reset(),
-        Msg::ActionChoices(actions) => ctx.ui_state.set(Choices::Actions(actions)),
-        Msg::ChallengeChoice(challenge, timestamp) => {
+        Msg::Outcome(_outcome) => ctx.ui_state.reset(),
+        // claims show up in the info that follows
+        Msg::ActionDeclared(..) | Msg::ReactionDeclared(..) => {}
+        Msg::ActionChoices(actions, _timestamp) => {
+            ctx.ui_state.set(Choices::Actions(actions, ctx.undo))
+        }
+        Msg::ChallengeChoice(challenge, _timestamp) => {
             ctx.ui_state.set(Choices::Challenge(challenge))
         }
-        Msg::BlockChoices(blocks, timestamp) => ctx.ui_state.set(Choices::Blocks(blocks)),
-        Msg::ReactionChoices(reactions, timestamp) => {
-            ctx.ui_state.set(Choices::Reactions(reactions))
+        Msg::BlockChoices(blocks, _timestamp) => ctx.ui_state.set(Choices::Blocks(blocks)),
+        Msg::ReactionChoices(reactions, stage, _timestamp) => {
+            ctx.ui_state.set(Choices::Reactions(reactions, stage))
+        }
+        Msg::VictimChoices(cards, _timestamp) => ctx.ui_state.set(Choices::Victim(cards)),
+        Msg::OneFromThreeChoices(cards, _timestamp) => {
+            ctx.ui_state.set(Choices::OneFromThree(cards))
+        }
+        Msg::TwoFromFourChoices(cards, _timestamp) => ctx.ui_state.set(Choices::TwoFromFour(cards)),
+        Msg::WaitingFor(deciders, deadline) => ctx.ui_state.wait_for(deciders, deadline),
+        Msg::PlayerDisconnected(player, _given_up) => ctx.ui_state.disconnected.push(player),
+        Msg::PlayerReconnected(player) => ctx.ui_state.disconnected.retain(|id| *id != player),
+        Msg::PlayerLeft(player) => ctx.ui_state.left.push(player),
+        Msg::Chat(chat) => ctx.ui_state.chatted(chat),
+        Msg::GamePaused => ctx.ui_state.paused = true,
+        Msg::GameResumed => ctx.ui_state.paused = false,
+        Msg::UndoRequested(requester, _timestamp) => ctx.ui_state.set(Choices::Undo(requester)),
+        // only clears an unanswered request, as the current player's choices may already be back
+        Msg::Undone | Msg::UndoDeclined => {
+            if let Some(Choices::Undo(..)) = ctx.ui_state.items {
+                ctx.ui_state.reset()
+            }
         }
-        Msg::VictimChoices(cards) => ctx.ui_state.set(Choices::Victim(cards)),
-        Msg::OneFromThreeChoices(cards) => ctx.ui_state.set(Choices::OneFromThree(cards)),
-        Msg::TwoFromFourChoices(cards) => todo!(),
     }
 
     GamePhase::Continue
 }
 
+fn handle_server_error(e: ClientError, ctx: Context) -> GamePhase {
+    match e {
+        ClientError::LobbyNotFound
+        | ClientError::LobbyFull
+        | ClientError::CannotResume
+        | ClientError::Kicked => GamePhase::Rejected(e),
+        e => {
+            ctx.ui_state.error = Some(e);
+            GamePhase::Continue
+        }
+    }
+}
+
 fn handle_term_event(event: Event, ctx: Context) -> GamePhase {
     use Event as E;
     match event {
@@ -173,6 +328,23 @@ fn handle_term_event(event: Event, ctx: Context) -> GamePhase {
 }
 
 fn handle_key_event(event: KeyEvent, ctx: Context) -> GamePhase {
+    ctx.ui_state.error = None;
+    if let Some(typing) = &mut ctx.ui_state.typing {
+        handle_typing_key(event, typing, ctx.sender);
+        if matches!(event.code, KeyCode::Enter | KeyCode::Esc) {
+            ctx.ui_state.typing = None;
+        }
+        return GamePhase::Continue;
+    }
+    if event.code == KeyCode::Char('t') {
+        ctx.ui_state.typing = Some(String::new());
+        return GamePhase::Continue;
+    }
+
+    if let State::InLobby { member, lobby, .. } = ctx.state {
+        return handle_lobby_key(event, *member, lobby.as_ref(), ctx.sender);
+    }
+
     match event.code {
         KeyCode::Char('q') | KeyCode::Esc => return GamePhase::Cancelled,
         KeyCode::Up => ctx.ui_state.state.scroll_up_by(1),
@@ -196,3 +368,60 @@ fn handle_key_event(event: KeyEvent, ctx: Context) -> GamePhase {
     }
     GamePhase::Continue
 }
+
+// writing a chat message, which is sent on enter (and thrown away on escape)
+fn handle_typing_key(
+    event: KeyEvent,
+    typing: &mut String,
+    sender: &mut mpsc::Sender<LocalMessage>,
+) {
+    match event.code {
+        KeyCode::Char(c) => typing.push(c),
+        KeyCode::Backspace => {
+            typing.pop();
+        }
+        KeyCode::Enter if !typing.trim().is_empty() => {
+            let chat = ClientResponse::Chat(std::mem::take(typing));
+            sender
+                .try_send(LocalMessage::Response(chat))
+                .expect("Should always have capacity");
+        }
+        _ => {}
+    }
+}
+
+fn handle_lobby_key(
+    event: KeyEvent,
+    member: Option<Uuid>,
+    lobby: Option<&LobbyState>,
+    sender: &mut mpsc::Sender<LocalMessage>,
+) -> GamePhase {
+    let response = match event.code {
+        KeyCode::Char('q') | KeyCode::Esc => return GamePhase::Cancelled,
+        // toggles whether we are ready
+        KeyCode::Char('r') => {
+            let ready = lobby
+                .and_then(|lobby| lobby.members.iter().find(|m| Some(m.id) == member))
+                .is_some_and(|member| member.ready);
+            ClientResponse::Ready(!ready)
+        }
+        KeyCode::Char('s') => ClientResponse::Start,
+        KeyCode::Char('e') => ClientResponse::AddBot(Difficulty::Easy),
+        KeyCode::Char('m') => ClientResponse::AddBot(Difficulty::Medium),
+        KeyCode::Char('h') => ClientResponse::AddBot(Difficulty::Hard),
+        // takes out the bot that was added last
+        KeyCode::Char('x') => {
+            let Some(bot) = lobby.and_then(|lobby| lobby.members.iter().rfind(|m| m.bot.is_some()))
+            else {
+                return GamePhase::Continue;
+            };
+            ClientResponse::RemoveBot(bot.id)
+        }
+        _ => return GamePhase::Continue,
+    };
+
+    sender
+        .try_send(LocalMessage::Response(response))
+        .expect("Should always have capacity");
+    GamePhase::Continue
+}
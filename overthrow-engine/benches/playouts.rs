@@ -0,0 +1,58 @@
+#![feature(test)]
+extern crate test;
+
+use overthrow_engine::compact::CompactState;
+use overthrow_engine::driver::Phase;
+use overthrow_engine::machine::{CoupGame, WaitState};
+use rand::SeedableRng;
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand_chacha::ChaCha8Rng;
+use test::Bencher;
+
+// Random playouts from the start of a 4 player game, one per iteration, which is what search
+// spends most of its time on. Run with `cargo +nightly bench -p overthrow-engine`
+
+const NAMES: [&str; 4] = ["Dave", "Garry", "Maxine", "Rosa"];
+
+// through the driver, which has to deal a new game for every playout as games can't be copied
+#[bench]
+fn driver_playout(b: &mut Bencher) {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut seed = 0;
+
+    b.iter(|| {
+        seed += 1;
+        let mut phase = Phase::from(CoupGame::with_seed(NAMES, seed));
+        while let Some(decision) = phase.options().into_iter().choose(&mut rng) {
+            phase = phase
+                .decide(decision)
+                .expect("Options are always valid")
+                .phase;
+        }
+        phase.is_over()
+    });
+}
+
+// through compact state, copied from the same starting position every time
+#[bench]
+fn compact_playout(b: &mut Bencher) {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let start = CompactState::from(&Phase::from(CoupGame::with_seed(NAMES, 0)));
+    let mut seed = 0;
+
+    b.iter(|| {
+        seed += 1;
+        let mut state = start;
+        state.reseed(seed);
+        while let Some(mv) = state.moves().choose(&mut rng) {
+            state.apply(*mv);
+        }
+        state.winner()
+    });
+}
+
+#[bench]
+fn compact_copy(b: &mut Bencher) {
+    let start = CompactState::from(&Phase::from(CoupGame::with_seed(NAMES, 0)));
+    b.iter(|| test::black_box(start));
+}
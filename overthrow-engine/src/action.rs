@@ -238,12 +238,13 @@ impl Block {
                     Card::Captain
                 }
             }
-            BlockableAct::Assassinate { .. } => Card::Assassin,
+            // the victim claims Contessa, not the Assassin the actor claimed
+            BlockableAct::Assassinate { .. } => Card::Contessa,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PossibleActions {
     pub(crate) actor: PlayerId,
     pub(crate) assassinations: Vec<Action>,
@@ -295,11 +296,24 @@ impl Challenge {
     }
 }
 
-#[derive(Debug)]
+// which reactions are being taken right now (see ReactionWindow)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ReactionStage {
+    // blocks and challenges at once
+    #[default]
+    All,
+    // only challenges to the action's claim
+    Challenges,
+    // the action's claim stands, so only blocks
+    Blocks,
+}
+
+#[derive(Debug, Clone)]
 pub struct PossibleReactions {
     pub(crate) block: Blocks,
     pub(crate) challenge: HashMap<PlayerId, Challenge>,
     pub(crate) actor: PlayerId,
+    pub(crate) stage: ReactionStage,
 }
 
 impl PossibleReactions {
@@ -315,19 +329,30 @@ impl PossibleReactions {
         &self.challenge
     }
 
+    pub fn stage(&self) -> ReactionStage {
+        self.stage
+    }
+
+    // reactions that can be made in the current stage
     pub fn all(&self) -> HashMap<PlayerId, Vec<Reaction>> {
         let mut map: HashMap<PlayerId, Vec<Reaction>> =
             HashMap::with_capacity(self.challenge.len() + 2);
 
-        match self.block.clone() {
-            Blocks::Other(block) => {
-                map.insert(block.blocker(), vec![Reaction::Block(block)]);
-            }
-            Blocks::Steal(b1, b2) => {
-                map.insert(b1.blocker(), vec![Reaction::Block(b1)]);
-                map.insert(b2.blocker(), vec![Reaction::Block(b2)]);
-            }
-        };
+        if self.stage != ReactionStage::Challenges {
+            match self.block.clone() {
+                Blocks::Other(block) => {
+                    map.insert(block.blocker(), vec![Reaction::Block(block)]);
+                }
+                Blocks::Steal(b1, b2) => {
+                    // both blocks belong to the victim, who can claim either card
+                    map.insert(b1.blocker(), vec![Reaction::Block(b1), Reaction::Block(b2)]);
+                }
+            };
+        }
+
+        if self.stage == ReactionStage::Blocks {
+            return map;
+        }
 
         for challenge in self.challenge.values().cloned() {
             let challenger = challenge.challenger();
@@ -341,7 +366,7 @@ impl PossibleReactions {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PossibleBlocks {
     pub(crate) blocks: HashMap<PlayerId, Block>,
     pub(crate) actor: PlayerId,
@@ -357,7 +382,7 @@ impl PossibleBlocks {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PossibleChallenges {
     pub(crate) challenges: HashMap<PlayerId, Challenge>,
     pub(crate) actor: PlayerId,
@@ -0,0 +1,396 @@
+use crate::deck::{Card, Hand, STARTING_DECK};
+use crate::driver::Decision;
+use crate::machine::Outcome;
+use crate::player_map::{Player, PlayerMap};
+use crate::players::PlayerId;
+use arrayvec::ArrayVec;
+use std::collections::BTreeMap;
+
+const CARD_KINDS: usize = 5;
+type CardCounts = [u32; CARD_KINDS];
+
+fn copies(card: Card) -> u32 {
+    STARTING_DECK.iter().filter(|c| **c == card).count() as u32
+}
+
+// how many ways there are of picking the cards in hand (with counts) from the cards in pool
+fn ways(pool: &CardCounts, hand: &CardCounts) -> f64 {
+    fn choose(n: u32, k: u32) -> f64 {
+        if k > n {
+            return 0.0;
+        }
+        (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+    }
+
+    pool.iter().zip(hand).map(|(n, k)| choose(*n, *k)).product()
+}
+
+// every unordered hand of size count
+fn hands_of(count: usize) -> Vec<ArrayVec<Card, 2>> {
+    match count {
+        0 => vec![ArrayVec::new()],
+        1 => Card::iter()
+            .map(|card| [card].into_iter().collect())
+            .collect(),
+        2 => Card::iter()
+            .enumerate()
+            .flat_map(|(i, c1)| Card::iter().skip(i).map(move |c2| [c1, c2].into()))
+            .collect(),
+        _ => unreachable!("Players never hold more than two cards"),
+    }
+}
+
+fn count_cards<'a>(cards: impl IntoIterator<Item = &'a Card>) -> CardCounts {
+    let mut counts = [0; CARD_KINDS];
+    for card in cards {
+        counts[*card as usize] += 1;
+    }
+    counts
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BeliefMode {
+    // every deal that fits what is publicly known is equally likely
+    Exact,
+    // same as Exact, but each claim of a card multiplies the odds of hands holding it by weight
+    ClaimWeighted { weight: f64 },
+}
+
+// what we know about a single opponent since their last exchange
+#[derive(Debug, Clone, Default)]
+struct Knowledge {
+    // cards still face down
+    hidden: usize,
+    // cards they have proven to hold through challenges
+    known: ArrayVec<Card, 2>,
+    // cards they were caught not holding
+    excluded: [bool; CARD_KINDS],
+    claims: CardCounts,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandBelief {
+    pub cards: ArrayVec<Card, 2>,
+    pub probability: f64,
+}
+
+// Tracks, from the point of view of a single player, which hidden cards each opponent could be
+// holding. Anything public (revealed cards, deaths) is read from the PlayerMap, while claims and
+// challenges have to be fed in as they happen (see observe)
+#[derive(Debug, Clone)]
+pub struct Beliefs {
+    me: PlayerId,
+    mode: BeliefMode,
+    own: ArrayVec<Card, 2>,
+    revealed: CardCounts,
+    opponents: BTreeMap<PlayerId, Knowledge>,
+}
+
+impl Beliefs {
+    pub fn new(me: PlayerId, mode: BeliefMode, players: &PlayerMap) -> Beliefs {
+        let mut beliefs = Beliefs {
+            me,
+            mode,
+            own: ArrayVec::new(),
+            revealed: [0; CARD_KINDS],
+            opponents: BTreeMap::new(),
+        };
+        beliefs.sync(players);
+        beliefs
+    }
+
+    pub fn mode(&self) -> BeliefMode {
+        self.mode
+    }
+
+    // refreshes our own hand and everything that is public (revealed cards, who is alive)
+    pub fn sync(&mut self, players: &PlayerMap) {
+        self.revealed = [0; CARD_KINDS];
+
+        for (id, player) in players.all() {
+            let (hidden, revealed): (ArrayVec<Card, 2>, ArrayVec<Card, 2>) = match player {
+                Player::Alive(data) => match data.hand() {
+                    Hand::Full(c1, c2) => ([c1, c2].into(), ArrayVec::new()),
+                    Hand::Last { alive, dead } => {
+                        ([alive].into_iter().collect(), [dead].into_iter().collect())
+                    }
+                },
+                Player::Dead(data) => (ArrayVec::new(), data.revealed().into()),
+            };
+
+            for card in &revealed {
+                self.revealed[*card as usize] += 1;
+            }
+
+            if id == self.me {
+                self.own = hidden;
+            } else if hidden.is_empty() {
+                self.opponents.remove(&id);
+            } else {
+                let knowledge = self.opponents.entry(id).or_default();
+
+                // a card known to be held was the one they gave up
+                if knowledge.hidden == 2
+                    && hidden.len() == 1
+                    && let Some(index) = knowledge.known.iter().position(|c| revealed.contains(c))
+                {
+                    knowledge.known.remove(index);
+                }
+
+                knowledge.hidden = hidden.len();
+                knowledge.known.truncate(knowledge.hidden);
+            }
+        }
+    }
+
+    pub fn record_claim(&mut self, player: PlayerId, card: Card) {
+        if let Some(knowledge) = self.opponents.get_mut(&player) {
+            knowledge.claims[card as usize] += 1;
+        }
+    }
+
+    // player won a challenge on card, so they must still be holding it
+    pub fn record_proven(&mut self, player: PlayerId, card: Card) {
+        if let Some(knowledge) = self.opponents.get_mut(&player) {
+            if !knowledge.known.contains(&card) && knowledge.known.len() < knowledge.hidden {
+                knowledge.known.push(card);
+            }
+            knowledge.excluded[card as usize] = false;
+        }
+    }
+
+    // player lost a challenge on card, so none of their cards are that card
+    pub fn record_bluff(&mut self, player: PlayerId, card: Card) {
+        if let Some(knowledge) = self.opponents.get_mut(&player) {
+            knowledge.excluded[card as usize] = true;
+        }
+    }
+
+    // after an exchange, nothing learnt about the player's hand holds anymore
+    pub fn record_exchange(&mut self, player: PlayerId) {
+        if let Some(knowledge) = self.opponents.get_mut(&player) {
+            *knowledge = Knowledge {
+                hidden: knowledge.hidden,
+                ..Default::default()
+            };
+        }
+    }
+
+    // updates beliefs with a decision made through the driver, and the outcome that came from it
+    pub fn observe(&mut self, decision: &Decision, outcome: Option<&Outcome>, players: &PlayerMap) {
+        match decision {
+            Decision::Act(action) => {
+                if let Some(card) = action.claim() {
+                    self.record_claim(action.actor(), card);
+                }
+            }
+            Decision::Block(block) => self.record_claim(block.blocker(), block.claim()),
+            Decision::Challenge(challenge) => {
+                let card = challenge.kind().into();
+                if let Some(Outcome::LosesInfluence { victim }) = outcome {
+                    if *victim == challenge.challenger() {
+                        self.record_proven(challenge.actor(), card);
+                    } else {
+                        self.record_bluff(challenge.actor(), card);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(Outcome::ExchangesCards { actor }) = outcome {
+            self.record_exchange(*actor);
+        }
+
+        self.sync(players);
+    }
+
+    // cards that could still be in someone's hand (or the deck), from our point of view
+    fn unseen(&self) -> CardCounts {
+        let own = count_cards(&self.own);
+        let known = count_cards(self.opponents.values().flat_map(|k| &k.known));
+
+        let mut pool = [0; CARD_KINDS];
+        for card in Card::iter() {
+            let i = card as usize;
+            pool[i] = copies(card).saturating_sub(self.revealed[i] + own[i] + known[i]);
+        }
+        pool
+    }
+
+    // probability of each hand the opponent could be holding (empty if they aren't an opponent
+    // that is alive)
+    pub fn distribution(&self, opponent: PlayerId) -> Vec<HandBelief> {
+        let Some(knowledge) = self.opponents.get(&opponent) else {
+            return Vec::new();
+        };
+
+        let pool = self.unseen();
+        let unknown = knowledge.hidden - knowledge.known.len();
+
+        let mut beliefs: Vec<_> = hands_of(unknown)
+            .into_iter()
+            .filter_map(|drawn| {
+                let mut weight = ways(&pool, &count_cards(&drawn));
+
+                let mut cards = knowledge.known.clone();
+                cards.extend(drawn);
+                if cards.iter().any(|card| knowledge.excluded[*card as usize]) {
+                    return None;
+                }
+
+                if let BeliefMode::ClaimWeighted { weight: factor } = self.mode {
+                    for card in Card::iter().filter(|card| cards.contains(card)) {
+                        weight *= factor.powi(knowledge.claims[card as usize] as i32);
+                    }
+                }
+
+                (weight > 0.0).then_some(HandBelief {
+                    cards,
+                    probability: weight,
+                })
+            })
+            .collect();
+
+        let total: f64 = beliefs.iter().map(|belief| belief.probability).sum();
+        for belief in &mut beliefs {
+            belief.probability /= total;
+        }
+
+        beliefs
+    }
+
+    // probability that the opponent holds at least one copy of card
+    pub fn card_probability(&self, opponent: PlayerId, card: Card) -> f64 {
+        self.distribution(opponent)
+            .iter()
+            .filter(|belief| belief.cards.contains(&card))
+            .map(|belief| belief.probability)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opponent(hidden: usize) -> Knowledge {
+        Knowledge {
+            hidden,
+            ..Default::default()
+        }
+    }
+
+    fn beliefs(own: &[Card], revealed: &[Card], mode: BeliefMode) -> Beliefs {
+        Beliefs {
+            me: PlayerId::One,
+            mode,
+            own: own.iter().copied().collect(),
+            revealed: count_cards(revealed),
+            opponents: [(PlayerId::Two, opponent(2)), (PlayerId::Three, opponent(1))].into(),
+        }
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn distribution_is_normalized() {
+        let beliefs = beliefs(&[Card::Duke, Card::Captain], &[], BeliefMode::Exact);
+
+        for id in [PlayerId::Two, PlayerId::Three] {
+            let total: f64 = beliefs.distribution(id).iter().map(|b| b.probability).sum();
+            assert_close(total, 1.0);
+        }
+        assert!(beliefs.distribution(PlayerId::Four).is_empty());
+    }
+
+    #[test]
+    fn seen_cards_are_ruled_out() {
+        let beliefs = beliefs(&[Card::Duke, Card::Duke], &[Card::Duke], BeliefMode::Exact);
+
+        assert_close(beliefs.card_probability(PlayerId::Two, Card::Duke), 0.0);
+        assert!(beliefs.card_probability(PlayerId::Two, Card::Captain) > 0.0);
+    }
+
+    #[test]
+    fn exact_counting() {
+        // 13 unseen cards: 3 of each, except one Duke and one Captain
+        let beliefs = beliefs(&[Card::Duke, Card::Captain], &[], BeliefMode::Exact);
+
+        // single card is simply the share of copies left
+        assert_close(
+            beliefs.card_probability(PlayerId::Three, Card::Duke),
+            2.0 / 13.0,
+        );
+        assert_close(
+            beliefs.card_probability(PlayerId::Three, Card::Contessa),
+            3.0 / 13.0,
+        );
+
+        // two Contessas out of three: C(3, 2) / C(13, 2)
+        let both = beliefs
+            .distribution(PlayerId::Two)
+            .into_iter()
+            .find(|b| b.cards.as_slice() == [Card::Contessa, Card::Contessa])
+            .expect("Should be possible");
+        assert_close(both.probability, 3.0 / 78.0);
+    }
+
+    #[test]
+    fn challenges_update_knowledge() {
+        let mut beliefs = beliefs(&[], &[], BeliefMode::Exact);
+
+        beliefs.record_proven(PlayerId::Two, Card::Duke);
+        assert_close(beliefs.card_probability(PlayerId::Two, Card::Duke), 1.0);
+
+        beliefs.record_bluff(PlayerId::Three, Card::Captain);
+        assert_close(
+            beliefs.card_probability(PlayerId::Three, Card::Captain),
+            0.0,
+        );
+
+        // exchanging forgets everything
+        beliefs.record_exchange(PlayerId::Two);
+        assert!(beliefs.card_probability(PlayerId::Two, Card::Duke) < 1.0);
+    }
+
+    #[test]
+    fn claims_shift_weight() {
+        let exact = beliefs(&[], &[], BeliefMode::Exact);
+        let mut weighted = beliefs(&[], &[], BeliefMode::ClaimWeighted { weight: 3.0 });
+        weighted.record_claim(PlayerId::Three, Card::Assassin);
+
+        let before = exact.card_probability(PlayerId::Three, Card::Assassin);
+        let after = weighted.card_probability(PlayerId::Three, Card::Assassin);
+        assert!(after > before);
+
+        // claims don't matter when counting exactly
+        let mut exact = exact;
+        exact.record_claim(PlayerId::Three, Card::Assassin);
+        assert_close(
+            exact.card_probability(PlayerId::Three, Card::Assassin),
+            before,
+        );
+    }
+
+    #[test]
+    fn sync_with_game() {
+        use crate::machine::{CoupGame, WaitState};
+
+        let game = CoupGame::with_seed(["Dave", "Garry", "Steve"], 4);
+        let info = game.info();
+        let beliefs = Beliefs::new(PlayerId::One, BeliefMode::Exact, info.players);
+
+        let Player::Alive(me) = &info.players[PlayerId::One] else {
+            unreachable!("Everyone starts alive")
+        };
+        let Hand::Full(c1, c2) = me.hand() else {
+            unreachable!("Everyone starts with two cards")
+        };
+
+        assert_eq!(beliefs.own.as_slice(), [c1, c2]);
+        assert_eq!(beliefs.distribution(PlayerId::Two).len(), 15);
+    }
+}
@@ -11,6 +11,10 @@ impl Default for PlayerCoins {
 }
 
 impl PlayerCoins {
+    pub(crate) fn new(amount: u8) -> PlayerCoins {
+        PlayerCoins(amount)
+    }
+
     pub(crate) fn steal(mut self, mut thief: PlayerCoins) -> (PlayerCoins, PlayerCoins) {
         self.0 = self
             .0
@@ -27,20 +31,20 @@ impl PlayerCoins {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub(crate) enum Withdrawal {
     Income = 1,
     ForeignAid = 2,
     Tax = 3,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub(crate) enum Deposit {
     Assassinate = 3,
     Coup = 7,
 }
 
-const STARTING_COINS: u8 = 50;
+pub(crate) const STARTING_COINS: u8 = 50;
 
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -56,12 +60,16 @@ pub(crate) struct DepositError {
     amount_remaining: u8,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct CoinPile {
     coins: u8,
 }
 
 impl CoinPile {
+    pub(crate) fn new(coins: u8) -> CoinPile {
+        CoinPile { coins }
+    }
+
     pub(crate) fn with_count(
         player_count: u8,
     ) -> (CoinPile, impl IntoIterator<Item = PlayerCoins>) {
@@ -0,0 +1,227 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+// Commit–reveal for the seed a game is shuffled with, so that players can check their deals
+// weren't rigged:
+//
+// 1. before anyone joins, the server picks a random Secret and publishes its Commitment
+// 2. players can then add entropy of their own, which the server can't have known about when it
+//    committed
+// 3. the game is seeded with Reveal::seed, which mixes the secret with every piece of entropy
+// 4. once the game is over, the server publishes the Reveal. Anyone can check it against the
+//    Commitment, and play the game again from the seed (with GameBuilder::seed) to confirm the
+//    deal and every shuffle after it
+//
+// The secret is much larger than the seed, so the commitment can't be brute forced back into it
+// while the game is still going.
+
+const COMMITMENT_PREFIX: &[u8] = b"overthrow commitment";
+const SEED_PREFIX: &[u8] = b"overthrow seed";
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct Secret(
+    #[serde(with = "hex")]
+    #[schemars(with = "String")]
+    [u8; 32],
+);
+
+// hash of a Secret
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct Commitment(
+    #[serde(with = "hex")]
+    #[schemars(with = "String")]
+    [u8; 32],
+);
+
+// what the server publishes once the game is over, where entropy is in the order it was mixed in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Reveal {
+    pub secret: Secret,
+    pub entropy: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitmentMismatch;
+
+impl fmt::Display for CommitmentMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Revealed secret does not match the commitment")
+    }
+}
+
+impl std::error::Error for CommitmentMismatch {}
+
+impl Secret {
+    pub fn random() -> Secret {
+        Secret(rand::random())
+    }
+
+    pub fn commitment(&self) -> Commitment {
+        let hash = Sha256::new()
+            .chain_update(COMMITMENT_PREFIX)
+            .chain_update(self.0)
+            .finalize();
+        Commitment(hash.into())
+    }
+}
+
+// the secret stays out of logs until it is revealed
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(..)")
+    }
+}
+
+impl Reveal {
+    pub fn new(secret: Secret, entropy: Vec<String>) -> Reveal {
+        Reveal { secret, entropy }
+    }
+
+    // seed the game was played with
+    pub fn seed(&self) -> u64 {
+        let mut hasher = Sha256::new()
+            .chain_update(SEED_PREFIX)
+            .chain_update(self.secret.0);
+        // lengths go first, so entropy can't be moved from one piece to the next
+        for entropy in &self.entropy {
+            hasher.update((entropy.len() as u64).to_le_bytes());
+            hasher.update(entropy);
+        }
+
+        let hash = hasher.finalize();
+        u64::from_le_bytes(hash[..8].try_into().expect("Hash is 32 bytes"))
+    }
+
+    // seed the game was played with, as long as the secret is the one that was committed to
+    pub fn verify(&self, commitment: &Commitment) -> Result<u64, CommitmentMismatch> {
+        if self.secret.commitment() == *commitment {
+            Ok(self.seed())
+        } else {
+            Err(CommitmentMismatch)
+        }
+    }
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(&self.0))
+    }
+}
+
+impl FromStr for Commitment {
+    type Err = hex::InvalidHex;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        hex::decode(s).map(Commitment)
+    }
+}
+
+// both halves are hex strings when serialized, as they are meant to be copied around by people
+mod hex {
+    use serde::{Deserialize, Deserializer, Serializer, de};
+    use std::fmt::Write;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InvalidHex;
+
+    impl std::fmt::Display for InvalidHex {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "Expected 64 hex digits")
+        }
+    }
+
+    impl std::error::Error for InvalidHex {}
+
+    pub fn encode(bytes: &[u8; 32]) -> String {
+        bytes.iter().fold(String::with_capacity(64), |mut s, byte| {
+            let _ = write!(s, "{byte:02x}");
+            s
+        })
+    }
+
+    pub fn decode(s: &str) -> Result<[u8; 32], InvalidHex> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(InvalidHex);
+        }
+
+        let mut bytes = [0; 32];
+        for (byte, digits) in bytes.iter_mut().zip(s.as_bytes().chunks(2)) {
+            let digits = std::str::from_utf8(digits).map_err(|_| InvalidHex)?;
+            *byte = u8::from_str_radix(digits, 16).map_err(|_| InvalidHex)?;
+        }
+        Ok(bytes)
+    }
+
+    pub fn serialize<S: Serializer>(bytes: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+        let s = String::deserialize(deserializer)?;
+        decode(&s).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameBuilder;
+    use crate::machine::WaitState;
+
+    #[test]
+    fn reveal_is_verified() {
+        let secret = Secret::random();
+        let commitment = secret.commitment();
+        let reveal = Reveal::new(secret, vec!["dave".to_owned(), "garry".to_owned()]);
+        assert_eq!(reveal.verify(&commitment), Ok(reveal.seed()));
+
+        let other = Reveal::new(Secret::random(), reveal.entropy.clone());
+        assert_eq!(other.verify(&commitment), Err(CommitmentMismatch));
+    }
+
+    #[test]
+    fn entropy_changes_seed() {
+        let secret = Secret::random();
+        let seed = |entropy: &[&str]| {
+            Reveal::new(secret, entropy.iter().map(|e| e.to_string()).collect()).seed()
+        };
+
+        assert_ne!(seed(&[]), seed(&["dave"]));
+        assert_ne!(seed(&["dave", "garry"]), seed(&["garry", "dave"]));
+        assert_ne!(seed(&["dave", "garry"]), seed(&["daveg", "arry"]));
+    }
+
+    #[test]
+    fn deal_can_be_checked() {
+        let reveal = Reveal::new(Secret::random(), vec!["dave".to_owned()]);
+        let seed = reveal.verify(&reveal.secret.commitment()).unwrap();
+
+        let deal = |seed| {
+            let game = GameBuilder::new(["Dave", "Garry", "Maxine"])
+                .seed(seed)
+                .build();
+            let info = game.info();
+            let hands: Vec<_> = info
+                .players
+                .alive()
+                .map(|(_, p)| format!("{:?}", p.hand()))
+                .collect();
+            (hands, info.deck.to_vec())
+        };
+        assert_eq!(deal(seed), deal(reveal.seed()));
+    }
+
+    #[test]
+    fn commitments_roundtrip_as_hex() {
+        let commitment = Secret::random().commitment();
+        let hex = commitment.to_string();
+        assert_eq!(hex.len(), 64);
+        assert_eq!(hex.parse(), Ok(commitment));
+        assert!("not hex".parse::<Commitment>().is_err());
+    }
+}
@@ -0,0 +1,761 @@
+use crate::action::{Act, Action, Block, BlockableAct, Challenge, ChallengeableAct, ReactionStage};
+use crate::deck::{BlockStealClaim, Card, Hand};
+use crate::driver::{Decision, Phase};
+use crate::game::match_to_indices;
+use crate::player_map::{MAX_PLAYER_COUNT, Player};
+use crate::players::PlayerId;
+use crate::priority::ReactionWindow;
+use arrayvec::ArrayVec;
+
+// A fixed size, Copy version of the game for search, where states get copied far more often than
+// they get played. It follows the same rules as the engine, but leaves out everything search
+// doesn't need: names, claim history, observers and the options of each phase (which are generated
+// on demand without allocating). Shuffles use a small generator of their own, so after an exchange
+// the deck order differs from what the engine would have. Reaction windows follow the game's
+// ReactionWindow, but reaction priority isn't kept: a move is always made by the player it names.
+
+// 4 basic actions, and a steal, assassination and coup on each of 5 opponents
+pub const MAX_MOVES: usize = 19;
+
+pub type Moves = ArrayVec<CompactMove, MAX_MOVES>;
+
+// in the order of their discriminants
+const CARDS: [Card; 5] = [
+    Card::Ambassador,
+    Card::Contessa,
+    Card::Assassin,
+    Card::Duke,
+    Card::Captain,
+];
+
+const DECK_SIZE: usize = 15;
+
+fn seat(id: PlayerId) -> usize {
+    id as usize - 1
+}
+
+fn player(seat: u8) -> PlayerId {
+    PlayerId::iter()
+        .nth(seat as usize)
+        .expect("Seat should be valid")
+}
+
+// two cards in a byte: three bits each, then a bit for whether each of them is revealed. The
+// unrevealed card always comes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct PackedHand(u8);
+
+impl PackedHand {
+    const FIRST_REVEALED: u8 = 1 << 6;
+    const SECOND_REVEALED: u8 = 1 << 7;
+
+    fn full(first: Card, second: Card) -> PackedHand {
+        PackedHand(first as u8 | (second as u8) << 3)
+    }
+
+    fn last(alive: Card, dead: Card) -> PackedHand {
+        PackedHand(PackedHand::full(alive, dead).0 | PackedHand::SECOND_REVEALED)
+    }
+
+    fn dead([first, second]: [Card; 2]) -> PackedHand {
+        PackedHand(
+            PackedHand::full(first, second).0
+                | PackedHand::FIRST_REVEALED
+                | PackedHand::SECOND_REVEALED,
+        )
+    }
+
+    fn first(self) -> Card {
+        CARDS[(self.0 & 0b111) as usize]
+    }
+
+    fn second(self) -> Card {
+        CARDS[(self.0 >> 3 & 0b111) as usize]
+    }
+
+    fn influence(self) -> u8 {
+        2 - (self.0 >> 6).count_ones() as u8
+    }
+
+    fn has(self, card: Card) -> bool {
+        match self.influence() {
+            2 => self.first() == card || self.second() == card,
+            1 => self.first() == card,
+            _ => false,
+        }
+    }
+
+    fn hand(self) -> Option<Hand> {
+        match self.influence() {
+            2 => Some(Hand::Full(self.first(), self.second())),
+            1 => Some(Hand::Last {
+                alive: self.first(),
+                dead: self.second(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl From<&Player> for PackedHand {
+    fn from(player: &Player) -> PackedHand {
+        match player {
+            Player::Alive(data) => match data.hand {
+                Hand::Full(c1, c2) => PackedHand::full(c1, c2),
+                Hand::Last { alive, dead } => PackedHand::last(alive, dead),
+            },
+            Player::Dead(data) => PackedHand::dead(data.revealed),
+        }
+    }
+}
+
+// cards are drawn from the end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PackedDeck {
+    cards: [Card; DECK_SIZE],
+    len: u8,
+}
+
+impl PackedDeck {
+    fn new(cards: &[Card]) -> PackedDeck {
+        let mut deck = PackedDeck {
+            cards: [Card::Ambassador; DECK_SIZE],
+            len: cards.len() as u8,
+        };
+        deck.cards[..cards.len()].copy_from_slice(cards);
+        deck
+    }
+
+    fn cards(&self) -> &[Card] {
+        &self.cards[..self.len as usize]
+    }
+
+    // clears the slot it drew from, so that equal decks compare equal
+    fn draw(&mut self) -> Card {
+        self.len -= 1;
+        std::mem::replace(&mut self.cards[self.len as usize], Card::Ambassador)
+    }
+
+    fn push(&mut self, card: Card) {
+        self.cards[self.len as usize] = card;
+        self.len += 1;
+    }
+}
+
+// splitmix64, which is plenty for shuffling a handful of cards
+fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// where the game is waiting on a decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    // current player picks an action
+    Wait,
+    // current player's action can be challenged and/or blocked, or only one of them in a two-stage
+    // window
+    Respond(Act, ReactionStage),
+    // the action was blocked, and the block can be challenged
+    Blocked {
+        act: Act,
+        blocker: PlayerId,
+        claim: Card,
+    },
+    ChooseVictim(PlayerId),
+    // current player exchanges with one card left, or with both
+    ExchangeOne([Card; 3]),
+    ExchangeTwo([Card; 4]),
+    End(PlayerId),
+}
+
+// the compact version of a Decision, where the actor of an action and any claimant are left for
+// the state to fill in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactMove {
+    Act(Act),
+    Challenge(PlayerId),
+    Block(PlayerId, Card),
+    Pass,
+    ChooseVictim(Card),
+    ExchangeOne(Card),
+    ExchangeTwo([Card; 2]),
+}
+
+impl From<&Decision> for CompactMove {
+    fn from(decision: &Decision) -> CompactMove {
+        match decision {
+            Decision::Act(action) => CompactMove::Act(action.kind()),
+            Decision::Challenge(challenge) => CompactMove::Challenge(challenge.challenger()),
+            Decision::Block(block) => CompactMove::Block(block.blocker(), block.claim()),
+            Decision::Pass => CompactMove::Pass,
+            Decision::ChooseVictim(card) => CompactMove::ChooseVictim(*card),
+            Decision::ExchangeOne(card) => CompactMove::ExchangeOne(*card),
+            Decision::ExchangeTwo(cards) => CompactMove::ExchangeTwo(*cards),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactState {
+    hands: [PackedHand; MAX_PLAYER_COUNT],
+    coins: [u8; MAX_PLAYER_COUNT],
+    treasury: u8,
+    players: u8,
+    deck: PackedDeck,
+    // seats of alive players in turn order, and the index of the current player in it
+    order: [u8; MAX_PLAYER_COUNT],
+    alive: u8,
+    current: u8,
+    stage: Stage,
+    window: ReactionWindow,
+    rng: u64,
+}
+
+impl From<&Phase> for CompactState {
+    fn from(phase: &Phase) -> CompactState {
+        let info = phase.info();
+        let mut hands = [PackedHand::default(); MAX_PLAYER_COUNT];
+        let mut coins = [0; MAX_PLAYER_COUNT];
+        for (id, player) in info.players.all() {
+            hands[seat(id)] = PackedHand::from(player);
+            if let Player::Alive(data) = player {
+                coins[seat(id)] = data.coins.amount();
+            }
+        }
+
+        let mut order = [0; MAX_PLAYER_COUNT];
+        let mut alive = 0;
+        for id in info.players.turn_order() {
+            order[alive] = seat(id) as u8;
+            alive += 1;
+        }
+
+        let stage = match phase {
+            Phase::Wait(..) => Stage::Wait,
+            Phase::OnlyChallengeable(game) => {
+                Stage::Respond(game.state.kind.into(), ReactionStage::All)
+            }
+            Phase::OnlyBlockable(..) => Stage::Respond(Act::ForeignAid, ReactionStage::All),
+            Phase::Reactable(game) => Stage::Respond(game.state.kind.into(), game.stage()),
+            Phase::Block(game) => {
+                let (act, claim) = match game.state.kind {
+                    BlockableAct::ForeignAid => (Act::ForeignAid, Card::Duke),
+                    BlockableAct::Steal { victim, claim } => {
+                        (Act::Steal { victim }, (&claim).into())
+                    }
+                    BlockableAct::Assassinate { victim } => {
+                        (Act::Assassinate { victim }, Card::Contessa)
+                    }
+                };
+                Stage::Blocked {
+                    act,
+                    blocker: game.state.blocker,
+                    claim,
+                }
+            }
+            Phase::ChooseVictimCard(game) => Stage::ChooseVictim(game.state.victim),
+            Phase::ChooseOneFromThree(game) => Stage::ExchangeOne(game.state.choices),
+            Phase::ChooseTwoFromFour(game) => Stage::ExchangeTwo(game.state.choices),
+            Phase::End(game) => Stage::End(game.winner()),
+        };
+
+        CompactState {
+            hands,
+            coins,
+            treasury: info.coins_remaining,
+            players: info.players.count() as u8,
+            deck: PackedDeck::new(info.deck),
+            order,
+            alive: alive as u8,
+            current: 0,
+            stage,
+            window: phase.reaction_window(),
+            rng: info.seed,
+        }
+    }
+}
+
+impl CompactState {
+    // seeds the shuffles after exchanges, like when sampling different futures of the same state
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = seed;
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.players as usize
+    }
+
+    pub fn current_player(&self) -> PlayerId {
+        player(self.order[self.current as usize])
+    }
+
+    pub fn is_over(&self) -> bool {
+        matches!(self.stage, Stage::End(..))
+    }
+
+    pub fn winner(&self) -> Option<PlayerId> {
+        match self.stage {
+            Stage::End(winner) => Some(winner),
+            _ => None,
+        }
+    }
+
+    // None once the player is dead
+    pub fn hand(&self, id: PlayerId) -> Option<Hand> {
+        self.hands[seat(id)].hand()
+    }
+
+    pub fn coins(&self, id: PlayerId) -> u8 {
+        self.coins[seat(id)]
+    }
+
+    pub fn treasury(&self) -> u8 {
+        self.treasury
+    }
+
+    pub fn deck(&self) -> &[Card] {
+        self.deck.cards()
+    }
+
+    // alive players in turn order, starting from the current player
+    pub fn turn_order(&self) -> impl Iterator<Item = PlayerId> + '_ {
+        let order = &self.order[..self.alive as usize];
+        let (before, after) = order.split_at(self.current as usize);
+        after.iter().chain(before).map(|seat| player(*seat))
+    }
+
+    fn others(&self, id: PlayerId) -> impl Iterator<Item = PlayerId> + '_ {
+        self.turn_order().filter(move |other| *other != id)
+    }
+
+    // player who makes a move, where passing can be done by anyone in a reaction window
+    pub fn decider(&self, mv: CompactMove) -> Option<PlayerId> {
+        match (self.stage, mv) {
+            (_, CompactMove::Challenge(id) | CompactMove::Block(id, _)) => Some(id),
+            (_, CompactMove::Pass) => None,
+            (Stage::ChooseVictim(victim), _) => Some(victim),
+            _ => Some(self.current_player()),
+        }
+    }
+
+    // every legal move, including Pass in reaction windows
+    pub fn moves(&self) -> Moves {
+        let mut moves = Moves::new();
+        let current = self.current_player();
+        match self.stage {
+            Stage::Wait => {
+                let coins = self.coins(current);
+                moves.extend(
+                    [Act::ForeignAid, Act::Income, Act::Tax, Act::Exchange].map(CompactMove::Act),
+                );
+                for victim in self.others(current) {
+                    if self.coins(victim) >= 2 {
+                        moves.push(CompactMove::Act(Act::Steal { victim }));
+                    }
+                    if coins >= 3 {
+                        moves.push(CompactMove::Act(Act::Assassinate { victim }));
+                    }
+                    if coins >= 7 {
+                        moves.push(CompactMove::Act(Act::Coup { victim }));
+                    }
+                }
+            }
+            Stage::Respond(act, stage) => {
+                if act.claim().is_some() && stage != ReactionStage::Blocks {
+                    moves.extend(self.others(current).map(CompactMove::Challenge));
+                }
+                match act {
+                    // blocks only open up once the claim stands
+                    _ if stage == ReactionStage::Challenges => {}
+                    Act::ForeignAid => {
+                        let blocks = self.others(current).map(|id| (id, Card::Duke));
+                        moves.extend(blocks.map(|(id, card)| CompactMove::Block(id, card)));
+                    }
+                    Act::Steal { victim } => moves.extend([
+                        CompactMove::Block(victim, Card::Ambassador),
+                        CompactMove::Block(victim, Card::Captain),
+                    ]),
+                    Act::Assassinate { victim } => {
+                        moves.push(CompactMove::Block(victim, Card::Contessa))
+                    }
+                    _ => {}
+                }
+                moves.push(CompactMove::Pass);
+            }
+            Stage::Blocked { blocker, .. } => {
+                moves.extend(self.others(blocker).map(CompactMove::Challenge));
+                moves.push(CompactMove::Pass);
+            }
+            // duplicate cards are only offered once, like the engine does
+            Stage::ChooseVictim(victim) => {
+                let hand = self.hands[seat(victim)];
+                moves.push(CompactMove::ChooseVictim(hand.first()));
+                if hand.second() != hand.first() {
+                    moves.push(CompactMove::ChooseVictim(hand.second()));
+                }
+            }
+            Stage::ExchangeOne(choices) => {
+                for (i, card) in choices.into_iter().enumerate() {
+                    if !choices[..i].contains(&card) {
+                        moves.push(CompactMove::ExchangeOne(card));
+                    }
+                }
+            }
+            Stage::ExchangeTwo(choices) => {
+                for i in 0..choices.len() {
+                    for j in i + 1..choices.len() {
+                        let mut pair = [choices[i], choices[j]];
+                        pair.sort_by_key(|card| *card as u8);
+                        let pair = CompactMove::ExchangeTwo(pair);
+                        if !moves.contains(&pair) {
+                            moves.push(pair);
+                        }
+                    }
+                }
+            }
+            Stage::End(..) => {}
+        }
+        moves
+    }
+
+    // the engine's version of a move, to play a move that was found through search
+    pub fn decision(&self, mv: CompactMove) -> Decision {
+        let current = self.current_player();
+        match (self.stage, mv) {
+            (_, CompactMove::Act(act)) => Decision::Act(Action::new(current, act)),
+            (Stage::Respond(act, _), CompactMove::Challenge(challenger)) => {
+                let kind = match act {
+                    Act::Tax => ChallengeableAct::Tax,
+                    Act::Exchange => ChallengeableAct::Exchange,
+                    Act::Steal { victim } => ChallengeableAct::Steal { victim },
+                    Act::Assassinate { victim } => ChallengeableAct::Assassinate { victim },
+                    _ => unreachable!("Only claims can be challenged"),
+                };
+                Decision::Challenge(Challenge {
+                    actor: current,
+                    challenger,
+                    kind,
+                })
+            }
+            (Stage::Blocked { blocker, claim, .. }, CompactMove::Challenge(challenger)) => {
+                let kind = match claim {
+                    Card::Duke => ChallengeableAct::BlockForeignAid,
+                    Card::Contessa => ChallengeableAct::BlockAssassination,
+                    Card::Ambassador => ChallengeableAct::BlockSteal {
+                        claim: BlockStealClaim::Ambassador,
+                    },
+                    _ => ChallengeableAct::BlockSteal {
+                        claim: BlockStealClaim::Captain,
+                    },
+                };
+                Decision::Challenge(Challenge {
+                    actor: blocker,
+                    challenger,
+                    kind,
+                })
+            }
+            (_, CompactMove::Block(blocker, claim)) => {
+                let kind = match claim {
+                    Card::Duke => BlockableAct::ForeignAid,
+                    Card::Contessa => BlockableAct::Assassinate { victim: blocker },
+                    Card::Ambassador => BlockableAct::Steal {
+                        victim: blocker,
+                        claim: BlockStealClaim::Ambassador,
+                    },
+                    _ => BlockableAct::Steal {
+                        victim: blocker,
+                        claim: BlockStealClaim::Captain,
+                    },
+                };
+                Decision::Block(Block {
+                    actor: current,
+                    blocker,
+                    kind,
+                })
+            }
+            (_, CompactMove::Pass) => Decision::Pass,
+            (_, CompactMove::ChooseVictim(card)) => Decision::ChooseVictim(card),
+            (_, CompactMove::ExchangeOne(card)) => Decision::ExchangeOne(card),
+            (_, CompactMove::ExchangeTwo(cards)) => Decision::ExchangeTwo(cards),
+            (_, CompactMove::Challenge(..)) => unreachable!("Nothing to challenge"),
+        }
+    }
+
+    // applies a move, which has to be one of moves()
+    pub fn apply(&mut self, mv: CompactMove) {
+        debug_assert!(self.moves().contains(&mv), "Move is not legal: {mv:?}");
+        let current = self.current_player();
+        match (self.stage, mv) {
+            (Stage::Wait, CompactMove::Act(act)) => match act {
+                Act::Income => {
+                    self.withdraw(current, 1);
+                    self.end_turn();
+                }
+                Act::Coup { victim } => {
+                    self.spend(current, 7);
+                    self.lose_influence(victim);
+                }
+                act @ (Act::Steal { .. } | Act::Assassinate { .. })
+                    if self.window == ReactionWindow::TwoStage =>
+                {
+                    self.stage = Stage::Respond(act, ReactionStage::Challenges)
+                }
+                act => self.stage = Stage::Respond(act, ReactionStage::All),
+            },
+            // the claim stands, so blocks open up
+            (Stage::Respond(act, ReactionStage::Challenges), CompactMove::Pass) => {
+                self.stage = Stage::Respond(act, ReactionStage::Blocks)
+            }
+            (Stage::Respond(act, _), CompactMove::Pass) => self.resolve(act),
+            (Stage::Respond(act, _), CompactMove::Challenge(challenger)) => {
+                let claim = act.claim().expect("Only claims can be challenged");
+                self.challenge(current, challenger, claim);
+            }
+            (Stage::Respond(act, _), CompactMove::Block(blocker, claim)) => {
+                self.stage = Stage::Blocked {
+                    act,
+                    blocker,
+                    claim,
+                }
+            }
+            (Stage::Blocked { act, .. }, CompactMove::Pass) => {
+                if let Act::Assassinate { .. } = act {
+                    self.spend(current, 3);
+                }
+                self.end_turn();
+            }
+            (Stage::Blocked { blocker, claim, .. }, CompactMove::Challenge(challenger)) => {
+                self.challenge(blocker, challenger, claim);
+            }
+            (Stage::ChooseVictim(victim), CompactMove::ChooseVictim(card)) => {
+                let hand = self.hands[seat(victim)];
+                let alive = if hand.first() == card {
+                    hand.second()
+                } else {
+                    hand.first()
+                };
+                self.hands[seat(victim)] = PackedHand::last(alive, card);
+                self.end_turn();
+            }
+            (Stage::ExchangeOne(choices), CompactMove::ExchangeOne(card)) => {
+                let [index] = match_to_indices([card], choices).expect("Choice should be valid");
+                let dead = self.hands[seat(current)].second();
+                self.hands[seat(current)] = PackedHand::last(card, dead);
+                self.return_cards(choices, &[index]);
+                self.end_turn();
+            }
+            (Stage::ExchangeTwo(choices), CompactMove::ExchangeTwo(cards @ [c1, c2])) => {
+                let indices = match_to_indices(cards, choices).expect("Choices should be valid");
+                self.hands[seat(current)] = PackedHand::full(c1, c2);
+                self.return_cards(choices, &indices);
+                self.end_turn();
+            }
+            (stage, mv) => unreachable!("Move {mv:?} can't be made in {stage:?}"),
+        }
+    }
+
+    // an action that nobody reacted to goes through
+    fn resolve(&mut self, act: Act) {
+        let current = self.current_player();
+        match act {
+            Act::ForeignAid => {
+                self.withdraw(current, 2);
+                self.end_turn();
+            }
+            Act::Tax => {
+                self.withdraw(current, 3);
+                self.end_turn();
+            }
+            Act::Exchange => {
+                let drawn = [self.deck.draw(), self.deck.draw()];
+                let hand = self.hands[seat(current)];
+                self.stage = match hand.influence() {
+                    2 => Stage::ExchangeTwo([drawn[0], drawn[1], hand.first(), hand.second()]),
+                    _ => Stage::ExchangeOne([drawn[0], drawn[1], hand.first()]),
+                };
+            }
+            Act::Steal { victim } => {
+                self.coins[seat(victim)] -= 2;
+                self.coins[seat(current)] += 2;
+                self.end_turn();
+            }
+            Act::Assassinate { victim } => {
+                self.spend(current, 3);
+                self.lose_influence(victim);
+            }
+            Act::Income | Act::Coup { .. } => unreachable!("Can't be reacted to"),
+        }
+    }
+
+    fn withdraw(&mut self, id: PlayerId, amount: u8) {
+        self.treasury -= amount;
+        self.coins[seat(id)] += amount;
+    }
+
+    fn spend(&mut self, id: PlayerId, amount: u8) {
+        self.coins[seat(id)] -= amount;
+        self.treasury += amount;
+    }
+
+    fn challenge(&mut self, claimant: PlayerId, challenger: PlayerId, claim: Card) {
+        if self.hands[seat(claimant)].has(claim) {
+            self.lose_influence(challenger);
+        } else {
+            self.lose_influence(claimant);
+        }
+    }
+
+    fn lose_influence(&mut self, victim: PlayerId) {
+        if self.hands[seat(victim)].influence() == 2 {
+            self.stage = Stage::ChooseVictim(victim);
+        } else {
+            self.kill(victim);
+        }
+    }
+
+    fn kill(&mut self, victim: PlayerId) {
+        let hand = self.hands[seat(victim)];
+        self.hands[seat(victim)] = PackedHand::dead([hand.first(), hand.second()]);
+        self.treasury += self.coins[seat(victim)];
+        self.coins[seat(victim)] = 0;
+
+        // same as the engine's turn order: step back once so that ending the turn lands on the
+        // player after the current one
+        let index = self.order[..self.alive as usize]
+            .iter()
+            .position(|seat| *seat == self::seat(victim) as u8)
+            .expect("Victim should be alive");
+        self.order
+            .copy_within(index + 1..self.alive as usize, index);
+        self.alive -= 1;
+        self.order[self.alive as usize] = 0;
+        if index <= self.current as usize {
+            self.current = (self.current + self.alive - 1) % self.alive;
+        }
+
+        if self.alive == 1 {
+            self.stage = Stage::End(player(self.order[0]));
+        } else {
+            self.end_turn();
+        }
+    }
+
+    fn end_turn(&mut self) {
+        self.current = (self.current + 1) % self.alive;
+        self.stage = Stage::Wait;
+    }
+
+    // puts every choice but the kept ones back into the deck, then shuffles it
+    fn return_cards<const N: usize, const K: usize>(
+        &mut self,
+        choices: [Card; N],
+        kept: &[usize; K],
+    ) {
+        for (index, card) in choices.into_iter().enumerate() {
+            if !kept.contains(&index) {
+                self.deck.push(card);
+            }
+        }
+
+        let len = self.deck.len as usize;
+        for i in (1..len).rev() {
+            let j = (next_random(&mut self.rng) % (i as u64 + 1)) as usize;
+            self.deck.cards.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameBuilder;
+    use rand::SeedableRng;
+    use rand::seq::IteratorRandom;
+    use rand_chacha::ChaCha8Rng;
+
+    // leaves out what is expected to differ between the engine and compact state, which is how
+    // shuffles turn out, and where the turn order starts
+    fn normalized(mut state: CompactState) -> CompactState {
+        state.rng = 0;
+        state.order[..state.alive as usize].rotate_left(state.current as usize);
+        state.current = 0;
+        state.deck.cards[..state.deck.len as usize].sort_by_key(|card| *card as u8);
+        state
+    }
+
+    #[test]
+    fn state_is_small() {
+        assert!(std::mem::size_of::<CompactState>() <= 64);
+    }
+
+    #[test]
+    fn hands_roundtrip() {
+        for (c1, c2) in CARDS.into_iter().zip(CARDS.into_iter().rev()) {
+            assert!(
+                matches!(PackedHand::full(c1, c2).hand(), Some(Hand::Full(a, b)) if a == c1 && b == c2)
+            );
+            assert!(matches!(
+                PackedHand::last(c1, c2).hand(),
+                Some(Hand::Last { alive, dead }) if alive == c1 && dead == c2
+            ));
+            assert!(PackedHand::dead([c1, c2]).hand().is_none());
+        }
+    }
+
+    // plays random games through both the engine and compact state, which have to agree on every
+    // option and every state along the way, with either kind of reaction window
+    #[test]
+    fn follows_engine() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        for seed in 0..100 {
+            let players = 2 + seed as usize % 5;
+            let names = (1..=players).map(|n| format!("Player {n}"));
+            let window = if seed % 2 == 0 {
+                ReactionWindow::Simultaneous
+            } else {
+                ReactionWindow::TwoStage
+            };
+            let game = GameBuilder::new(names)
+                .seed(seed)
+                .reaction_window(window)
+                .build();
+            let mut phase = Phase::from(game);
+
+            loop {
+                let state = CompactState::from(&phase);
+                let mut options: Vec<_> = phase.options();
+                let mut moves: Vec<_> = state
+                    .moves()
+                    .into_iter()
+                    .map(|mv| state.decision(mv))
+                    .collect();
+                options.sort_by_key(|d| format!("{d:?}"));
+                moves.sort_by_key(|d| format!("{d:?}"));
+                assert_eq!(moves, options);
+
+                let Some(decision) = phase.options().into_iter().choose(&mut rng) else {
+                    break;
+                };
+                let mut next = state;
+                next.apply(CompactMove::from(&decision));
+                phase = phase
+                    .decide(decision)
+                    .expect("Options are always valid")
+                    .phase;
+
+                assert_eq!(normalized(next), normalized(CompactState::from(&phase)));
+            }
+
+            assert!(phase.is_over());
+        }
+    }
+}
@@ -1,25 +1,39 @@
 use arrayvec::ArrayVec;
-#[allow(unused_imports)]
 use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
 
 use crate::player_map::MAX_PLAYER_COUNT;
 
 use super::players::PlayerId;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(super) struct CurrentPlayer {
     order: ArrayVec<PlayerId, MAX_PLAYER_COUNT>,
     current: usize,
 }
 
 impl CurrentPlayer {
-    pub(crate) fn new(player_count: usize) -> CurrentPlayer {
+    pub(crate) fn new(player_count: usize, rng: &mut ChaCha8Rng) -> CurrentPlayer {
         let mut order: ArrayVec<_, _> = PlayerId::iter().take(player_count).collect();
-        order[..].shuffle(&mut rand::thread_rng());
+        order[..].shuffle(rng);
 
         CurrentPlayer { order, current: 0 }
     }
 
+    // alive players take turns in seat order, starting from current
+    pub(crate) fn starting_at(
+        alive: impl IntoIterator<Item = PlayerId>,
+        current: PlayerId,
+    ) -> CurrentPlayer {
+        let order: ArrayVec<_, _> = alive.into_iter().collect();
+        let current = order
+            .iter()
+            .position(|id| *id == current)
+            .expect("Current player should be alive");
+
+        CurrentPlayer { order, current }
+    }
+
     pub(crate) fn current(&self) -> PlayerId {
         self.order[self.current]
     }
@@ -28,8 +42,10 @@ impl CurrentPlayer {
         self.current = (self.current + 1) % self.order.len();
     }
 
-    pub(crate) fn order(&self) -> impl Iterator<Item = PlayerId> {
-        self.order.iter().copied()
+    // turn order of alive players, starting from the current player
+    pub(crate) fn order_from_current(&self) -> impl Iterator<Item = PlayerId> {
+        let (before, after) = self.order.split_at(self.current);
+        after.iter().chain(before).copied()
     }
 
     pub(crate) fn kill(&mut self, player_id: PlayerId) {
@@ -40,20 +56,62 @@ impl CurrentPlayer {
             .find_map(|(index, id)| (player_id == *id).then_some(index))
             .expect("Player ID should be valid");
 
-        let current_player = self.current();
+        self.order.remove(index);
 
-        // killed player won't have another turn, so it'll go to next player
-        if current_player == player_id {
-            self.end_turn();
+        // the turn always ends after a player is killed, so we step back once to land on the
+        // player after the current one (or after the killed player, if they were the current
+        // player and won't have another turn)
+        if index <= self.current {
+            self.current = (self.current + self.order.len() - 1) % self.order.len();
         }
-        self.order.remove(index);
+    }
+}
 
-        // now we have to fix the index
-        self.current = self
-            .order
-            .iter()
-            .enumerate()
-            .find_map(|(index, id)| (*id == current_player).then_some(index))
-            .expect("Player ID should be valid");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn ordered(count: usize) -> CurrentPlayer {
+        let order = PlayerId::iter().take(count).collect();
+        CurrentPlayer { order, current: 0 }
+    }
+
+    #[test]
+    fn kill_current_player() {
+        let mut current = ordered(3);
+        current.kill(PlayerId::One);
+        current.end_turn();
+
+        assert_eq!(current.current(), PlayerId::Two);
+    }
+
+    #[test]
+    fn kill_last_in_order() {
+        let mut current = ordered(3);
+        current.end_turn();
+        current.end_turn();
+        current.kill(PlayerId::Three);
+        current.end_turn();
+
+        assert_eq!(current.current(), PlayerId::One);
+    }
+
+    #[test]
+    fn kill_other_player() {
+        let mut current = ordered(4);
+        current.end_turn();
+        current.kill(PlayerId::One);
+        current.end_turn();
+
+        assert_eq!(current.current(), PlayerId::Three);
+    }
+
+    #[test]
+    fn seeded_order() {
+        let first = CurrentPlayer::new(6, &mut ChaCha8Rng::seed_from_u64(7));
+        let second = CurrentPlayer::new(6, &mut ChaCha8Rng::seed_from_u64(7));
+
+        assert!(first.order_from_current().eq(second.order_from_current()));
     }
 }
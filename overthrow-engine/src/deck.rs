@@ -4,12 +4,13 @@ use std::fmt::Display;
 
 use itermore::IterArrayChunks;
 use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use subenum::subenum;
 
 // standard starting deck
-const STARTING_DECK: [Card; 15] = [
+pub(crate) const STARTING_DECK: [Card; 15] = [
     Card::Ambassador,
     Card::Ambassador,
     Card::Ambassador,
@@ -52,6 +53,13 @@ impl Display for Card {
     }
 }
 
+impl Card {
+    pub fn iter() -> impl Iterator<Item = Card> {
+        use Card::*;
+        [Ambassador, Contessa, Assassin, Duke, Captain].into_iter()
+    }
+}
+
 impl From<&BlockStealClaim> for Card {
     fn from(value: &BlockStealClaim) -> Self {
         match value {
@@ -68,7 +76,7 @@ pub enum Hand {
 }
 
 impl Hand {
-    pub(crate) fn has_card(&self, card: Card) -> bool {
+    pub fn has_card(&self, card: Card) -> bool {
         match self {
             Hand::Full(c1, c2) => *c1 == card || *c2 == card,
             Hand::Last { alive, .. } => *alive == card,
@@ -82,9 +90,9 @@ pub struct Deck {
 }
 
 impl Deck {
-    pub(crate) fn with_count(player_count: u8) -> (Deck, Vec<Hand>) {
+    pub(crate) fn with_count(player_count: u8, rng: &mut ChaCha8Rng) -> (Deck, Vec<Hand>) {
         let mut deck: Vec<Card> = STARTING_DECK.into();
-        deck.shuffle(&mut rand::thread_rng());
+        deck.shuffle(rng);
 
         let cards_left = deck.len() - (2 * player_count) as usize;
 
@@ -97,8 +105,13 @@ impl Deck {
         (Deck { deck }, hands)
     }
 
-    pub(crate) fn shuffle(&mut self) {
-        self.deck.shuffle(&mut rand::thread_rng());
+    // cards are drawn from the end
+    pub(crate) fn from_cards(deck: Vec<Card>) -> Deck {
+        Deck { deck }
+    }
+
+    pub(crate) fn shuffle(&mut self, rng: &mut ChaCha8Rng) {
+        self.deck.shuffle(rng);
     }
 
     // cards remaining in pile
@@ -110,8 +123,8 @@ impl Deck {
         [self.deck.pop(), self.deck.pop()].map(|card| card.expect("Deck should have cards left"))
     }
 
-    pub(crate) fn return_cards(&mut self, cards: &[Card]) {
+    pub(crate) fn return_cards(&mut self, cards: &[Card], rng: &mut ChaCha8Rng) {
         self.deck.extend_from_slice(cards);
-        self.shuffle();
+        self.shuffle(rng);
     }
 }
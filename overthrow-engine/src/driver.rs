@@ -0,0 +1,610 @@
+use crate::action::{self, Action, Reaction, ReactionStage};
+use crate::deck::Card;
+use crate::machine::*;
+use crate::match_to_indices;
+use crate::players::PlayerId;
+use crate::priority::ReactionWindow;
+use itertools::Itertools;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// A decision that moves the game from one decision point to the next. Reaction windows (blocks
+// and challenges) are resolved as a whole: either one player reacts, or everyone passes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Decision {
+    Act(Action),
+    Challenge(action::Challenge),
+    Block(action::Block),
+    Pass,
+    ChooseVictim(Card),
+    ExchangeOne(Card),
+    ExchangeTwo([Card; 2]),
+}
+
+impl From<Reaction> for Decision {
+    fn from(reaction: Reaction) -> Self {
+        match reaction {
+            Reaction::Challenge(challenge) => Decision::Challenge(challenge),
+            Reaction::Block(block) => Decision::Block(block),
+        }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::Act(action) => write!(f, "Player {}: {}", action.actor(), action.kind()),
+            Decision::Challenge(challenge) => write!(
+                f,
+                "Player {} challenges: {}",
+                challenge.challenger(),
+                challenge.kind()
+            ),
+            Decision::Block(block) => write!(
+                f,
+                "Player {} blocks as {}: {}",
+                block.blocker(),
+                block.claim(),
+                block.kind()
+            ),
+            Decision::Pass => write!(f, "Pass"),
+            Decision::ChooseVictim(card) => write!(f, "Lose {card}"),
+            Decision::ExchangeOne(card) => write!(f, "Keep {card}"),
+            Decision::ExchangeTwo([c1, c2]) => write!(f, "Keep {c1} and {c2}"),
+        }
+    }
+}
+
+// Every state of the typestate machine that is waiting on a decision (plus the end of the game).
+// Safe actions and challenges resolve on their own, so they never show up here
+#[derive(Debug)]
+pub enum Phase {
+    Wait(CoupGame<Wait>),
+    OnlyChallengeable(CoupGame<OnlyChallengeable>),
+    OnlyBlockable(CoupGame<OnlyBlockable>),
+    Reactable(CoupGame<Reactable>),
+    Block(CoupGame<Block>),
+    ChooseVictimCard(CoupGame<ChooseVictimCard>),
+    ChooseOneFromThree(CoupGame<ChooseOneFromThree>),
+    ChooseTwoFromFour(CoupGame<ChooseTwoFromFour>),
+    End(CoupGame<End>),
+}
+
+// result of a successful decision
+#[derive(Debug)]
+pub struct Step {
+    pub phase: Phase,
+    // set whenever the decision settled an action, block or challenge
+    pub outcome: Option<Outcome>,
+}
+
+impl Step {
+    fn pending(phase: Phase) -> Step {
+        Step {
+            phase,
+            outcome: None,
+        }
+    }
+
+    fn resolved(phase: impl Into<Phase>, outcome: Outcome) -> Step {
+        Step {
+            phase: phase.into(),
+            outcome: Some(outcome),
+        }
+    }
+}
+
+// a decision that was applied to a game, along with what it resolved to
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Record {
+    pub decision: Decision,
+    pub outcome: Option<Outcome>,
+}
+
+// returned when a decision is not one of the options of the current phase, the phase is handed back
+// untouched
+#[derive(Debug)]
+pub struct InvalidDecision {
+    pub phase: Phase,
+    pub decision: Decision,
+}
+
+impl fmt::Display for InvalidDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Decision is not valid in this phase: {}", self.decision)
+    }
+}
+
+impl std::error::Error for InvalidDecision {}
+
+impl From<GameState> for Phase {
+    fn from(state: GameState) -> Self {
+        match state {
+            GameState::Wait(game) => Phase::Wait(game),
+            GameState::ChooseVictimCard(game) => Phase::ChooseVictimCard(game),
+            GameState::ChooseOneFromThree(game) => Phase::ChooseOneFromThree(game),
+            GameState::ChooseTwoFromFour(game) => Phase::ChooseTwoFromFour(game),
+            GameState::End(game) => Phase::End(game),
+        }
+    }
+}
+
+impl From<CoupGame<Wait>> for Phase {
+    fn from(game: CoupGame<Wait>) -> Self {
+        Phase::Wait(game)
+    }
+}
+
+impl Phase {
+    pub fn info(&self) -> GameInfo<'_> {
+        match self {
+            Phase::Wait(game) => game.game_info(),
+            Phase::OnlyChallengeable(game) => game.game_info(),
+            Phase::OnlyBlockable(game) => game.game_info(),
+            Phase::Reactable(game) => game.game_info(),
+            Phase::Block(game) => game.game_info(),
+            Phase::ChooseVictimCard(game) => game.game_info(),
+            Phase::ChooseOneFromThree(game) => game.game_info(),
+            Phase::ChooseTwoFromFour(game) => game.game_info(),
+            Phase::End(game) => game.game_info(),
+        }
+    }
+
+    pub fn reaction_window(&self) -> ReactionWindow {
+        match self {
+            Phase::Wait(game) => game.reaction_window(),
+            Phase::OnlyChallengeable(game) => game.reaction_window(),
+            Phase::OnlyBlockable(game) => game.reaction_window(),
+            Phase::Reactable(game) => game.reaction_window(),
+            Phase::Block(game) => game.reaction_window(),
+            Phase::ChooseVictimCard(game) => game.reaction_window(),
+            Phase::ChooseOneFromThree(game) => game.reaction_window(),
+            Phase::ChooseTwoFromFour(game) => game.reaction_window(),
+            Phase::End(game) => game.reaction_window(),
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        matches!(self, Phase::End(..))
+    }
+
+    // decisions that can be taken back, which needs a game built with an undo limit
+    pub fn undo_depth(&self) -> usize {
+        match self {
+            Phase::Wait(game) => game.undo_depth(),
+            Phase::OnlyChallengeable(game) => game.undo_depth(),
+            Phase::OnlyBlockable(game) => game.undo_depth(),
+            Phase::Reactable(game) => game.undo_depth(),
+            Phase::Block(game) => game.undo_depth(),
+            Phase::ChooseVictimCard(game) => game.undo_depth(),
+            Phase::ChooseOneFromThree(game) => game.undo_depth(),
+            Phase::ChooseTwoFromFour(game) => game.undo_depth(),
+            Phase::End(game) => game.undo_depth(),
+        }
+    }
+
+    // takes back the last decision, or gives the phase back unchanged if there is none to take
+    // back
+    pub fn undo(self) -> Result<Phase, Phase> {
+        match self {
+            Phase::Wait(game) => game.undo().map_err(Phase::Wait),
+            Phase::OnlyChallengeable(game) => game.undo().map_err(Phase::OnlyChallengeable),
+            Phase::OnlyBlockable(game) => game.undo().map_err(Phase::OnlyBlockable),
+            Phase::Reactable(game) => game.undo().map_err(Phase::Reactable),
+            Phase::Block(game) => game.undo().map_err(Phase::Block),
+            Phase::ChooseVictimCard(game) => game.undo().map_err(Phase::ChooseVictimCard),
+            Phase::ChooseOneFromThree(game) => game.undo().map_err(Phase::ChooseOneFromThree),
+            Phase::ChooseTwoFromFour(game) => game.undo().map_err(Phase::ChooseTwoFromFour),
+            Phase::End(game) => game.undo().map_err(Phase::End),
+        }
+    }
+
+    fn checkpoint(&mut self) {
+        match self {
+            Phase::Wait(game) => game.checkpoint(),
+            Phase::OnlyChallengeable(game) => game.checkpoint(),
+            Phase::OnlyBlockable(game) => game.checkpoint(),
+            Phase::Reactable(game) => game.checkpoint(),
+            Phase::Block(game) => game.checkpoint(),
+            Phase::ChooseVictimCard(game) => game.checkpoint(),
+            Phase::ChooseOneFromThree(game) => game.checkpoint(),
+            Phase::ChooseTwoFromFour(game) => game.checkpoint(),
+            Phase::End(..) => {}
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Wait(..) => "Wait",
+            Phase::OnlyChallengeable(..) => "OnlyChallengeable",
+            Phase::OnlyBlockable(..) => "OnlyBlockable",
+            Phase::Reactable(..) => "Reactable",
+            Phase::Block(..) => "Block",
+            Phase::ChooseVictimCard(..) => "ChooseVictimCard",
+            Phase::ChooseOneFromThree(..) => "ChooseOneFromThree",
+            Phase::ChooseTwoFromFour(..) => "ChooseTwoFromFour",
+            Phase::End(..) => "End",
+        }
+    }
+
+    // all decisions that can be made from this phase. Reaction windows always include Pass
+    pub fn options(&self) -> Vec<Decision> {
+        use Decision as D;
+        let reactions = |reactions: Vec<Decision>| {
+            reactions
+                .into_iter()
+                .chain([D::Pass])
+                .collect::<Vec<Decision>>()
+        };
+
+        match self {
+            Phase::Wait(game) => game.actions().all().cloned().map(D::Act).collect(),
+            Phase::OnlyChallengeable(game) => reactions(
+                game.challenges()
+                    .all()
+                    .values()
+                    .sorted_by_key(|c| c.challenger())
+                    .cloned()
+                    .map(D::Challenge)
+                    .collect(),
+            ),
+            Phase::OnlyBlockable(game) => reactions(
+                game.blocks()
+                    .all()
+                    .values()
+                    .sorted_by_key(|b| b.blocker())
+                    .cloned()
+                    .map(D::Block)
+                    .collect(),
+            ),
+            Phase::Reactable(game) => reactions(
+                game.reactions()
+                    .all()
+                    .into_iter()
+                    .sorted_by_key(|(id, _)| *id)
+                    .flat_map(|(_, reactions)| reactions)
+                    .map(D::from)
+                    .collect(),
+            ),
+            Phase::Block(game) => reactions(
+                game.challenges()
+                    .all()
+                    .values()
+                    .sorted_by_key(|c| c.challenger())
+                    .cloned()
+                    .map(D::Challenge)
+                    .collect(),
+            ),
+            Phase::ChooseVictimCard(game) => game
+                .choices()
+                .into_iter()
+                .unique()
+                .map(D::ChooseVictim)
+                .collect(),
+            Phase::ChooseOneFromThree(game) => game
+                .choices()
+                .into_iter()
+                .unique()
+                .map(D::ExchangeOne)
+                .collect(),
+            Phase::ChooseTwoFromFour(game) => game
+                .choices()
+                .into_iter()
+                .tuple_combinations()
+                .map(|(c1, c2)| {
+                    // keep pairs in a consistent order so duplicates are removed
+                    let mut pair = [c1, c2];
+                    pair.sort_by_key(|card| *card as u8);
+                    pair
+                })
+                .unique()
+                .map(D::ExchangeTwo)
+                .collect(),
+            Phase::End(..) => Vec::new(),
+        }
+    }
+
+    // options that belong to a single player. Pass is never included, as passing is implied by
+    // not choosing any of them
+    pub fn options_for(&self, player: PlayerId) -> Vec<Decision> {
+        self.options()
+            .into_iter()
+            .filter(|decision| self.decider(decision) == Some(player))
+            .collect()
+    }
+
+    // the player who would be making this decision (None for passing a reaction window)
+    pub fn decider(&self, decision: &Decision) -> Option<PlayerId> {
+        match decision {
+            Decision::Act(action) => Some(action.actor()),
+            Decision::Challenge(challenge) => Some(challenge.challenger()),
+            Decision::Block(block) => Some(block.blocker()),
+            Decision::Pass => None,
+            Decision::ChooseVictim(..) | Decision::ExchangeOne(..) | Decision::ExchangeTwo(..) => {
+                match self {
+                    Phase::ChooseVictimCard(game) => Some(game.victim()),
+                    Phase::ChooseOneFromThree(game) => Some(game.actor()),
+                    Phase::ChooseTwoFromFour(game) => Some(game.actor()),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    // blocks and challenges can come from several players at once, everything else has one decider
+    pub fn is_reaction_window(&self) -> bool {
+        matches!(
+            self,
+            Phase::OnlyChallengeable(..)
+                | Phase::OnlyBlockable(..)
+                | Phase::Reactable(..)
+                | Phase::Block(..)
+        )
+    }
+
+    // players who can make a decision in this phase, in turn order starting from the current
+    // player. Empty once the game is over
+    pub fn deciders(&self) -> Vec<PlayerId> {
+        match self {
+            Phase::Wait(game) => game.deciders(),
+            Phase::OnlyChallengeable(game) => game.deciders(),
+            Phase::OnlyBlockable(game) => game.deciders(),
+            Phase::Reactable(game) => game.deciders(),
+            Phase::Block(game) => game.deciders(),
+            Phase::ChooseVictimCard(game) => game.deciders(),
+            Phase::ChooseOneFromThree(game) => game.deciders(),
+            Phase::ChooseTwoFromFour(game) => game.deciders(),
+            Phase::End(game) => game.deciders(),
+        }
+    }
+
+    fn accepts(&self, decision: &Decision) -> bool {
+        match (self, decision) {
+            // order of kept cards doesn't matter
+            (Phase::ChooseTwoFromFour(game), Decision::ExchangeTwo(cards)) => {
+                match_to_indices(*cards, game.choices()).is_some()
+            }
+            _ => self.options().contains(decision),
+        }
+    }
+
+    // applies decision, moving on to the next decision point. Valid decisions are checkpointed
+    // first, so they can be undone
+    pub fn decide(mut self, decision: Decision) -> Result<Step, InvalidDecision> {
+        if !self.accepts(&decision) {
+            return Err(InvalidDecision {
+                phase: self,
+                decision,
+            });
+        }
+        self.checkpoint();
+
+        use Decision as D;
+        let step = match (self, decision) {
+            (Phase::Wait(game), D::Act(action)) => match game.play(action) {
+                ActionKind::Safe(game) => {
+                    let outcome = game.outcome();
+                    Step::resolved(game.advance(), outcome)
+                }
+                ActionKind::OnlyChallengeable(game) => {
+                    Step::pending(Phase::OnlyChallengeable(game))
+                }
+                ActionKind::OnlyBlockable(game) => Step::pending(Phase::OnlyBlockable(game)),
+                ActionKind::Reactable(game) => Step::pending(Phase::Reactable(game)),
+            },
+            (Phase::OnlyChallengeable(game), D::Challenge(challenge)) => {
+                resolve_challenge(game.challenge(challenge))
+            }
+            (Phase::OnlyChallengeable(game), D::Pass) => {
+                let outcome = game.outcome();
+                Step::resolved(game.advance(), outcome)
+            }
+            (Phase::OnlyBlockable(game), D::Block(block)) => {
+                Step::pending(Phase::Block(game.block(block)))
+            }
+            (Phase::OnlyBlockable(game), D::Pass) => {
+                let outcome = game.outcome();
+                Step::resolved(game.advance(), outcome)
+            }
+            (Phase::Reactable(game), D::Challenge(challenge)) => {
+                resolve_challenge(game.challenge(challenge))
+            }
+            (Phase::Reactable(game), D::Block(block)) => {
+                Step::pending(Phase::Block(game.block(block)))
+            }
+            // in two stages, nobody challenging only moves on to blocks
+            (Phase::Reactable(game), D::Pass) if game.stage() == ReactionStage::Challenges => {
+                Step::pending(Phase::Reactable(game.open_blocks()))
+            }
+            (Phase::Reactable(game), D::Pass) => {
+                let outcome = game.outcome();
+                Step::resolved(game.advance(), outcome)
+            }
+            (Phase::Block(game), D::Challenge(challenge)) => {
+                resolve_challenge(game.challenge(challenge))
+            }
+            (Phase::Block(game), D::Pass) => {
+                let outcome = game.outcome();
+                Step::resolved(game.advance(), outcome)
+            }
+            (Phase::ChooseVictimCard(game), D::ChooseVictim(card)) => {
+                Step::pending(Phase::Wait(game.advance(card)))
+            }
+            (Phase::ChooseOneFromThree(game), D::ExchangeOne(card)) => {
+                Step::pending(Phase::Wait(game.advance(card)))
+            }
+            (Phase::ChooseTwoFromFour(game), D::ExchangeTwo(cards)) => {
+                Step::pending(Phase::Wait(game.advance(cards)))
+            }
+            _ => unreachable!("Decision was checked against the phase's options"),
+        };
+
+        Ok(step)
+    }
+}
+
+fn resolve_challenge(game: CoupGame<Challenge>) -> Step {
+    let outcome = game.outcome();
+    Step::resolved(game.advance(), outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Act;
+
+    // plays a game where the first option is always taken
+    fn play_first_options(seed: u64, players: usize) -> Phase {
+        let names = (1..=players).map(|n| format!("Player {n}"));
+        let mut phase = Phase::from(CoupGame::with_seed(names, seed));
+
+        for _ in 0..1000 {
+            let Some(decision) = phase.options().into_iter().next() else {
+                break;
+            };
+            phase = phase
+                .decide(decision)
+                .expect("Options are always valid")
+                .phase;
+        }
+
+        phase
+    }
+
+    #[test]
+    fn first_options_finish() {
+        for players in 2..=6 {
+            let phase = play_first_options(3, players);
+            assert!(phase.is_over());
+        }
+    }
+
+    #[test]
+    fn deciders_match_options() {
+        for seed in 0..20 {
+            let names = ["Dave", "Garry", "Steve", "Maxine"];
+            let mut phase = Phase::from(CoupGame::with_seed(names, seed));
+            for _ in 0..1000 {
+                let Some(decision) = phase.options().into_iter().next() else {
+                    break;
+                };
+                let options = phase.options();
+                let expected: Vec<_> = phase
+                    .info()
+                    .players
+                    .turn_order()
+                    .filter(|id| options.iter().any(|d| phase.decider(d) == Some(*id)))
+                    .collect();
+                assert_eq!(phase.deciders(), expected, "{}", phase.name());
+
+                phase = phase.decide(decision).expect("Options are valid").phase;
+            }
+            assert!(phase.deciders().is_empty());
+        }
+    }
+
+    #[test]
+    fn invalid_decision_is_rejected() {
+        let phase = Phase::from(CoupGame::with_seed(["Dave", "Garry"], 1));
+        let Err(InvalidDecision { phase, .. }) = phase.decide(Decision::Pass) else {
+            panic!("Can't pass on choosing an action")
+        };
+
+        assert!(matches!(phase, Phase::Wait(..)));
+    }
+
+    #[test]
+    fn safe_action_resolves() {
+        let phase = Phase::from(CoupGame::with_seed(["Dave", "Garry"], 1));
+        let income = phase
+            .options()
+            .into_iter()
+            .find(|d| matches!(d, Decision::Act(action) if action.kind() == Act::Income))
+            .expect("Income is always possible");
+
+        let step = phase.decide(income).expect("Income is valid");
+        assert!(matches!(step.phase, Phase::Wait(..)));
+        assert!(matches!(
+            step.outcome,
+            Some(Outcome::GainCoins { amount: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn reaction_options_belong_to_players() {
+        let phase = Phase::from(CoupGame::with_seed(["Dave", "Garry", "Steve"], 1));
+        let actor = phase.info().current_player;
+        let tax = phase
+            .options()
+            .into_iter()
+            .find(|d| matches!(d, Decision::Act(action) if action.kind() == Act::Tax))
+            .expect("Tax is always possible");
+        let phase = phase.decide(tax).expect("Tax is valid").phase;
+
+        assert!(phase.options_for(actor).is_empty());
+        assert!(phase.options().contains(&Decision::Pass));
+        for id in PlayerId::iter().take(3).filter(|id| *id != actor) {
+            assert_eq!(phase.options_for(id).len(), 1);
+        }
+    }
+
+    #[test]
+    fn two_stage_reactions_challenge_first() {
+        use crate::GameBuilder;
+        use crate::priority::ReactionWindow;
+
+        let phase = Phase::from(
+            GameBuilder::new(["Dave", "Garry", "Steve"])
+                .seed(1)
+                .reaction_window(ReactionWindow::TwoStage)
+                .build(),
+        );
+        let steal = phase
+            .options()
+            .into_iter()
+            .find(|d| matches!(d, Decision::Act(action) if matches!(action.kind(), Act::Steal { .. })))
+            .expect("Everyone starts with coins to steal");
+        let Decision::Act(action) = &steal else {
+            unreachable!()
+        };
+        let Act::Steal { victim } = action.kind() else {
+            unreachable!()
+        };
+
+        let phase = phase.decide(steal).expect("Steal is valid").phase;
+        let Phase::Reactable(game) = &phase else {
+            panic!("Steals can be reacted to")
+        };
+        assert_eq!(game.stage(), ReactionStage::Challenges);
+        let options = phase.options();
+        assert!(
+            options
+                .iter()
+                .all(|d| matches!(d, Decision::Challenge(..) | Decision::Pass))
+        );
+        assert_eq!(options.len(), 3);
+
+        // nobody challenged, so only the victim can block now
+        let step = phase.decide(Decision::Pass).expect("Passing is valid");
+        assert!(step.outcome.is_none());
+        let Phase::Reactable(game) = &step.phase else {
+            panic!("Steal still needs to be blocked or let through")
+        };
+        assert_eq!(game.stage(), ReactionStage::Blocks);
+        assert_eq!(step.phase.deciders(), vec![victim]);
+        assert!(
+            step.phase
+                .options()
+                .iter()
+                .all(|d| matches!(d, Decision::Block(..) | Decision::Pass))
+        );
+
+        let step = step.phase.decide(Decision::Pass).expect("Passing is valid");
+        assert!(matches!(step.phase, Phase::Wait(..)));
+        assert!(matches!(
+            step.outcome,
+            Some(Outcome::LoseCoins { amount: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,587 @@
+use crate::action::Act;
+use crate::deck::{Card, Hand};
+use crate::driver::{Decision, Phase};
+use crate::machine::{
+    ChooseOneFromThreeState, ChooseTwoFromFourState, CoupGame, Outcome, WaitState,
+};
+use crate::player_map::{Player, PlayerMap};
+use crate::players::PlayerId;
+use std::collections::VecDeque;
+use std::fmt;
+
+// Fixed action space shared by every seat. Victims are given relative to the seat making the
+// decision (1 is the next player in seat order), so the same index means the same thing from
+// every seat:
+//
+// 0..4     Income, ForeignAid, Tax, Exchange
+// 4..9     Steal from victim 1..=5
+// 9..14    Assassinate victim 1..=5
+// 14..19   Coup victim 1..=5
+// 19       Challenge
+// 20..25   Block, claiming card
+// 25       Pass
+// 26..31   Lose card (ChooseVictimCard)
+// 31..36   Keep card (ChooseOneFromThree)
+// 36..51   Keep pair of cards (ChooseTwoFromFour)
+pub const ACTION_COUNT: usize = 51;
+
+const MAX_PLAYERS: usize = 6;
+const CARD_KINDS: usize = 5;
+const VICTIM_SLOTS: usize = MAX_PLAYERS - 1;
+
+const STEAL: usize = 4;
+const ASSASSINATE: usize = STEAL + VICTIM_SLOTS;
+const COUP: usize = ASSASSINATE + VICTIM_SLOTS;
+const CHALLENGE: usize = COUP + VICTIM_SLOTS;
+const BLOCK: usize = CHALLENGE + 1;
+const PASS: usize = BLOCK + CARD_KINDS;
+const CHOOSE_VICTIM: usize = PASS + 1;
+const EXCHANGE_ONE: usize = CHOOSE_VICTIM + CARD_KINDS;
+const EXCHANGE_TWO: usize = EXCHANGE_ONE + CARD_KINDS;
+
+const PHASES: usize = 9;
+// present, alive, coins, hidden cards, revealed cards
+const SEAT_SIZE: usize = 4 + CARD_KINDS;
+const HISTORY_ENTRY_SIZE: usize = MAX_PLAYERS + ACTION_COUNT;
+
+// seat of other relative to me, 0 being me
+fn offset(me: PlayerId, other: PlayerId, players: usize) -> usize {
+    (other as usize + players - me as usize) % players
+}
+
+fn pair_index([c1, c2]: [Card; 2]) -> usize {
+    let (low, high) = if (c1 as usize) <= (c2 as usize) {
+        (c1 as usize, c2 as usize)
+    } else {
+        (c2 as usize, c1 as usize)
+    };
+    // pairs before low, plus distance from low
+    (0..low).map(|i| CARD_KINDS - i).sum::<usize>() + (high - low)
+}
+
+// index of decision in the action space, as seen from me
+pub fn action_index(me: PlayerId, players: usize, decision: &Decision) -> usize {
+    match decision {
+        Decision::Act(action) => match action.kind() {
+            Act::Income => 0,
+            Act::ForeignAid => 1,
+            Act::Tax => 2,
+            Act::Exchange => 3,
+            Act::Steal { victim } => STEAL + offset(me, victim, players) - 1,
+            Act::Assassinate { victim } => ASSASSINATE + offset(me, victim, players) - 1,
+            Act::Coup { victim } => COUP + offset(me, victim, players) - 1,
+        },
+        Decision::Challenge(..) => CHALLENGE,
+        Decision::Block(block) => BLOCK + block.claim() as usize,
+        Decision::Pass => PASS,
+        Decision::ChooseVictim(card) => CHOOSE_VICTIM + *card as usize,
+        Decision::ExchangeOne(card) => EXCHANGE_ONE + *card as usize,
+        Decision::ExchangeTwo(cards) => EXCHANGE_TWO + pair_index(*cards),
+    }
+}
+
+// exchanges happen in private, so other seats only get to see that they happened
+fn is_private(action: usize) -> bool {
+    action >= EXCHANGE_ONE
+}
+
+fn phase_index(phase: &Phase) -> usize {
+    match phase {
+        Phase::Wait(..) => 0,
+        Phase::OnlyChallengeable(..) => 1,
+        Phase::OnlyBlockable(..) => 2,
+        Phase::Reactable(..) => 3,
+        Phase::Block(..) => 4,
+        Phase::ChooseVictimCard(..) => 5,
+        Phase::ChooseOneFromThree(..) => 6,
+        Phase::ChooseTwoFromFour(..) => 7,
+        Phase::End(..) => 8,
+    }
+}
+
+fn hidden_cards(player: &Player) -> usize {
+    match player {
+        Player::Alive(data) => match data.hand() {
+            Hand::Full(..) => 2,
+            Hand::Last { .. } => 1,
+        },
+        Player::Dead(..) => 0,
+    }
+}
+
+// card counts, scaled down by scale
+fn cards(cards: impl IntoIterator<Item = Card>, scale: f32) -> [f32; CARD_KINDS] {
+    let mut counts = [0.0; CARD_KINDS];
+    for card in cards {
+        counts[card as usize] += 1.0 / scale;
+    }
+    counts
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rewards {
+    // given to the last player standing
+    pub win: f32,
+    // given to a player when they are eliminated
+    pub loss: f32,
+    // given to a player for every card they lose (including the last one)
+    pub lose_influence: f32,
+    // given to the player that made a decision, every time they make one
+    pub step: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Rewards {
+            win: 1.0,
+            loss: -1.0,
+            lose_influence: 0.0,
+            step: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvConfig {
+    pub players: usize,
+    pub rewards: Rewards,
+    // how many of the most recent decisions are part of observations
+    pub history: usize,
+    // episodes are truncated after this many turns
+    pub max_turns: u32,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        EnvConfig {
+            players: 2,
+            rewards: Rewards::default(),
+            history: 8,
+            max_turns: 500,
+        }
+    }
+}
+
+impl EnvConfig {
+    // length of every observation vector
+    pub fn observation_size(&self) -> usize {
+        // own hand, exchange choices, seats, current player, deck, treasury, phase, to act
+        CARD_KINDS * 2
+            + SEAT_SIZE * MAX_PLAYERS
+            + MAX_PLAYERS
+            + 2
+            + PHASES
+            + 1
+            + HISTORY_ENTRY_SIZE * self.history
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Transition {
+    // indexed by seat (PlayerId::One is 0), for every seat in the game
+    pub rewards: Vec<f32>,
+    // game is over
+    pub done: bool,
+    // game went on for too long
+    pub truncated: bool,
+    // seat that acts next (None once done)
+    pub next: Option<PlayerId>,
+    pub outcome: Option<Outcome>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IllegalAction {
+    pub seat: Option<PlayerId>,
+    pub action: usize,
+}
+
+impl fmt::Display for IllegalAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.seat {
+            Some(seat) => write!(f, "Action {} is not legal for player {seat}", self.action),
+            None => write!(f, "Action {} was taken after the game ended", self.action),
+        }
+    }
+}
+
+impl std::error::Error for IllegalAction {}
+
+// Environment for training agents, built on top of the driver. Every decision is made by a single
+// seat: reaction windows ask each player that can react in turn order, and the window closes once
+// one of them reacts or all of them pass
+#[derive(Debug)]
+pub struct Env {
+    config: EnvConfig,
+    phase: Option<Phase>,
+    // seats still to decide in the current phase, front is next
+    waiting: VecDeque<PlayerId>,
+    history: VecDeque<(PlayerId, usize)>,
+    turns: u32,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig, seed: u64) -> Env {
+        assert!((2..=MAX_PLAYERS).contains(&config.players));
+
+        let mut env = Env {
+            config,
+            phase: None,
+            waiting: VecDeque::new(),
+            history: VecDeque::new(),
+            turns: 0,
+        };
+        env.reset(seed);
+        env
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.config
+    }
+
+    // starts a new game, returning the first seat to act
+    pub fn reset(&mut self, seed: u64) -> PlayerId {
+        let names = (1..=self.config.players).map(|seat| format!("Player {seat}"));
+        let phase = Phase::from(CoupGame::with_seed(names, seed));
+
+        self.waiting = phase.deciders().into();
+        self.phase = Some(phase);
+        self.history.clear();
+        self.turns = 0;
+
+        self.current()
+            .expect("New games always have a player to act")
+    }
+
+    fn phase(&self) -> &Phase {
+        self.phase
+            .as_ref()
+            .expect("Phase is only taken while deciding")
+    }
+
+    fn players(&self) -> &PlayerMap {
+        self.phase().info().players
+    }
+
+    // the seat that has to act next
+    pub fn current(&self) -> Option<PlayerId> {
+        self.waiting.front().copied()
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.phase(), Phase::End(..))
+    }
+
+    // decisions available to the current seat, along with their index
+    fn legal(&self) -> Vec<(usize, Decision)> {
+        let Some(seat) = self.current() else {
+            return Vec::new();
+        };
+
+        let phase = self.phase();
+        let mut options = phase.options_for(seat);
+        if phase.is_reaction_window() {
+            options.push(Decision::Pass);
+        }
+
+        options
+            .into_iter()
+            .map(|decision| {
+                let index = action_index(seat, self.config.players, &decision);
+                (index, decision)
+            })
+            .collect()
+    }
+
+    // legal actions for the current seat (all false once the game is over)
+    pub fn action_mask(&self) -> [bool; ACTION_COUNT] {
+        let mut mask = [false; ACTION_COUNT];
+        for (index, _) in self.legal() {
+            mask[index] = true;
+        }
+        mask
+    }
+
+    pub fn step(&mut self, action: usize) -> Result<Transition, IllegalAction> {
+        let seat = self.current();
+        let decision = self
+            .legal()
+            .into_iter()
+            .find_map(|(index, decision)| (index == action).then_some(decision))
+            .ok_or(IllegalAction { seat, action })?;
+        let seat = seat.expect("Only the current seat has legal actions");
+
+        if self.history.len() == self.config.history {
+            self.history.pop_front();
+        }
+        if self.config.history > 0 {
+            self.history.push_back((seat, action));
+        }
+
+        let mut rewards = vec![0.0; self.config.players];
+        rewards[seat as usize - 1] += self.config.rewards.step;
+
+        // passing only closes the window once everyone has passed
+        self.waiting.pop_front();
+        if decision == Decision::Pass && !self.waiting.is_empty() {
+            return Ok(Transition {
+                rewards,
+                done: false,
+                truncated: false,
+                next: self.current(),
+                outcome: None,
+            });
+        }
+
+        if matches!(decision, Decision::Act(..)) {
+            self.turns += 1;
+        }
+
+        let hidden_before: Vec<_> = self.players().all().map(|(_, p)| hidden_cards(p)).collect();
+
+        let phase = self
+            .phase
+            .take()
+            .expect("Phase is only taken while deciding");
+        let step = phase
+            .decide(decision)
+            .expect("Decision was one of the phase's options");
+        self.waiting = step.phase.deciders().into();
+        self.phase = Some(step.phase);
+
+        let Rewards {
+            win,
+            loss,
+            lose_influence,
+            ..
+        } = self.config.rewards;
+        let players = self.players().all().zip(hidden_before).enumerate();
+        for (index, ((_, player), before)) in players {
+            let after = hidden_cards(player);
+            rewards[index] += lose_influence * (before - after) as f32;
+            if before > 0 && after == 0 {
+                rewards[index] += loss;
+            }
+        }
+
+        let done = self.is_done();
+        if done && let Some(winner) = self.players().game_over() {
+            rewards[winner as usize - 1] += win;
+        }
+
+        Ok(Transition {
+            rewards,
+            done,
+            truncated: !done && self.turns >= self.config.max_turns,
+            next: self.current(),
+            outcome: step.outcome,
+        })
+    }
+
+    // fixed size encoding of everything seat is allowed to know
+    pub fn observe(&self, seat: PlayerId) -> Vec<f32> {
+        let phase = self.phase();
+        let info = phase.info();
+        let players = self.config.players;
+
+        let mut obs = Vec::with_capacity(self.config.observation_size());
+        // own hidden cards
+        let own = match &info.players[seat] {
+            Player::Alive(data) => match data.hand() {
+                Hand::Full(c1, c2) => cards([c1, c2], 2.0),
+                Hand::Last { alive, .. } => cards([alive], 2.0),
+            },
+            Player::Dead(..) => [0.0; CARD_KINDS],
+        };
+        obs.extend(own);
+
+        // cards to pick from while exchanging
+        let choices = match phase {
+            Phase::ChooseOneFromThree(game) if game.actor() == seat => cards(game.choices(), 4.0),
+            Phase::ChooseTwoFromFour(game) if game.actor() == seat => cards(game.choices(), 4.0),
+            _ => [0.0; CARD_KINDS],
+        };
+        obs.extend(choices);
+
+        // every seat, starting with our own
+        for relative in 0..MAX_PLAYERS {
+            if relative >= players {
+                obs.extend([0.0; SEAT_SIZE]);
+                continue;
+            }
+
+            let id = (seat as usize - 1 + relative) % players;
+            let (_, player) = info
+                .players
+                .all()
+                .nth(id)
+                .expect("Seat is within the player count");
+
+            let (alive, coins, revealed) = match player {
+                Player::Alive(data) => {
+                    let revealed = match data.hand() {
+                        Hand::Full(..) => [0.0; CARD_KINDS],
+                        Hand::Last { dead, .. } => cards([dead], 2.0),
+                    };
+                    (1.0, data.coins().amount() as f32 / 12.0, revealed)
+                }
+                Player::Dead(data) => (0.0, 0.0, cards(data.revealed(), 2.0)),
+            };
+
+            obs.extend([1.0, alive, coins, hidden_cards(player) as f32 / 2.0]);
+            obs.extend(revealed);
+        }
+
+        let mut current = [0.0; MAX_PLAYERS];
+        current[offset(seat, info.current_player, players)] = 1.0;
+        obs.extend(current);
+
+        obs.push(info.deck.len() as f32 / 15.0);
+        obs.push(info.coins_remaining as f32 / 50.0);
+
+        let mut phases = [0.0; PHASES];
+        phases[phase_index(phase)] = 1.0;
+        obs.extend(phases);
+
+        obs.push((self.current() == Some(seat)) as u8 as f32);
+
+        // most recent decisions, padded with zeroes at the start
+        for _ in self.history.len()..self.config.history {
+            obs.extend([0.0; HISTORY_ENTRY_SIZE]);
+        }
+        for (decider, action) in &self.history {
+            let mut entry = [0.0; HISTORY_ENTRY_SIZE];
+            entry[offset(seat, *decider, players)] = 1.0;
+            // victims stay relative to the decider, whose seat is part of the entry
+            if *decider == seat || !is_private(*action) {
+                entry[MAX_PLAYERS + *action] = 1.0;
+            }
+            obs.extend(entry);
+        }
+
+        obs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+    use rand::SeedableRng;
+    use rand::seq::IteratorRandom;
+    use rand_chacha::ChaCha8Rng;
+
+    fn config(players: usize) -> EnvConfig {
+        EnvConfig {
+            players,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pairs_fill_their_range() {
+        let indices: Vec<_> = Card::iter()
+            .collect_vec()
+            .into_iter()
+            .tuple_combinations()
+            .map(|(c1, c2)| [c1, c2])
+            .chain(Card::iter().map(|card| [card, card]))
+            .map(pair_index)
+            .sorted()
+            .collect();
+
+        assert_eq!(indices, (0..15).collect::<Vec<_>>());
+        assert_eq!(EXCHANGE_TWO + 15, ACTION_COUNT);
+    }
+
+    #[test]
+    fn random_games_finish() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        for players in 2..=6 {
+            let mut env = Env::new(config(players), players as u64);
+            let size = env.config().observation_size();
+            let mut total = vec![0.0; players];
+
+            loop {
+                let seat = env.current().expect("Game isn't over yet");
+                for id in PlayerId::iter().take(players) {
+                    assert_eq!(env.observe(id).len(), size);
+                }
+
+                let mask = env.action_mask();
+                let action = (0..ACTION_COUNT)
+                    .filter(|i| mask[*i])
+                    .choose(&mut rng)
+                    .expect("Current seat always has an option");
+                let transition = env.step(action).expect("Action was legal");
+
+                for (total, reward) in total.iter_mut().zip(&transition.rewards) {
+                    *total += reward;
+                }
+                if transition.done || transition.truncated {
+                    break;
+                }
+                assert_ne!(transition.next, None, "{seat} left nobody to act");
+            }
+
+            if env.is_done() {
+                assert_eq!(env.current(), None);
+                assert_eq!(env.action_mask(), [false; ACTION_COUNT]);
+                // one winner, everyone else lost
+                assert_eq!(total.iter().sum::<f32>(), 1.0 - (players - 1) as f32);
+            }
+        }
+    }
+
+    #[test]
+    fn legal_actions_have_distinct_indices() {
+        let mut env = Env::new(config(4), 3);
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+
+        while !env.is_done() {
+            let legal = env.legal();
+            assert!(legal.iter().map(|(index, _)| index).all_unique());
+
+            let (action, _) = legal.into_iter().choose(&mut rng).unwrap();
+            if env.step(action).unwrap().truncated {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn reactions_follow_turn_order() {
+        let mut env = Env::new(config(3), 1);
+        let first = env.current().unwrap();
+
+        // tax can only be challenged, by everyone else in turn order
+        let transition = env.step(2).unwrap();
+        let order: Vec<_> = env.players().turn_order().skip(1).collect();
+        assert_eq!(transition.next, Some(order[0]));
+        assert!(env.action_mask()[CHALLENGE] && env.action_mask()[PASS]);
+
+        env.step(PASS).unwrap();
+        assert_eq!(env.current(), Some(order[1]));
+
+        // last pass resolves the tax
+        let transition = env.step(PASS).unwrap();
+        assert!(matches!(
+            transition.outcome,
+            Some(Outcome::GainCoins { actor, amount: 3 }) if actor == first
+        ));
+    }
+
+    #[test]
+    fn illegal_actions_are_rejected() {
+        let mut env = Env::new(config(2), 5);
+        let seat = env.current();
+
+        // nobody can pass on their own turn
+        assert_eq!(
+            env.step(PASS).unwrap_err(),
+            IllegalAction { seat, action: PASS }
+        );
+        assert!(env.step(ACTION_COUNT).is_err());
+        assert_eq!(env.current(), seat);
+    }
+}
@@ -1,4 +1,7 @@
-use crate::player_map::PlayerMap;
+use crate::observer::{Observer, Observers};
+use crate::player_map::{ChallengeResult, PlayerMap};
+use crate::priority::{ReactionPriority, ReactionWindow};
+use crate::undo::UndoStack;
 
 use super::action::{
     self, BlockableAct, OnlyChallengeableAct, PossibleActions, PossibleBlocks, PossibleChallenges,
@@ -7,27 +10,36 @@ use super::action::{
 use super::coins::{Deposit, Withdrawal};
 use super::deck::Hand;
 
-use super::action::{Act, Action};
+use super::action::{Act, Action, Reaction, ReactionStage};
 use super::coins::CoinPile;
 use super::deck::{Card, Deck};
 use super::machine::*;
 use itertools::{Itertools, izip};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 
-impl WaitState for CoupGame<Wait> {
-    fn with_count(count: usize) -> CoupGame<Wait> {
-        assert!((2..=6).contains(&count));
-        let players = vec![String::new(); count];
-        let count = count as u8;
-        let (deck, hands) = Deck::with_count(count);
-        let (coins, player_coins) = CoinPile::with_count(count);
+impl CoupGame<Wait> {
+    fn new(names: Vec<String>, seed: u64, observers: Observers) -> CoupGame<Wait> {
+        assert!((2..=6).contains(&names.len()));
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let player_count = names.len() as u8;
+
+        let (deck, hands) = Deck::with_count(player_count, &mut rng);
+        let (coins, player_coins) = CoinPile::with_count(player_count);
 
         // compile initial player data
-        let data = izip!(players, player_coins, hands);
-        let players = PlayerMap::new(data);
+        let data = izip!(names, player_coins, hands);
+        let players = PlayerMap::new(data, &mut rng);
         let data = Box::new(CoupData {
             players,
             coins,
             deck,
+            seed,
+            rng,
+            observers,
+            undo: UndoStack::default(),
+            priority: ReactionPriority::default(),
+            window: ReactionWindow::default(),
         });
 
         let possible_actions = data
@@ -39,49 +51,113 @@ impl WaitState for CoupGame<Wait> {
             state: Wait { possible_actions },
         }
     }
+}
 
-    fn with_player_names<T>(players: T) -> CoupGame<Wait>
+// builds a game with observers registered before anything happens in it. Without a seed, a random
+// one is used
+#[derive(Debug)]
+pub struct GameBuilder {
+    names: Vec<String>,
+    seed: Option<u64>,
+    observers: Observers,
+    undo_limit: usize,
+    priority: ReactionPriority,
+    window: ReactionWindow,
+}
+
+impl GameBuilder {
+    pub fn new<T>(players: T) -> GameBuilder
     where
         T: IntoIterator,
         T::Item: AsRef<str>,
     {
-        let names: Vec<_> = players
-            .into_iter()
-            .map(|name| name.as_ref().to_owned())
-            .collect();
+        GameBuilder {
+            names: to_names(players),
+            seed: None,
+            observers: Observers::default(),
+            undo_limit: 0,
+            priority: ReactionPriority::default(),
+            window: ReactionWindow::default(),
+        }
+    }
 
-        assert!((2..=6).contains(&names.len()));
-        let player_count = names.len() as u8;
+    pub fn seed(mut self, seed: u64) -> GameBuilder {
+        self.seed = Some(seed);
+        self
+    }
 
-        let (deck, hands) = Deck::with_count(player_count);
-        let (coins, player_coins) = CoinPile::with_count(player_count);
+    // observers are told about transitions in the order they were registered
+    pub fn observer(mut self, observer: impl Observer + 'static) -> GameBuilder {
+        self.observers.push(Box::new(observer));
+        self
+    }
 
-        // compile initial player data
-        let data = izip!(names, player_coins, hands);
-        let players = PlayerMap::new(data);
-        let data = Box::new(CoupData {
-            players,
-            coins,
-            deck,
-        });
+    // checkpoints that can be undone (see CoupGame::undo). Without one, games can't be undone
+    pub fn undo_limit(mut self, limit: usize) -> GameBuilder {
+        self.undo_limit = limit;
+        self
+    }
 
-        let possible_actions = data
-            .players
-            .generate_actions_for(data.players.current_player());
+    // how simultaneous reactions are picked between (see CoupGame::resolve)
+    pub fn reaction_priority(mut self, priority: ReactionPriority) -> GameBuilder {
+        self.priority = priority;
+        self
+    }
 
-        CoupGame {
-            data,
-            state: Wait { possible_actions },
-        }
+    // whether blocks and challenges are taken together, or challenges first
+    pub fn reaction_window(mut self, window: ReactionWindow) -> GameBuilder {
+        self.window = window;
+        self
+    }
+
+    pub fn build(self) -> CoupGame<Wait> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut game = CoupGame::new(self.names, seed, self.observers);
+        game.data.undo = UndoStack::new(self.undo_limit);
+        game.data.priority = self.priority;
+        game.data.window = self.window;
+        game
+    }
+}
+
+fn to_names<T>(players: T) -> Vec<String>
+where
+    T: IntoIterator,
+    T::Item: AsRef<str>,
+{
+    players
+        .into_iter()
+        .map(|name| name.as_ref().to_owned())
+        .collect()
+}
+
+impl WaitState for CoupGame<Wait> {
+    fn with_count(count: usize) -> CoupGame<Wait> {
+        CoupGame::new(
+            vec![String::new(); count],
+            rand::random(),
+            Observers::default(),
+        )
+    }
+
+    fn with_player_names<T>(players: T) -> CoupGame<Wait>
+    where
+        T: IntoIterator,
+        T::Item: AsRef<str>,
+    {
+        CoupGame::new(to_names(players), rand::random(), Observers::default())
+    }
+
+    fn with_seed<T>(players: T, seed: u64) -> CoupGame<Wait>
+    where
+        T: IntoIterator,
+        T::Item: AsRef<str>,
+    {
+        CoupGame::new(to_names(players), seed, Observers::default())
     }
 
     fn info(&self) -> GameInfo<'_> {
-        GameInfo {
-            players: &self.data.players,
-            current_player: self.data.players.current_player(),
-            coins_remaining: self.data.coins.remaining(),
-            deck: self.data.deck.cards(),
-        }
+        self.game_info()
     }
 
     fn actions(&self) -> &PossibleActions {
@@ -89,12 +165,21 @@ impl WaitState for CoupGame<Wait> {
     }
 
     fn play(mut self, action: Action) -> ActionKind {
+        self.data.observers.notify(|o| o.action_declared(&action));
+        let first_stage = match self.data.window {
+            ReactionWindow::Simultaneous => ReactionStage::All,
+            ReactionWindow::TwoStage => ReactionStage::Challenges,
+        };
         let players = &mut self.data.players;
         let actor = action.actor;
+        if let Some(card) = action.claim() {
+            players.record_claim(actor, card);
+        }
         match action.kind {
             Act::Assassinate { victim } => {
                 let kind = ReactableAct::Assassinate { victim };
-                let possible_reactions = players.generate_reactions_against(actor, kind);
+                let mut possible_reactions = players.generate_reactions_against(actor, kind);
+                possible_reactions.stage = first_stage;
                 ActionKind::Reactable(self.transition_with_state(Reactable {
                     actor,
                     kind,
@@ -128,7 +213,8 @@ impl WaitState for CoupGame<Wait> {
             }
             Act::Steal { victim } => {
                 let kind = ReactableAct::Steal { victim };
-                let possible_reactions = players.generate_reactions_against(actor, kind);
+                let mut possible_reactions = players.generate_reactions_against(actor, kind);
+                possible_reactions.stage = first_stage;
 
                 ActionKind::Reactable(self.transition_with_state(Reactable {
                     actor,
@@ -158,7 +244,10 @@ impl ReactableState for CoupGame<Reactable> {
         self.transition_to_block(block)
     }
 
-    fn challenge(self, challenge: action::Challenge) -> CoupGame<Challenge> {
+    fn challenge(mut self, challenge: action::Challenge) -> CoupGame<Challenge> {
+        self.data
+            .observers
+            .notify(|o| o.reaction_submitted(&Reaction::Challenge(challenge.clone())));
         let action::Challenge {
             actor,
             challenger,
@@ -200,6 +289,10 @@ impl ReactableState for CoupGame<Reactable> {
                 self.data
                     .players
                     .set_coins_for(self.state.actor, actor_coins);
+                let actor = self.state.actor;
+                self.data
+                    .observers
+                    .notify(|o| o.coins_moved(Some(victim), Some(actor), 2));
 
                 GameState::Wait(self.end_turn())
             }
@@ -237,7 +330,10 @@ impl OnlyChallengeableState for CoupGame<OnlyChallengeable> {
         &self.state.possible_challenges
     }
 
-    fn challenge(self, challenge: action::Challenge) -> CoupGame<Challenge> {
+    fn challenge(mut self, challenge: action::Challenge) -> CoupGame<Challenge> {
+        self.data
+            .observers
+            .notify(|o| o.reaction_submitted(&Reaction::Challenge(challenge.clone())));
         let action::Challenge {
             actor,
             challenger,
@@ -342,7 +438,11 @@ impl ChooseVictimCardState for CoupGame<ChooseVictimCard> {
             alive: remaining_card,
             dead: choice,
         };
-        self.data.players.exchange(self.state.victim, hand);
+        let victim = self.state.victim;
+        self.data.players.exchange(victim, hand);
+        self.data
+            .observers
+            .notify(|o| o.influence_lost(victim, choice));
 
         self.end_turn()
     }
@@ -382,7 +482,13 @@ impl ChooseOneFromThreeState for CoupGame<ChooseOneFromThree> {
             .filter_map(|(i, card)| (index != i).then_some(card))
             .collect_array()
             .expect("Two other cards must exist");
-        self.data.deck.return_cards(&other_cards);
+        let actor = self.state.actor;
+        self.data
+            .observers
+            .notify(|o| o.cards_exchanged(actor, &[choice], &other_cards));
+        self.data
+            .deck
+            .return_cards(&other_cards, &mut self.data.rng);
 
         self.end_turn()
     }
@@ -414,7 +520,13 @@ impl ChooseTwoFromFourState for CoupGame<ChooseTwoFromFour> {
             .filter_map(|(index, card)| (index != i1 && index != i2).then_some(card))
             .collect_array()
             .expect("Must have two cards left");
-        self.data.deck.return_cards(&remaining_cards);
+        let actor = self.state.actor;
+        self.data
+            .observers
+            .notify(|o| o.cards_exchanged(actor, &cards, &remaining_cards));
+        self.data
+            .deck
+            .return_cards(&remaining_cards, &mut self.data.rng);
 
         self.end_turn()
     }
@@ -426,17 +538,32 @@ impl ChallengeState for CoupGame<Challenge> {
         let victim =
             self.data
                 .players
-                .challenge_winner(self.state.actor, self.state.challenger, claim);
+                .challenge_loser(self.state.actor, self.state.challenger, claim);
 
         Outcome::LosesInfluence { victim }
     }
 
-    fn advance(self) -> GameState {
+    fn advance(mut self) -> GameState {
         let claim = (&self.state.kind).into();
         let victim =
             self.data
                 .players
-                .challenge_winner(self.state.actor, self.state.challenger, claim);
+                .challenge_loser(self.state.actor, self.state.challenger, claim);
+        let result = if victim == self.state.actor {
+            ChallengeResult::Bluffed
+        } else {
+            ChallengeResult::Proven
+        };
+        self.data.players.record_challenge(self.state.actor, result);
+
+        let challenge = action::Challenge {
+            actor: self.state.actor,
+            challenger: self.state.challenger,
+            kind: self.state.kind.clone(),
+        };
+        self.data
+            .observers
+            .notify(|o| o.challenge_resolved(&challenge, result));
 
         self.lose_influence(victim)
     }
@@ -447,7 +574,10 @@ impl BlockState for CoupGame<Block> {
         &self.state.possible_challenges
     }
 
-    fn challenge(self, challenge: action::Challenge) -> CoupGame<Challenge> {
+    fn challenge(mut self, challenge: action::Challenge) -> CoupGame<Challenge> {
+        self.data
+            .observers
+            .notify(|o| o.reaction_submitted(&Reaction::Challenge(challenge.clone())));
         let action::Challenge {
             actor,
             challenger,
@@ -491,6 +621,10 @@ impl BlockState for CoupGame<Block> {
 }
 
 impl EndState for CoupGame<End> {
+    fn info(&self) -> GameInfo<'_> {
+        self.game_info()
+    }
+
     fn summary(self) -> Summary {
         Summary {
             winner: self.state.winner,
@@ -1,11 +1,21 @@
 #![warn(unused_crate_dependencies)]
 #![feature(array_try_map)]
 pub mod action;
+pub mod belief;
 mod coins;
+pub mod commitment;
+pub mod compact;
 mod current_player;
 pub mod deck;
+pub mod driver;
+pub mod env;
 mod game;
-pub use game::match_to_indices;
+pub use game::{GameBuilder, match_to_indices};
 pub mod machine;
+pub mod notation;
+pub mod observer;
 pub mod player_map;
 pub mod players;
+pub mod priority;
+pub mod scenario;
+mod undo;
@@ -1,6 +1,11 @@
 use crate::action::BlockableAct;
 use crate::action::ChallengeableAct;
+use crate::action::Reaction;
+use crate::action::ReactionStage;
+use crate::observer::Observers;
 use crate::player_map::PlayerMap;
+use crate::priority::{ReactionPriority, ReactionWindow};
+use crate::undo::UndoStack;
 
 use super::action;
 use super::action::Action;
@@ -12,6 +17,7 @@ use super::coins::Withdrawal;
 use super::deck::Hand;
 use super::deck::{Card, Deck};
 use super::players::PlayerId;
+use rand_chacha::ChaCha8Rng;
 use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
@@ -23,6 +29,7 @@ pub struct GameInfo<'state> {
     pub current_player: PlayerId,
     pub coins_remaining: u8,
     pub deck: &'state [Card],
+    pub seed: u64,
 }
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, JsonSchema)]
@@ -44,6 +51,14 @@ pub(crate) struct CoupData {
     pub(crate) players: PlayerMap,
     pub(crate) coins: CoinPile,
     pub(crate) deck: Deck,
+    // games without a given seed get a random one, so that every game can be replayed
+    pub(crate) seed: u64,
+    // every shuffle draws from this, so a seeded game always plays out the same way
+    pub(crate) rng: ChaCha8Rng,
+    pub(crate) observers: Observers,
+    pub(crate) undo: UndoStack,
+    pub(crate) priority: ReactionPriority,
+    pub(crate) window: ReactionWindow,
 }
 
 // Typestate that describes the entire Coup state loop
@@ -106,57 +121,69 @@ pub(crate) mod game {
     use super::*;
 
     #[automaton]
+    #[derive(Debug)]
     pub struct CoupGame {
         // we box this data so that moves are cheap
         pub(crate) data: Box<CoupData>
     }
 
+    #[derive(Debug, Clone)]
     #[state] pub struct Wait {
         pub(crate) possible_actions: PossibleActions,
     }
 
+    #[derive(Debug)]
     #[state] pub struct Safe { 
         pub(crate) actor: PlayerId,
         pub(crate) kind: SafeAct,
     }
+    #[derive(Debug, Clone)]
     #[state] pub struct OnlyChallengeable {
         pub(crate) possible_challenges: PossibleChallenges,
         pub(crate) actor: PlayerId,
         pub(crate) kind: OnlyChallengeableAct,
     }
+    #[derive(Debug, Clone)]
     #[state] pub struct OnlyBlockable {
         pub(crate) possible_blocks: PossibleBlocks,
         pub(crate) actor: PlayerId,
     }
+    #[derive(Debug, Clone)]
     #[state] pub struct Reactable {
         pub(crate) possible_reactions: PossibleReactions,
         pub(crate) actor: PlayerId,
         pub(crate) kind: ReactableAct,
     }
+    #[derive(Debug, Clone)]
     #[state] pub struct ChooseVictimCard {
         pub(crate) victim: PlayerId,
         pub(crate) choices: [Card; 2],
     }
+    #[derive(Debug, Clone)]
     #[state] pub struct ChooseOneFromThree {
         pub(crate) actor: PlayerId,
         pub(crate) choices: [Card; 3],
     }
+    #[derive(Debug, Clone)]
     #[state] pub struct ChooseTwoFromFour {
         pub(crate) actor: PlayerId,
         pub(crate) choices: [Card; 4],
     }
+    #[derive(Debug)]
     #[state] pub struct Challenge {
         pub(crate) actor: PlayerId,
         pub(crate) challenger: PlayerId,
         pub(crate) kind: ChallengeableAct,
     }
     #[allow(dead_code)]
+    #[derive(Debug, Clone)]
     #[state] pub struct Block {
         pub(crate) possible_challenges: PossibleChallenges,
         pub(crate) actor: PlayerId,
         pub(crate) blocker: PlayerId,
         pub(crate) kind: BlockableAct,
     }
+    #[derive(Debug)]
     #[state] pub struct End {
         pub(crate) winner: PlayerId,
     }
@@ -183,6 +210,11 @@ pub(crate) mod game {
             T: IntoIterator,
             T::Item: AsRef<str>,
         ;
+        fn with_seed<T>(players: T, seed: u64) -> Wait
+        where 
+            T: IntoIterator,
+            T::Item: AsRef<str>,
+        ;
         fn info(&self) -> GameInfo<'_>;
         fn actions(&self) -> &PossibleActions;
         fn play(self, action: Action) -> ActionKind;
@@ -243,10 +275,84 @@ pub(crate) mod game {
     }
 
     pub trait End {
+        // the table as it was left, with every card the loser had face up
+        fn info(&self) -> GameInfo<'_>;
         fn summary(self) -> Summary;
     }
 }
 
+// Players each decision point is waiting on, in turn order starting from the current player.
+// Reaction windows wait on everyone that can react, everything else on a single player
+
+impl CoupGame<Wait> {
+    pub fn deciders(&self) -> Vec<PlayerId> {
+        vec![self.data.players.current_player()]
+    }
+}
+
+impl CoupGame<OnlyChallengeable> {
+    pub fn deciders(&self) -> Vec<PlayerId> {
+        self.in_turn_order(self.state.possible_challenges.all().keys())
+    }
+}
+
+impl CoupGame<OnlyBlockable> {
+    pub fn deciders(&self) -> Vec<PlayerId> {
+        self.in_turn_order(self.state.possible_blocks.all().keys())
+    }
+}
+
+impl CoupGame<Reactable> {
+    // only those who can react in the current stage
+    pub fn deciders(&self) -> Vec<PlayerId> {
+        self.in_turn_order(self.state.possible_reactions.all().keys())
+    }
+}
+
+impl CoupGame<Block> {
+    pub fn deciders(&self) -> Vec<PlayerId> {
+        self.in_turn_order(self.state.possible_challenges.all().keys())
+    }
+}
+
+impl CoupGame<ChooseVictimCard> {
+    pub fn deciders(&self) -> Vec<PlayerId> {
+        vec![self.state.victim]
+    }
+}
+
+impl CoupGame<ChooseOneFromThree> {
+    pub fn deciders(&self) -> Vec<PlayerId> {
+        vec![self.state.actor]
+    }
+}
+
+impl CoupGame<ChooseTwoFromFour> {
+    pub fn deciders(&self) -> Vec<PlayerId> {
+        vec![self.state.actor]
+    }
+}
+
+impl CoupGame<End> {
+    pub fn deciders(&self) -> Vec<PlayerId> {
+        Vec::new()
+    }
+}
+
+impl CoupGame<Reactable> {
+    pub fn stage(&self) -> ReactionStage {
+        self.state.possible_reactions.stage()
+    }
+
+    // everyone passed on challenging the action in the first of two stages, so it stands and can
+    // now be blocked
+    pub fn open_blocks(mut self) -> CoupGame<Reactable> {
+        assert_eq!(self.stage(), ReactionStage::Challenges);
+        self.state.possible_reactions.stage = ReactionStage::Blocks;
+        self
+    }
+}
+
 impl CoupGame<ChooseVictimCard> {
     pub fn victim(&self) -> PlayerId {
         self.state.victim
@@ -265,15 +371,95 @@ impl CoupGame<ChooseTwoFromFour> {
     }
 }
 
+impl CoupGame<End> {
+    pub fn winner(&self) -> PlayerId {
+        self.state.winner
+    }
+}
+
+impl CoupGame<Wait> {
+    // takes a player out of the game between turns (like when they leave it for good), turning
+    // their cards face up and returning their coins to the pile. If it was their turn, it passes
+    // to the next player. Checkpoints are forgotten, as going back to one would bring them back
+    pub fn resign(mut self, player: PlayerId) -> GameState {
+        let data = &mut *self.data;
+        let hidden = match data.players.hand_for(player) {
+            Hand::Full(c1, c2) => vec![c1, c2],
+            Hand::Last { alive, .. } => vec![alive],
+        };
+        for card in hidden {
+            data.observers.notify(|o| o.influence_lost(player, card));
+        }
+
+        let was_current = data.players.current_player() == player;
+        let player_coins = data.players.resign(player);
+        let amount = player_coins.amount();
+        data.coins.return_coins(player_coins);
+        if amount > 0 {
+            data.observers
+                .notify(|o| o.coins_moved(Some(player), None, amount));
+        }
+        data.undo.clear();
+
+        if let Some(id) = data.players.game_over() {
+            data.observers.notify(|o| o.game_ended(id));
+            return GameState::End(self.transition_with_state(End { winner: id }));
+        }
+
+        if was_current {
+            GameState::Wait(self.end_turn())
+        } else {
+            // the current player can't target them anymore
+            let current = self.data.players.current_player();
+            let possible_actions = self.data.players.generate_actions_for(current);
+            GameState::Wait(self.transition_with_state(Wait { possible_actions }))
+        }
+    }
+}
+
 impl<S: CoupGameState> CoupGame<S> {
+    // info is available from every state, not just Wait
+    pub(crate) fn game_info(&self) -> GameInfo<'_> {
+        GameInfo {
+            players: &self.data.players,
+            current_player: self.data.players.current_player(),
+            coins_remaining: self.data.coins.remaining(),
+            deck: self.data.deck.cards(),
+            seed: self.data.seed,
+        }
+    }
+
+    pub fn reaction_window(&self) -> ReactionWindow {
+        self.data.window
+    }
+
+    fn in_turn_order<'a>(&self, deciders: impl Iterator<Item = &'a PlayerId>) -> Vec<PlayerId> {
+        let deciders: Vec<_> = deciders.copied().collect();
+        self.data
+            .players
+            .turn_order()
+            .filter(|id| deciders.contains(id))
+            .collect()
+    }
+
     pub(crate) fn kill(mut self, victim: PlayerId) -> GameState {
-        let coins = &mut self.data.coins;
-        let players = &mut self.data.players;
-        let player_coins = players.kill(victim);
-        coins.return_coins(player_coins);
+        let Hand::Last { alive, .. } = self.data.players.hand_for(victim) else {
+            unreachable!("Player should only be killed when on their last card")
+        };
+        let data = &mut *self.data;
+        data.observers.notify(|o| o.influence_lost(victim, alive));
+
+        let player_coins = data.players.kill(victim);
+        let amount = player_coins.amount();
+        data.coins.return_coins(player_coins);
+        if amount > 0 {
+            data.observers
+                .notify(|o| o.coins_moved(Some(victim), None, amount));
+        }
 
         // checking if game is over
-        if let Some(id) = players.game_over() {
+        if let Some(id) = data.players.game_over() {
+            data.observers.notify(|o| o.game_ended(id));
             GameState::End(CoupGame {
                 data: self.data,
                 state: End { winner: id },
@@ -298,6 +484,7 @@ impl<S: CoupGameState> CoupGame<S> {
     }
 
     pub(crate) fn withdraw(mut self, withdrawal: Withdrawal, actor: PlayerId) -> CoupGame<Wait> {
+        let amount = withdrawal as u8;
         let coins = self.data.players.get_coins_for(actor);
         let coins = self
             .data
@@ -305,10 +492,14 @@ impl<S: CoupGameState> CoupGame<S> {
             .withdraw(withdrawal, coins)
             .expect("Should have coins left");
         self.data.players.set_coins_for(actor, coins);
+        self.data
+            .observers
+            .notify(|o| o.coins_moved(None, Some(actor), amount));
         self.end_turn()
     }
 
     pub(crate) fn spend(&mut self, deposit: Deposit, actor: PlayerId) {
+        let amount = deposit as u8;
         let coins = self.data.players.get_coins_for(actor);
         let coins = self
             .data
@@ -316,14 +507,16 @@ impl<S: CoupGameState> CoupGame<S> {
             .spend(deposit, coins)
             .expect("Should have coins left");
         self.data.players.set_coins_for(actor, coins);
+        self.data
+            .observers
+            .notify(|o| o.coins_moved(Some(actor), None, amount));
     }
 
     pub(crate) fn end_turn(mut self) -> CoupGame<Wait> {
         self.data.players.end_turn();
-        let possible_actions = self
-            .data
-            .players
-            .generate_actions_for(self.data.players.current_player());
+        let next = self.data.players.current_player();
+        self.data.observers.notify(|o| o.turn_ended(next));
+        let possible_actions = self.data.players.generate_actions_for(next);
 
         self.transition_with_state(Wait { possible_actions })
     }
@@ -335,7 +528,13 @@ impl<S: CoupGameState> CoupGame<S> {
         }
     }
 
-    pub(crate) fn transition_to_block(self, block: action::Block) -> CoupGame<Block> {
+    pub(crate) fn transition_to_block(mut self, block: action::Block) -> CoupGame<Block> {
+        self.data
+            .observers
+            .notify(|o| o.reaction_submitted(&Reaction::Block(block.clone())));
+        self.data
+            .players
+            .record_claim(block.blocker(), block.claim());
         let action::Block {
             actor,
             blocker,
@@ -368,21 +567,90 @@ pub use game::*;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::GameBuilder;
+    use crate::action::Act;
 
     #[test]
     fn basic_game_info() {
-        let game = CoupGame::with_player_names(["Dave", "Garry"].to_vec());
+        let game = CoupGame::with_seed(["Dave", "Garry"].to_vec(), 1);
 
         let GameInfo {
             players,
             current_player,
             coins_remaining,
             deck,
+            seed,
         } = game.info();
 
+        assert_eq!(seed, 1);
         assert_eq!(coins_remaining, 46);
         assert_eq!(current_player, PlayerId::One);
         assert_eq!(players.alive().count(), 2);
         assert_eq!(deck.len(), 11);
     }
+
+    #[test]
+    fn resigning_passes_the_turn_and_returns_coins() {
+        let mut game = GameBuilder::new(["Dave", "Garry", "Lisa"])
+            .seed(1)
+            .undo_limit(5)
+            .build();
+        game.checkpoint();
+        let order: Vec<_> = game.info().players.turn_order().collect();
+        let coins = game.info().coins_remaining;
+
+        let GameState::Wait(game) = game.resign(order[0]) else {
+            panic!("Two players are left")
+        };
+
+        let info = game.info();
+        assert_eq!(info.current_player, order[1]);
+        assert_eq!(info.coins_remaining, coins + 2);
+        assert!(info.players.dead().any(|(id, _)| id == order[0]));
+        assert_eq!(game.undo_depth(), 0);
+    }
+
+    #[test]
+    fn resigning_someone_else_keeps_the_turn() {
+        let game = CoupGame::with_seed(["Dave", "Garry", "Lisa"], 1);
+        let order: Vec<_> = game.info().players.turn_order().collect();
+
+        let GameState::Wait(game) = game.resign(order[1]) else {
+            panic!("Two players are left")
+        };
+
+        assert_eq!(game.info().current_player, order[0]);
+        // they can't be stolen from anymore
+        assert!(
+            game.actions()
+                .all()
+                .all(|action| action.kind() != Act::Steal { victim: order[1] })
+        );
+    }
+
+    #[test]
+    fn resigning_down_to_one_player_ends_the_game() {
+        let game = CoupGame::with_seed(["Dave", "Garry"], 1);
+        let order: Vec<_> = game.info().players.turn_order().collect();
+
+        let GameState::End(game) = game.resign(order[0]) else {
+            panic!("Only one player is left")
+        };
+
+        assert_eq!(game.winner(), order[1]);
+    }
+
+    #[test]
+    fn finished_games_still_show_the_table() {
+        let game = CoupGame::with_seed(["Dave", "Garry"], 1);
+        let order: Vec<_> = game.info().players.turn_order().collect();
+
+        let GameState::End(game) = game.resign(order[0]) else {
+            panic!("Only one player is left")
+        };
+
+        let info = game.info();
+        assert!(info.players.alive().all(|(id, _)| id == order[1]));
+        assert_eq!(info.players.dead().count(), 1);
+    }
 }
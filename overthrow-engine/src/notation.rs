@@ -0,0 +1,850 @@
+use crate::action::{Act, ReactionStage};
+use crate::deck::{Card, Hand};
+use crate::driver::{Decision, Phase, Record};
+use crate::game::GameBuilder;
+use crate::machine::Outcome;
+use crate::player_map::Player;
+use crate::players::PlayerId;
+use crate::priority::{ReactionPriority, ReactionWindow};
+use itertools::Itertools;
+use std::fmt;
+use std::str::FromStr;
+
+// Text notation for whole games, in the spirit of chess PGN. A header of tags is followed by one
+// line per turn: the action and any reactions to it, then what they resolved to.
+//
+// [Seed "42"]
+// [Rules "Standard"]
+// [P1 "Dave"]
+// [P2 "Garry"]
+// [Result "P2"]
+//
+// 1: P1 ForeignAid -> P1 gains 2
+// 2: P2 Income -> P2 gains 1
+// 3: P1 ForeignAid -> P1 gains 2
+// 4: P2 Tax(Duke) -> P2 gains 3
+// 5: P1 Tax(Duke) ; P2 Challenge -> P1 proves, P2 loses Assassin
+// 6: P2 Income -> P2 gains 1
+// 7: P1 ForeignAid -> P1 gains 2
+// 8: P2 Exchange(Ambassador) -> P2 keeps Contessa
+// 9: P1 Steal(Captain) P2 ; P2 Challenge -> P1 bluffed, P1 loses Duke
+// 10: P2 Coup P1 -> P1 loses Duke
+//
+// Passing is implied by a reaction window not having any reactions. Everything after the arrow
+// is checked when replaying, and losing or keeping cards is how those decisions are given.
+// Lines starting with # are comments.
+
+// The settings a game was played with that change how the same decisions play out. Games with
+// ReactionPriority::Random can't be written down: which reaction went ahead was drawn from the
+// game's rng, which the history doesn't record, so a replay would shuffle differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rules {
+    // blocks and challenges are taken together (ReactionWindow::Simultaneous)
+    Standard,
+    // challenges first, then blocks (ReactionWindow::TwoStage)
+    TwoStage,
+}
+
+impl Rules {
+    // None for games that can't be replayed from their history
+    pub fn new(window: ReactionWindow, priority: ReactionPriority) -> Option<Rules> {
+        match (window, priority) {
+            (_, ReactionPriority::Random) => None,
+            (ReactionWindow::Simultaneous, ReactionPriority::TurnOrder) => Some(Rules::Standard),
+            (ReactionWindow::TwoStage, ReactionPriority::TurnOrder) => Some(Rules::TwoStage),
+        }
+    }
+
+    pub fn window(self) -> ReactionWindow {
+        match self {
+            Rules::Standard => ReactionWindow::Simultaneous,
+            Rules::TwoStage => ReactionWindow::TwoStage,
+        }
+    }
+
+    // the game these rules are played with, before any decisions
+    fn start(self, seed: u64, players: &[String]) -> Phase {
+        let game = GameBuilder::new(players)
+            .seed(seed)
+            .reaction_window(self.window())
+            .build();
+        Phase::from(game)
+    }
+}
+
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rules::Standard => write!(f, "Standard"),
+            Rules::TwoStage => write!(f, "TwoStage"),
+        }
+    }
+}
+
+// one of the moves that make up a turn, in the order they were made
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    Act(PlayerId, Act),
+    Challenge(PlayerId),
+    Block(PlayerId, Card),
+}
+
+// what the moves of a turn resolved to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    // challenged player had the card
+    Proves(PlayerId),
+    // challenged player didn't have the card
+    Bluffed(PlayerId),
+    Loses(PlayerId, Card),
+    Gains(PlayerId, u8),
+    Pays(PlayerId, u8),
+    Keeps(PlayerId, Vec<Card>),
+    Blocked(PlayerId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Turn {
+    pub number: u32,
+    pub moves: Vec<Move>,
+    pub resolutions: Vec<Resolution>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notation {
+    pub seed: u64,
+    pub rules: Rules,
+    pub players: Vec<String>,
+    pub result: Option<PlayerId>,
+    pub turns: Vec<Turn>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    // 1 based
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    // a move or card choice that isn't possible at that point of the game
+    Illegal {
+        turn: u32,
+        text: String,
+    },
+    // turn was missing a decision the game was waiting on
+    Incomplete {
+        turn: u32,
+    },
+    // the game played out differently from what was written down
+    Mismatch {
+        turn: u32,
+        expected: String,
+        found: String,
+    },
+    // the history doesn't follow the rules of the game it is from
+    InvalidHistory(Decision),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Illegal { turn, text } => write!(f, "Turn {turn}: {text} is not legal"),
+            ReplayError::Incomplete { turn } => write!(f, "Turn {turn} is missing a decision"),
+            ReplayError::Mismatch {
+                turn,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Turn {turn} was written as {expected} but played as {found}"
+            ),
+            ReplayError::InvalidHistory(decision) => {
+                write!(f, "History contains an invalid decision: {decision}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+fn player(id: PlayerId) -> String {
+    format!("P{id}")
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Move::Act(actor, act) => {
+                let actor = player(*actor);
+                match act {
+                    Act::Income => write!(f, "{actor} Income"),
+                    Act::ForeignAid => write!(f, "{actor} ForeignAid"),
+                    Act::Tax => write!(f, "{actor} Tax(Duke)"),
+                    Act::Exchange => write!(f, "{actor} Exchange(Ambassador)"),
+                    Act::Steal { victim } => {
+                        write!(f, "{actor} Steal(Captain) {}", player(*victim))
+                    }
+                    Act::Assassinate { victim } => {
+                        write!(f, "{actor} Assassinate(Assassin) {}", player(*victim))
+                    }
+                    Act::Coup { victim } => write!(f, "{actor} Coup {}", player(*victim)),
+                }
+            }
+            Move::Challenge(challenger) => write!(f, "{} Challenge", player(*challenger)),
+            Move::Block(blocker, card) => write!(f, "{} Block({card})", player(*blocker)),
+        }
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resolution::Proves(id) => write!(f, "{} proves", player(*id)),
+            Resolution::Bluffed(id) => write!(f, "{} bluffed", player(*id)),
+            Resolution::Loses(id, card) => write!(f, "{} loses {card}", player(*id)),
+            Resolution::Gains(id, amount) => write!(f, "{} gains {amount}", player(*id)),
+            Resolution::Pays(id, amount) => write!(f, "{} pays {amount}", player(*id)),
+            Resolution::Keeps(id, cards) => {
+                write!(f, "{} keeps {}", player(*id), cards.iter().join("+"))
+            }
+            Resolution::Blocked(id) => write!(f, "{} blocked", player(*id)),
+        }
+    }
+}
+
+impl fmt::Display for Turn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.number, self.moves.iter().join(" ; "))?;
+        if !self.resolutions.is_empty() {
+            write!(f, " -> {}", self.resolutions.iter().join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Notation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[Seed \"{}\"]", self.seed)?;
+        writeln!(f, "[Rules \"{}\"]", self.rules)?;
+        for (id, name) in PlayerId::iter().zip(&self.players) {
+            writeln!(f, "[{} \"{name}\"]", player(id))?;
+        }
+        if let Some(winner) = self.result {
+            writeln!(f, "[Result \"{}\"]", player(winner))?;
+        }
+
+        writeln!(f)?;
+        for turn in &self.turns {
+            writeln!(f, "{turn}")?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_player(text: &str) -> Result<PlayerId, String> {
+    text.strip_prefix('P')
+        .and_then(|seat| seat.parse::<usize>().ok())
+        .and_then(|seat| PlayerId::iter().nth(seat.checked_sub(1)?))
+        .ok_or_else(|| format!("Expected a player (P1 to P6), found {text:?}"))
+}
+
+fn parse_card(text: &str) -> Result<Card, String> {
+    Card::iter()
+        .find(|card| card.to_string() == text)
+        .ok_or_else(|| format!("Expected a card, found {text:?}"))
+}
+
+// splits "Name(Card)" into its parts
+fn parse_claim(text: &str) -> Result<(&str, Option<Card>), String> {
+    match text.split_once('(') {
+        Some((name, claim)) => {
+            let claim = claim
+                .strip_suffix(')')
+                .ok_or_else(|| format!("Unclosed claim in {text:?}"))?;
+            Ok((name, Some(parse_card(claim)?)))
+        }
+        None => Ok((text, None)),
+    }
+}
+
+impl FromStr for Move {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let words: Vec<_> = text.split_whitespace().collect();
+        let [actor, kind, rest @ ..] = words.as_slice() else {
+            return Err(format!("Expected a player and a move, found {text:?}"));
+        };
+        let actor = parse_player(actor)?;
+        let (kind, claim) = parse_claim(kind)?;
+
+        let victim = || match rest {
+            [victim] => parse_player(victim),
+            _ => Err(format!("Expected a single victim in {text:?}")),
+        };
+        let act = match kind {
+            "Income" => Act::Income,
+            "ForeignAid" => Act::ForeignAid,
+            "Tax" => Act::Tax,
+            "Exchange" => Act::Exchange,
+            "Steal" => Act::Steal { victim: victim()? },
+            "Assassinate" => Act::Assassinate { victim: victim()? },
+            "Coup" => Act::Coup { victim: victim()? },
+            "Challenge" if rest.is_empty() && claim.is_none() => {
+                return Ok(Move::Challenge(actor));
+            }
+            "Block" if rest.is_empty() => {
+                let claim = claim.ok_or_else(|| format!("Block needs a claim in {text:?}"))?;
+                return Ok(Move::Block(actor, claim));
+            }
+            _ => return Err(format!("Unknown move {text:?}")),
+        };
+
+        if !matches!(
+            act,
+            Act::Steal { .. } | Act::Assassinate { .. } | Act::Coup { .. }
+        ) && !rest.is_empty()
+        {
+            return Err(format!("Unexpected victim in {text:?}"));
+        }
+        if claim.is_some() && claim != act.claim() {
+            return Err(format!("Wrong claim in {text:?}"));
+        }
+
+        Ok(Move::Act(actor, act))
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let words: Vec<_> = text.split_whitespace().collect();
+        let amount = |amount: &str| {
+            amount
+                .parse::<u8>()
+                .map_err(|_| format!("Expected an amount of coins, found {amount:?}"))
+        };
+
+        match words.as_slice() {
+            [id, "proves"] => Ok(Resolution::Proves(parse_player(id)?)),
+            [id, "bluffed"] => Ok(Resolution::Bluffed(parse_player(id)?)),
+            [id, "blocked"] => Ok(Resolution::Blocked(parse_player(id)?)),
+            [id, "loses", card] => Ok(Resolution::Loses(parse_player(id)?, parse_card(card)?)),
+            [id, "gains", n] => Ok(Resolution::Gains(parse_player(id)?, amount(n)?)),
+            [id, "pays", n] => Ok(Resolution::Pays(parse_player(id)?, amount(n)?)),
+            [id, "keeps", cards] => {
+                let cards = cards.split('+').map(parse_card).collect::<Result<_, _>>()?;
+                Ok(Resolution::Keeps(parse_player(id)?, cards))
+            }
+            _ => Err(format!("Unknown resolution {text:?}")),
+        }
+    }
+}
+
+impl FromStr for Turn {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (number, rest) = text
+            .split_once(':')
+            .ok_or_else(|| format!("Expected a turn number in {text:?}"))?;
+        let number = number
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid turn number {number:?}"))?;
+
+        let (moves, resolutions) = match rest.split_once("->") {
+            Some((moves, resolutions)) => (moves, Some(resolutions)),
+            None => (rest, None),
+        };
+        let moves: Vec<Move> = moves
+            .split(';')
+            .map(|text| text.trim().parse())
+            .collect::<Result<_, _>>()?;
+        if !matches!(moves.first(), Some(Move::Act(..))) {
+            return Err("Turns have to start with an action".to_owned());
+        }
+
+        let resolutions = match resolutions {
+            Some(resolutions) => resolutions
+                .split(',')
+                .map(|text| text.trim().parse())
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+
+        Ok(Turn {
+            number,
+            moves,
+            resolutions,
+        })
+    }
+}
+
+fn parse_tag(line: &str) -> Result<(&str, &str), String> {
+    let tag = line
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+        .ok_or_else(|| format!("Expected a tag, found {line:?}"))?;
+    let (name, value) = tag
+        .split_once(' ')
+        .ok_or_else(|| format!("Tag has no value: {line:?}"))?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(|| format!("Tag values must be quoted: {line:?}"))?;
+    Ok((name, value))
+}
+
+impl FromStr for Notation {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut seed = None;
+        let mut rules = Rules::Standard;
+        let mut players: Vec<(PlayerId, String)> = Vec::new();
+        let mut result = None;
+        let mut turns = Vec::new();
+
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        for (line, text) in lines {
+            let error = |message| ParseError { line, message };
+
+            if !text.starts_with('[') {
+                turns.push(text.parse().map_err(error)?);
+                continue;
+            }
+            if !turns.is_empty() {
+                return Err(error("Tags have to come before any turns".to_owned()));
+            }
+
+            match parse_tag(text).map_err(error)? {
+                ("Seed", value) => {
+                    seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| error(format!("Invalid seed {value:?}")))?,
+                    )
+                }
+                ("Rules", "Standard") => rules = Rules::Standard,
+                ("Rules", "TwoStage") => rules = Rules::TwoStage,
+                ("Rules", value) => return Err(error(format!("Unknown rules {value:?}"))),
+                ("Result", value) => result = Some(parse_player(value).map_err(error)?),
+                (tag, value) if tag.starts_with('P') => {
+                    players.push((parse_player(tag).map_err(error)?, value.to_owned()))
+                }
+                (tag, _) => return Err(error(format!("Unknown tag {tag:?}"))),
+            }
+        }
+
+        let missing = |message: &str| ParseError {
+            line: 1,
+            message: message.to_owned(),
+        };
+        let seed = seed.ok_or_else(|| missing("Missing Seed tag"))?;
+
+        players.sort_by_key(|(id, _)| *id);
+        let in_order = players
+            .iter()
+            .map(|(id, _)| *id)
+            .eq(PlayerId::iter().take(players.len()));
+        if !(2..=6).contains(&players.len()) || !in_order {
+            return Err(missing("Expected between 2 and 6 players, from P1 onwards"));
+        }
+
+        Ok(Notation {
+            seed,
+            rules,
+            players: players.into_iter().map(|(_, name)| name).collect(),
+            result,
+            turns,
+        })
+    }
+}
+
+// builds the turns of a game out of its history, replaying it to fill in what the history doesn't
+// say (like which card a player lost on their last influence)
+struct Printer {
+    phase: Phase,
+    turns: Vec<Turn>,
+}
+
+impl Printer {
+    fn new(seed: u64, rules: Rules, players: &[String]) -> Printer {
+        Printer {
+            phase: rules.start(seed, players),
+            turns: Vec::new(),
+        }
+    }
+
+    fn record(mut self, decision: Decision) -> Result<Printer, ReplayError> {
+        // players on their last card lose it without a choice
+        let last_cards: Vec<Option<Card>> = self
+            .phase
+            .info()
+            .players
+            .all()
+            .map(|(_, player)| match player {
+                Player::Alive(data) => match data.hand() {
+                    Hand::Last { alive, .. } => Some(alive),
+                    Hand::Full(..) => None,
+                },
+                Player::Dead(..) => None,
+            })
+            .collect();
+        let mut moves = Vec::new();
+        let mut resolutions = Vec::new();
+        let mut challenged = None;
+        match (&decision, self.phase.decider(&decision)) {
+            (Decision::Act(action), _) => {
+                self.turns.push(Turn {
+                    number: self.turns.len() as u32 + 1,
+                    moves: Vec::new(),
+                    resolutions: Vec::new(),
+                });
+                moves.push(Move::Act(action.actor(), action.kind()));
+            }
+            (Decision::Challenge(challenge), _) => {
+                challenged = Some((challenge.actor(), challenge.challenger()));
+                moves.push(Move::Challenge(challenge.challenger()));
+            }
+            (Decision::Block(block), _) => moves.push(Move::Block(block.blocker(), block.claim())),
+            (Decision::ChooseVictim(card), Some(id)) => {
+                resolutions.push(Resolution::Loses(id, *card))
+            }
+            (Decision::ExchangeOne(card), Some(id)) => {
+                resolutions.push(Resolution::Keeps(id, vec![*card]))
+            }
+            (Decision::ExchangeTwo(cards), Some(id)) => {
+                resolutions.push(Resolution::Keeps(id, cards.to_vec()))
+            }
+            _ => {}
+        }
+
+        let step = self
+            .phase
+            .decide(decision)
+            .map_err(|invalid| ReplayError::InvalidHistory(invalid.decision))?;
+
+        match step.outcome {
+            Some(Outcome::LosesInfluence { victim }) => {
+                if let Some((claimant, challenger)) = challenged {
+                    resolutions.push(if victim == challenger {
+                        Resolution::Proves(claimant)
+                    } else {
+                        Resolution::Bluffed(claimant)
+                    });
+                }
+                // with two cards left the victim chooses, which is recorded with that choice
+                if let Some(card) = last_cards[victim as usize - 1] {
+                    resolutions.push(Resolution::Loses(victim, card));
+                }
+            }
+            Some(Outcome::GainCoins { actor, amount }) => {
+                resolutions.push(Resolution::Gains(actor, amount))
+            }
+            Some(Outcome::LoseCoins { actor, amount }) => {
+                resolutions.push(Resolution::Pays(actor, amount))
+            }
+            Some(Outcome::LoseTurn { victim }) => resolutions.push(Resolution::Blocked(victim)),
+            Some(Outcome::ExchangesCards { .. }) | None => {}
+        }
+        self.phase = step.phase;
+
+        let turn = self
+            .turns
+            .last_mut()
+            .expect("Games start with an action, which starts a turn");
+        turn.moves.extend(moves);
+        turn.resolutions.extend(resolutions);
+        Ok(self)
+    }
+}
+
+// the option that matches a move, if there is one
+fn find_move(phase: &Phase, mv: Move) -> Option<Decision> {
+    phase
+        .options()
+        .into_iter()
+        .find(|decision| match (mv, decision) {
+            (Move::Act(actor, act), Decision::Act(action)) => {
+                action.actor() == actor && action.kind() == act
+            }
+            (Move::Challenge(challenger), Decision::Challenge(challenge)) => {
+                challenge.challenger() == challenger
+            }
+            (Move::Block(blocker, claim), Decision::Block(block)) => {
+                block.blocker() == blocker && block.claim() == claim
+            }
+            _ => false,
+        })
+}
+
+// the card choice a resolution stands for, if the game is waiting on one
+fn find_choice(phase: &Phase, resolution: &Resolution) -> Option<Decision> {
+    let (chooser, decision) = match resolution {
+        Resolution::Loses(id, card) => (*id, Decision::ChooseVictim(*card)),
+        Resolution::Keeps(id, cards) => match cards.as_slice() {
+            [card] => (*id, Decision::ExchangeOne(*card)),
+            [c1, c2] => (*id, Decision::ExchangeTwo([*c1, *c2])),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    (phase.decider(&decision) == Some(chooser)).then_some(decision)
+}
+
+impl Notation {
+    // notation for a game with the given seed, rules and players, from every decision made in it
+    pub fn from_history(
+        seed: u64,
+        rules: Rules,
+        players: Vec<String>,
+        history: &[Record],
+    ) -> Result<Notation, ReplayError> {
+        let mut printer = Printer::new(seed, rules, &players);
+        for record in history {
+            printer = printer.record(record.decision.clone())?;
+        }
+
+        let result = match &printer.phase {
+            Phase::End(game) => Some(game.winner()),
+            _ => None,
+        };
+        Ok(Notation {
+            seed,
+            rules,
+            players,
+            result,
+            turns: printer.turns,
+        })
+    }
+
+    // plays the game back, checking that it plays out as written down. Returns where the game
+    // ended up along with every decision that was made
+    pub fn replay(&self) -> Result<(Phase, Vec<Record>), ReplayError> {
+        let mut phase = self.rules.start(self.seed, &self.players);
+        let mut history = Vec::new();
+
+        let apply = |phase: Phase, decision: Decision, history: &mut Vec<Record>| {
+            let step = phase
+                .decide(decision.clone())
+                .expect("Decision was one of the options");
+            history.push(Record {
+                decision,
+                outcome: step.outcome,
+            });
+            step.phase
+        };
+
+        for turn in &self.turns {
+            let illegal = |text: String| ReplayError::Illegal {
+                turn: turn.number,
+                text,
+            };
+
+            for mv in &turn.moves {
+                // in a two-stage window, blocks only come once nobody challenged the action
+                let challenges_only = matches!(
+                    &phase,
+                    Phase::Reactable(game) if game.stage() == ReactionStage::Challenges
+                );
+                if matches!(mv, Move::Block(..)) && challenges_only {
+                    phase = apply(phase, Decision::Pass, &mut history);
+                }
+                let decision = find_move(&phase, *mv).ok_or_else(|| illegal(mv.to_string()))?;
+                phase = apply(phase, decision, &mut history);
+            }
+            // nobody else reacted
+            while phase.is_reaction_window() {
+                phase = apply(phase, Decision::Pass, &mut history);
+            }
+
+            // the rest only describe what happened, and are checked once the game is replayed
+            for resolution in &turn.resolutions {
+                let Some(decision) = find_choice(&phase, resolution) else {
+                    continue;
+                };
+                let step = phase
+                    .decide(decision.clone())
+                    .map_err(|_| illegal(resolution.to_string()))?;
+                history.push(Record {
+                    decision,
+                    outcome: step.outcome,
+                });
+                phase = step.phase;
+            }
+
+            if !matches!(phase, Phase::Wait(..) | Phase::End(..)) {
+                return Err(ReplayError::Incomplete { turn: turn.number });
+            }
+        }
+
+        let played = Notation::from_history(self.seed, self.rules, self.players.clone(), &history)?;
+        for (written, played) in self.turns.iter().zip(&played.turns) {
+            if written != played {
+                return Err(ReplayError::Mismatch {
+                    turn: written.number,
+                    expected: written.to_string(),
+                    found: played.to_string(),
+                });
+            }
+        }
+        if self.result.is_some() && self.result != played.result {
+            let result = |result: Option<PlayerId>| result.map_or("unfinished".to_owned(), player);
+            return Err(ReplayError::Mismatch {
+                turn: played.turns.len() as u32,
+                expected: result(self.result),
+                found: result(played.result),
+            });
+        }
+
+        Ok((phase, history))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rand::SeedableRng;
+    use rand::seq::IteratorRandom;
+    use rand_chacha::ChaCha8Rng;
+
+    const EXAMPLE: &str = "\
+[Seed \"1\"]
+[Rules \"Standard\"]
+[P1 \"Dave\"]
+[P2 \"Garry\"]
+
+# Dave goes first with seed 1
+1: P1 Income -> P1 gains 1
+2: P2 ForeignAid ; P1 Block(Duke) -> P2 blocked
+";
+
+    fn names(players: usize) -> Vec<String> {
+        (1..=players).map(|n| format!("Player {n}")).collect()
+    }
+
+    // plays a game out of random options, returning every decision made
+    fn play_random(seed: u64, rules: Rules, players: usize) -> Vec<Record> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut phase = rules.start(seed, &names(players));
+        let mut history = Vec::new();
+
+        while let Some(decision) = phase.options().into_iter().choose(&mut rng) {
+            let step = phase
+                .decide(decision.clone())
+                .expect("Options are always valid");
+            history.push(Record {
+                decision,
+                outcome: step.outcome,
+            });
+            phase = step.phase;
+        }
+
+        history
+    }
+
+    #[test]
+    fn example_replays() {
+        let notation: Notation = EXAMPLE.parse().unwrap();
+        assert_eq!(notation.players, ["Dave", "Garry"]);
+        assert_eq!(notation.turns.len(), 2);
+
+        let (phase, history) = notation.replay().unwrap();
+        assert!(matches!(phase, Phase::Wait(..)));
+        // income, foreign aid, block, pass on the block
+        assert_eq!(history.len(), 4);
+    }
+
+    #[test]
+    fn random_games_roundtrip() {
+        for seed in 0..20 {
+            let players = 2 + seed as usize % 5;
+            let rules = if seed % 2 == 0 {
+                Rules::Standard
+            } else {
+                Rules::TwoStage
+            };
+            let history = play_random(seed, rules, players);
+            let notation = Notation::from_history(seed, rules, names(players), &history).unwrap();
+            assert!(notation.result.is_some());
+
+            let parsed: Notation = notation.to_string().parse().unwrap();
+            assert_eq!(parsed, notation);
+
+            let (phase, replayed) = parsed.replay().unwrap();
+            assert!(phase.is_over());
+            let decisions = |history: &[Record]| -> Vec<Decision> {
+                history.iter().map(|r| r.decision.clone()).collect()
+            };
+            assert_eq!(decisions(&replayed), decisions(&history));
+        }
+    }
+
+    #[test]
+    fn random_priority_has_no_rules() {
+        let rules = |window| Rules::new(window, ReactionPriority::Random);
+        assert_eq!(rules(ReactionWindow::Simultaneous), None);
+        assert_eq!(rules(ReactionWindow::TwoStage), None);
+        assert_eq!(
+            Rules::new(ReactionWindow::TwoStage, ReactionPriority::TurnOrder),
+            Some(Rules::TwoStage)
+        );
+    }
+
+    #[test]
+    fn parse_errors_have_lines() {
+        let text = EXAMPLE.replace("P1 Block(Duke)", "P1 Block(Joker)");
+        let error = text.parse::<Notation>().unwrap_err();
+        assert_eq!(error.line, 8);
+
+        let error = "[P1 \"Dave\"]\n[P2 \"Garry\"]\n"
+            .parse::<Notation>()
+            .unwrap_err();
+        assert_eq!(error.message, "Missing Seed tag");
+    }
+
+    #[test]
+    fn illegal_moves_are_rejected() {
+        // Garry can't act on Dave's turn
+        let notation: Notation = EXAMPLE
+            .replace("1: P1 Income", "1: P2 Income")
+            .parse()
+            .unwrap();
+        assert_eq!(
+            notation.replay().unwrap_err(),
+            ReplayError::Illegal {
+                turn: 1,
+                text: "P2 Income".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn mismatches_are_found() {
+        let notation: Notation = EXAMPLE.replace("P1 gains 1", "P1 gains 2").parse().unwrap();
+        assert!(matches!(
+            notation.replay().unwrap_err(),
+            ReplayError::Mismatch { turn: 1, .. }
+        ));
+    }
+}
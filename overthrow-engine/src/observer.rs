@@ -0,0 +1,143 @@
+use crate::action::{Action, Challenge, Reaction};
+use crate::deck::Card;
+use crate::player_map::ChallengeResult;
+use crate::players::PlayerId;
+use std::fmt;
+
+// Gets told about every transition of a game as it happens, for things like logging, statistics
+// or live feeds that would otherwise have to follow the game state by state. Observers are given
+// to the game when it is built (see GameBuilder and ScenarioBuilder), and every callback does
+// nothing by default.
+//
+// Some callbacks carry private information (like which cards were kept in an exchange), so an
+// observer that passes things on to players is responsible for hiding it.
+pub trait Observer: Send + Sync {
+    // current player declared an action, before anyone reacts to it
+    fn action_declared(&mut self, _action: &Action) {}
+
+    // someone blocked or challenged. Passing isn't a transition, so it isn't reported
+    fn reaction_submitted(&mut self, _reaction: &Reaction) {}
+
+    fn challenge_resolved(&mut self, _challenge: &Challenge, _result: ChallengeResult) {}
+
+    // card is now face up
+    fn influence_lost(&mut self, _victim: PlayerId, _card: Card) {}
+
+    // None stands for the treasury
+    fn coins_moved(&mut self, _from: Option<PlayerId>, _to: Option<PlayerId>, _amount: u8) {}
+
+    // private to the actor: the cards they kept, and the ones that went back into the deck
+    fn cards_exchanged(&mut self, _actor: PlayerId, _kept: &[Card], _returned: &[Card]) {}
+
+    fn turn_ended(&mut self, _next: PlayerId) {}
+
+    fn game_ended(&mut self, _winner: PlayerId) {}
+
+    // game went back to an earlier checkpoint, so everything since then is taken back
+    fn undone(&mut self) {}
+}
+
+// observers registered with a game, which are all told about every transition in order
+#[derive(Default)]
+pub(crate) struct Observers(Vec<Box<dyn Observer>>);
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
+
+impl Observers {
+    pub(crate) fn push(&mut self, observer: Box<dyn Observer>) {
+        self.0.push(observer);
+    }
+
+    pub(crate) fn notify(&mut self, mut callback: impl FnMut(&mut dyn Observer)) {
+        for observer in &mut self.0 {
+            callback(observer.as_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deck::Hand;
+    use crate::machine::{ActionKind, ChallengeState, GameState, ReactableState, WaitState};
+    use crate::scenario::ScenarioBuilder;
+    use std::sync::{Arc, Mutex};
+
+    // writes every event down, to be checked once the game is done with
+    #[derive(Default, Clone)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Recorder {
+        fn push(&self, event: String) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    impl Observer for Recorder {
+        fn action_declared(&mut self, action: &Action) {
+            self.push(format!("{:?} by {}", action.kind(), action.actor()));
+        }
+
+        fn reaction_submitted(&mut self, reaction: &Reaction) {
+            self.push(format!("reaction by {}", reaction.reactor()));
+        }
+
+        fn challenge_resolved(&mut self, challenge: &Challenge, result: ChallengeResult) {
+            self.push(format!("{} {result:?}", challenge.actor()));
+        }
+
+        fn influence_lost(&mut self, victim: PlayerId, card: Card) {
+            self.push(format!("{victim} lost {card}"));
+        }
+
+        fn coins_moved(&mut self, from: Option<PlayerId>, to: Option<PlayerId>, amount: u8) {
+            self.push(format!("{from:?} paid {to:?} {amount}"));
+        }
+
+        fn game_ended(&mut self, winner: PlayerId) {
+            self.push(format!("{winner} won"));
+        }
+    }
+
+    #[test]
+    fn observers_see_transitions() {
+        let recorder = Recorder::default();
+        let game = ScenarioBuilder::new(["Dave", "Garry"])
+            .hand(PlayerId::One, Hand::Full(Card::Captain, Card::Duke))
+            .hand(
+                PlayerId::Two,
+                Hand::Last {
+                    alive: Card::Contessa,
+                    dead: Card::Assassin,
+                },
+            )
+            .observer(recorder.clone())
+            .build()
+            .unwrap();
+
+        let action = game.actions().steal()[0].clone();
+        let ActionKind::Reactable(game) = game.play(action) else {
+            panic!("Steal is reactable")
+        };
+        let challenge = game.reactions().challenges()[&PlayerId::Two].clone();
+        let GameState::End(..) = game.challenge(challenge).advance() else {
+            panic!("Garry loses their last card")
+        };
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                "Steal { victim: Two } by 1",
+                "reaction by 2",
+                "1 Proven",
+                "2 lost Contessa",
+                "Some(Two) paid None 2",
+                "1 won",
+            ]
+        );
+    }
+}
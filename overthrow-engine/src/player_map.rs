@@ -3,14 +3,17 @@ use std::ops::Index;
 use crate::{
     action::{
         Act, Action, Block, BlockableAct, Blocks, Challenge, ChallengeableAct, PossibleActions,
-        PossibleBlocks, PossibleChallenges, PossibleReactions, ReactableAct,
+        PossibleBlocks, PossibleChallenges, PossibleReactions, ReactableAct, ReactionStage,
     },
     coins::PlayerCoins,
     current_player::CurrentPlayer,
-    deck::{BlockStealClaim, Card, Hand},
+    deck::{BlockStealClaim, Card, Hand, STARTING_DECK},
     players::PlayerId,
 };
 use arrayvec::ArrayVec;
+use rand_chacha::ChaCha8Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 pub const MAX_PLAYER_COUNT: usize = 6;
 
@@ -70,25 +73,56 @@ pub enum Player {
 }
 
 impl Player {
-    fn alive(name: String, coins: PlayerCoins, hand: Hand) -> Player {
+    pub(crate) fn alive(name: String, coins: PlayerCoins, hand: Hand) -> Player {
         let data = AlivePlayerData { name, coins, hand };
         Player::Alive(data)
     }
 
-    fn dead(name: String, revealed: [Card; 2]) -> Player {
+    pub(crate) fn dead(name: String, revealed: [Card; 2]) -> Player {
         let data = DeadPlayerData { name, revealed };
         Player::Dead(data)
     }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Player::Alive(data) => data.name(),
+            Player::Dead(data) => data.name(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ChallengeResult {
+    // claimant had the card
+    Proven,
+    // claimant didn't have the card
+    Bluffed,
 }
 
-#[derive(Debug)]
+// a card a player said they had, through an action or a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Claim {
+    pub card: Card,
+    // None if nobody challenged it
+    pub challenge: Option<ChallengeResult>,
+    // every copy of the card has since been revealed by other players, so the claimant can't be
+    // holding one now (though they may have before exchanging it away)
+    pub contradicted: bool,
+}
+
+#[derive(Debug, Clone)]
 pub struct PlayerMap {
     players: ArrayVec<Player, MAX_PLAYER_COUNT>,
     current: CurrentPlayer,
+    // claims of each player, in the order they were made
+    claims: ArrayVec<Vec<Claim>, MAX_PLAYER_COUNT>,
 }
 
 impl PlayerMap {
-    pub fn new(players: impl IntoIterator<Item = (String, PlayerCoins, Hand)>) -> PlayerMap {
+    pub fn new(
+        players: impl IntoIterator<Item = (String, PlayerCoins, Hand)>,
+        rng: &mut ChaCha8Rng,
+    ) -> PlayerMap {
         let players = players
             .into_iter()
             .map(|(name, coins, hand)| Player::alive(name, coins, hand));
@@ -101,7 +135,23 @@ impl PlayerMap {
         assert!(count >= 2);
         PlayerMap {
             players,
-            current: CurrentPlayer::new(count),
+            current: CurrentPlayer::new(count, rng),
+            claims: (0..count).map(|_| Vec::new()).collect(),
+        }
+    }
+
+    // players in a set position, where alive players take turns in seat order from current
+    pub(crate) fn with_position(players: Vec<Player>, current: PlayerId) -> PlayerMap {
+        let players = ArrayVec::<Player, MAX_PLAYER_COUNT>::from_iter(players);
+        let alive = PlayerId::iter()
+            .zip(&players)
+            .filter(|(_, p)| matches!(p, Player::Alive(..)))
+            .map(|(id, _)| id);
+
+        PlayerMap {
+            current: CurrentPlayer::starting_at(alive, current),
+            claims: players.iter().map(|_| Vec::new()).collect(),
+            players,
         }
     }
 
@@ -132,23 +182,88 @@ impl PlayerMap {
 
     // returns coins to be replaced in pile if successful, otherwise, returns error
     pub(crate) fn kill(&mut self, id: PlayerId) -> PlayerCoins {
+        let Hand::Last { .. } = self.hand_for(id) else {
+            unreachable!("Player should only be killed when on their last card")
+        };
+
+        self.resign(id)
+    }
+
+    // takes a player out with every card they have left face up, returning their coins to be
+    // replaced in pile
+    pub(crate) fn resign(&mut self, id: PlayerId) -> PlayerCoins {
         let index = id as usize - 1;
         let Player::Alive(data) = self.players[index].clone() else {
             unreachable!("Player should exist")
         };
 
-        let Hand::Last { alive, dead } = data.hand else {
-            unreachable!("Player should only be killed when on their last card")
+        let revealed = match data.hand {
+            Hand::Full(c1, c2) => [c1, c2],
+            Hand::Last { alive, dead } => [alive, dead],
         };
-
-        let player = Player::dead(data.name, [alive, dead]);
+        let player = Player::dead(data.name, revealed);
 
         self.players[index] = player;
         self.current.kill(id);
+        self.contradict_claims();
 
         data.coins
     }
 
+    // every claim id has made, in order
+    pub fn claims(&self, id: PlayerId) -> &[Claim] {
+        &self.claims[id as usize - 1]
+    }
+
+    pub(crate) fn record_claim(&mut self, id: PlayerId, card: Card) {
+        self.claims[id as usize - 1].push(Claim {
+            card,
+            challenge: None,
+            contradicted: false,
+        });
+        self.contradict_claims();
+    }
+
+    // challenges are always against the claim that was just made
+    pub(crate) fn record_challenge(&mut self, id: PlayerId, result: ChallengeResult) {
+        let claim = self.claims[id as usize - 1]
+            .last_mut()
+            .expect("Challenged player should have made a claim");
+        claim.challenge = Some(result);
+    }
+
+    // cards that are face up for id, either lost influence or both cards once dead
+    fn revealed(player: &Player) -> ArrayVec<Card, 2> {
+        match player {
+            Player::Alive(data) => match data.hand {
+                Hand::Full(..) => ArrayVec::new(),
+                Hand::Last { dead, .. } => [dead].into_iter().collect(),
+            },
+            Player::Dead(data) => data.revealed.into(),
+        }
+    }
+
+    fn contradict_claims(&mut self) {
+        for (index, claims) in self.claims.iter_mut().enumerate() {
+            for claim in claims.iter_mut().filter(|claim| !claim.contradicted) {
+                let revealed = self
+                    .players
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != index)
+                    .flat_map(|(_, player)| PlayerMap::revealed(player))
+                    .filter(|card| *card == claim.card)
+                    .count();
+                let copies = STARTING_DECK
+                    .iter()
+                    .filter(|card| **card == claim.card)
+                    .count();
+
+                claim.contradicted = revealed == copies;
+            }
+        }
+    }
+
     pub(crate) fn end_turn(&mut self) {
         self.current.end_turn();
     }
@@ -157,6 +272,11 @@ impl PlayerMap {
         self.current.current()
     }
 
+    // alive players in turn order, starting from the current player
+    pub fn turn_order(&self) -> impl Iterator<Item = PlayerId> {
+        self.current.order_from_current()
+    }
+
     // returns last player's player id if there is only one player left (equivalent to saying game is over)
     pub(crate) fn game_over(&self) -> Option<PlayerId> {
         let mut alive_players = PlayerId::iter()
@@ -172,6 +292,7 @@ impl PlayerMap {
 
     pub(crate) fn exchange(&mut self, id: PlayerId, hand: Hand) {
         self.as_alive_mut(id).hand = hand;
+        self.contradict_claims();
     }
 
     pub(crate) fn hand_for(&self, id: PlayerId) -> Hand {
@@ -209,17 +330,17 @@ impl PlayerMap {
         data
     }
 
-    // rules on a challenge
-    pub(crate) fn challenge_winner(
+    // rules on a challenge, returning the player who loses influence
+    pub(crate) fn challenge_loser(
         &self,
         actor: PlayerId,
         challenger: PlayerId,
         claim: Card,
     ) -> PlayerId {
         if self.has_card(actor, claim) {
-            actor
-        } else {
             challenger
+        } else {
+            actor
         }
     }
 
@@ -319,6 +440,7 @@ impl PlayerMap {
             block,
             challenge,
             actor,
+            stage: ReactionStage::All,
         }
     }
 
@@ -399,3 +521,73 @@ impl Index<PlayerId> for PlayerMap {
         &self.players[index]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{
+        ActionKind, ChallengeState, CoupGame, GameState, OnlyChallengeableState, Wait, WaitState,
+    };
+    use crate::scenario::ScenarioBuilder;
+
+    fn tax(game: CoupGame<Wait>) -> ActionKind {
+        let action = game
+            .actions()
+            .basic()
+            .iter()
+            .find(|action| action.kind() == Act::Tax)
+            .expect("Always possible")
+            .clone();
+        game.play(action)
+    }
+
+    #[test]
+    fn challenged_claims_are_recorded() {
+        let game = ScenarioBuilder::new(["Dave", "Garry"])
+            .hand(PlayerId::One, Hand::Full(Card::Captain, Card::Contessa))
+            .hand(PlayerId::Two, Hand::Full(Card::Duke, Card::Duke))
+            .build()
+            .unwrap();
+
+        let ActionKind::OnlyChallengeable(game) = tax(game) else {
+            panic!("Tax is challengeable")
+        };
+        let challenge = game.challenges().all()[&PlayerId::Two].clone();
+        let GameState::ChooseVictimCard(game) = game.challenge(challenge).advance() else {
+            panic!("Dave should lose a card")
+        };
+
+        let claims = game.game_info().players.claims(PlayerId::One).to_vec();
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].challenge, Some(ChallengeResult::Bluffed));
+        assert!(game.game_info().players.claims(PlayerId::Two).is_empty());
+    }
+
+    #[test]
+    fn claims_are_contradicted_by_revealed_cards() {
+        let game = ScenarioBuilder::new(["Dave", "Garry", "Judit"])
+            .hand(PlayerId::One, Hand::Full(Card::Captain, Card::Contessa))
+            .hand(
+                PlayerId::Two,
+                Hand::Last {
+                    alive: Card::Captain,
+                    dead: Card::Duke,
+                },
+            )
+            .dead(PlayerId::Three, [Card::Duke, Card::Duke])
+            .build()
+            .unwrap();
+
+        let ActionKind::OnlyChallengeable(game) = tax(game) else {
+            panic!("Tax is challengeable")
+        };
+        assert_eq!(
+            game.game_info().players.claims(PlayerId::One),
+            [Claim {
+                card: Card::Duke,
+                challenge: None,
+                contradicted: true,
+            }]
+        );
+    }
+}
@@ -40,7 +40,7 @@ mod tests {
 
     use super::*;
     static BASIC_GAME: LazyLock<CoupGame<Wait>> =
-        LazyLock::new(|| CoupGame::with_player_names(["Dave", "Garry"]));
+        LazyLock::new(|| CoupGame::with_seed(["Dave", "Garry"], 1));
 
     #[test]
     fn basic_generate_actions() {
@@ -0,0 +1,163 @@
+use crate::action::{Block, Challenge, Reaction};
+use crate::machine::{
+    Block as Blocked, CoupData, CoupGame, OnlyBlockable, OnlyChallengeable, Reactable,
+};
+use crate::players::PlayerId;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// How a reaction is picked when several players react to the same thing at once. Anything taking
+// reactions from players at the same time (like the server) should gather every response in the
+// window and let the game pick with resolve, rather than going with whichever arrives first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ReactionPriority {
+    // first reactor in turn order, starting after whoever made the claim being reacted to
+    #[default]
+    TurnOrder,
+    // drawn from the game's rng, so seeded games still play out the same way
+    Random,
+}
+
+// Whether blocks and challenges to an action that can have both (steals and assassinations) are
+// taken together, or one after the other like at the table: first everyone may challenge the
+// action's claim, then only if it stands may it be blocked. The stage a game is in shows up in
+// PossibleReactions::stage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ReactionWindow {
+    #[default]
+    Simultaneous,
+    TwoStage,
+}
+
+// anything a player can react with
+pub trait Reactor {
+    fn reactor(&self) -> PlayerId;
+}
+
+impl Reactor for Reaction {
+    fn reactor(&self) -> PlayerId {
+        Reaction::reactor(self)
+    }
+}
+
+impl Reactor for Challenge {
+    fn reactor(&self) -> PlayerId {
+        self.challenger()
+    }
+}
+
+impl Reactor for Block {
+    fn reactor(&self) -> PlayerId {
+        self.blocker()
+    }
+}
+
+impl CoupData {
+    fn resolve<R: Reactor>(&mut self, claimant: PlayerId, mut reactions: Vec<R>) -> Option<R> {
+        if reactions.is_empty() {
+            return None;
+        }
+
+        match self.priority {
+            ReactionPriority::TurnOrder => {
+                let order: Vec<_> = self.players.turn_order().collect();
+                let start = order.iter().position(|id| *id == claimant).unwrap_or(0);
+                // claimant goes last, as they can't react to themselves anyway
+                let seat = |reaction: &R| {
+                    let position = order.iter().position(|id| *id == reaction.reactor());
+                    position.map(|p| (p + order.len() - start - 1) % order.len())
+                };
+                let index = (0..reactions.len()).min_by_key(|i| seat(&reactions[*i]))?;
+                Some(reactions.swap_remove(index))
+            }
+            ReactionPriority::Random => {
+                let index = self.rng.gen_range(0..reactions.len());
+                Some(reactions.swap_remove(index))
+            }
+        }
+    }
+}
+
+// reactions go in turn order after whoever made the claim being reacted to
+macro_rules! resolve {
+    ($($state:ident => $reaction:ty, $claimant:ident);*) => {$(
+        impl CoupGame<$state> {
+            // picks the reaction that goes ahead out of everyone that reacted, by the game's
+            // ReactionPriority. None if nobody reacted
+            pub fn resolve(&mut self, reactions: Vec<$reaction>) -> Option<$reaction> {
+                let claimant = self.state.$claimant;
+                self.data.resolve(claimant, reactions)
+            }
+        }
+    )*};
+}
+
+resolve!(
+    OnlyChallengeable => Challenge, actor;
+    OnlyBlockable => Block, actor;
+    Reactable => Reaction, actor;
+    Blocked => Challenge, blocker
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameBuilder;
+    use crate::action::Act;
+    use crate::machine::{ActionKind, OnlyChallengeableState, WaitState};
+
+    fn tax(priority: ReactionPriority, seed: u64) -> CoupGame<OnlyChallengeable> {
+        let game = GameBuilder::new(["Dave", "Garry", "Maxine", "Elliot"])
+            .seed(seed)
+            .reaction_priority(priority)
+            .build();
+        let tax = game
+            .actions()
+            .all()
+            .find(|a| a.kind() == Act::Tax)
+            .cloned()
+            .expect("Tax is always possible");
+        let ActionKind::OnlyChallengeable(game) = game.play(tax) else {
+            panic!("Tax can only be challenged")
+        };
+        game
+    }
+
+    fn challenges(game: &CoupGame<OnlyChallengeable>) -> Vec<Challenge> {
+        let mut challenges: Vec<_> = game.challenges().all().values().cloned().collect();
+        challenges.sort_by_key(|c| c.challenger());
+        challenges
+    }
+
+    #[test]
+    fn turn_order_goes_from_the_actor() {
+        let mut game = tax(ReactionPriority::TurnOrder, 8);
+        let order: Vec<_> = game.game_info().players.turn_order().collect();
+        let mut challenges = challenges(&game);
+        assert_eq!(challenges.len(), 3);
+
+        // no matter the order they arrive in
+        challenges.reverse();
+        let picked = game.resolve(challenges.clone()).unwrap();
+        assert_eq!(picked.challenger(), order[1]);
+        challenges.retain(|c| c.challenger() != order[1]);
+        assert_eq!(game.resolve(challenges).unwrap().challenger(), order[2]);
+        assert!(game.resolve(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn random_draws_are_seeded() {
+        let picks = |seed| {
+            let mut game = tax(ReactionPriority::Random, seed);
+            let challenges = challenges(&game);
+            (0..8)
+                .map(|_| game.resolve(challenges.clone()).unwrap().challenger())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(picks(3), picks(3));
+        let picked = picks(3);
+        assert!(picked.iter().any(|id| *id != picked[0]));
+    }
+}
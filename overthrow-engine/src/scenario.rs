@@ -0,0 +1,423 @@
+use crate::coins::{CoinPile, PlayerCoins, STARTING_COINS};
+use crate::deck::{Card, Deck, Hand, STARTING_DECK};
+use crate::machine::{CoupData, CoupGame, Wait};
+use crate::observer::{Observer, Observers};
+use crate::player_map::{Player, PlayerMap};
+use crate::players::PlayerId;
+use crate::priority::{ReactionPriority, ReactionWindow};
+use crate::undo::UndoStack;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::fmt;
+
+// Sets up a game in a specific position, rather than from a random deal. Every card and coin has
+// to be accounted for: hands (including dead cards) and the deck have to add up to the standard
+// deck, and player coins and the treasury have to add up to the 50 coins a game starts with.
+//
+// let game = ScenarioBuilder::new(["Dave", "Garry"])
+//     .hand(PlayerId::One, Hand::Full(Card::Duke, Card::Duke))
+//     .hand(PlayerId::Two, Hand::Last { alive: Card::Captain, dead: Card::Assassin })
+//     .coins(PlayerId::One, 7)
+//     .build()?;
+//
+// The built game is waiting on the current player's action, and any other decision point can be
+// reached from there by playing into it.
+#[derive(Debug)]
+pub struct ScenarioBuilder {
+    names: Vec<String>,
+    seats: Vec<Seat>,
+    // in the order cards are drawn
+    deck: Option<Vec<Card>>,
+    treasury: Option<u8>,
+    current: Option<PlayerId>,
+    seed: u64,
+    observers: Observers,
+    undo_limit: usize,
+    priority: ReactionPriority,
+    window: ReactionWindow,
+    // first player set up that isn't part of the game
+    unknown: Option<PlayerId>,
+}
+
+#[derive(Debug, Clone)]
+enum Seat {
+    Alive { hand: Option<Hand>, coins: u8 },
+    Dead([Card; 2]),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScenarioError {
+    PlayerCount(usize),
+    UnknownPlayer(PlayerId),
+    MissingHand(PlayerId),
+    // current player has to be alive
+    DeadCurrentPlayer(PlayerId),
+    // fewer than two players are alive
+    GameOver,
+    // a card doesn't appear as many times as it does in the standard deck
+    CardCount {
+        card: Card,
+        expected: usize,
+        found: usize,
+    },
+    CoinCount {
+        expected: u32,
+        found: u32,
+    },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::PlayerCount(count) => {
+                write!(f, "Games need between 2 and 6 players, found {count}")
+            }
+            ScenarioError::UnknownPlayer(id) => write!(f, "Player {id} is not in the game"),
+            ScenarioError::MissingHand(id) => write!(f, "Player {id} has no hand"),
+            ScenarioError::DeadCurrentPlayer(id) => {
+                write!(f, "Current player {id} is dead")
+            }
+            ScenarioError::GameOver => write!(f, "At least two players have to be alive"),
+            ScenarioError::CardCount {
+                card,
+                expected,
+                found,
+            } => write!(f, "Expected {expected} of {card} in total, found {found}"),
+            ScenarioError::CoinCount { expected, found } => {
+                write!(f, "Expected {expected} coins in total, found {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl ScenarioBuilder {
+    // players start alive with two coins and no hand
+    pub fn new<T>(players: T) -> ScenarioBuilder
+    where
+        T: IntoIterator,
+        T::Item: AsRef<str>,
+    {
+        let names: Vec<String> = players
+            .into_iter()
+            .map(|name| name.as_ref().to_owned())
+            .collect();
+        let seats = vec![
+            Seat::Alive {
+                hand: None,
+                coins: 2
+            };
+            names.len()
+        ];
+
+        ScenarioBuilder {
+            names,
+            seats,
+            deck: None,
+            treasury: None,
+            current: None,
+            seed: 0,
+            observers: Observers::default(),
+            undo_limit: 0,
+            priority: ReactionPriority::default(),
+            window: ReactionWindow::default(),
+            unknown: None,
+        }
+    }
+
+    fn seat(&mut self, id: PlayerId) -> Option<&mut Seat> {
+        let seat = self.seats.get_mut(id as usize - 1);
+        if seat.is_none() {
+            self.unknown.get_or_insert(id);
+        }
+        seat
+    }
+
+    // gives an alive player their hand, where Hand::Last includes the card they already lost
+    pub fn hand(mut self, id: PlayerId, hand: Hand) -> ScenarioBuilder {
+        if let Some(seat) = self.seat(id) {
+            let coins = match seat {
+                Seat::Alive { coins, .. } => *coins,
+                Seat::Dead(..) => 2,
+            };
+            *seat = Seat::Alive {
+                hand: Some(hand),
+                coins,
+            };
+        }
+        self
+    }
+
+    // coins of an alive player
+    pub fn coins(mut self, id: PlayerId, amount: u8) -> ScenarioBuilder {
+        if let Some(Seat::Alive { coins, .. }) = self.seat(id) {
+            *coins = amount;
+        }
+        self
+    }
+
+    // kills a player, who has revealed both of their cards (and has no coins)
+    pub fn dead(mut self, id: PlayerId, revealed: [Card; 2]) -> ScenarioBuilder {
+        if let Some(seat) = self.seat(id) {
+            *seat = Seat::Dead(revealed);
+        }
+        self
+    }
+
+    // exact order of the deck, starting with the next card to be drawn. Without one, cards
+    // that aren't in a hand are shuffled into the deck
+    pub fn deck(mut self, cards: impl IntoIterator<Item = Card>) -> ScenarioBuilder {
+        self.deck = Some(cards.into_iter().collect());
+        self
+    }
+
+    // coins left in the treasury. Without one, the treasury holds whatever players don't
+    pub fn treasury(mut self, coins: u8) -> ScenarioBuilder {
+        self.treasury = Some(coins);
+        self
+    }
+
+    // player whose turn it is, otherwise the first alive player. The rest take their turns in
+    // seat order
+    pub fn current(mut self, id: PlayerId) -> ScenarioBuilder {
+        if self.seat(id).is_some() {
+            self.current = Some(id);
+        }
+        self
+    }
+
+    // seeds the shuffles that happen during the game (like returning cards after an exchange)
+    pub fn seed(mut self, seed: u64) -> ScenarioBuilder {
+        self.seed = seed;
+        self
+    }
+
+    // observers are told about transitions in the order they were registered
+    pub fn observer(mut self, observer: impl Observer + 'static) -> ScenarioBuilder {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    // checkpoints that can be undone, like for replaying a position in different ways (see
+    // CoupGame::undo)
+    pub fn undo_limit(mut self, limit: usize) -> ScenarioBuilder {
+        self.undo_limit = limit;
+        self
+    }
+
+    // how simultaneous reactions are picked between (see CoupGame::resolve)
+    pub fn reaction_priority(mut self, priority: ReactionPriority) -> ScenarioBuilder {
+        self.priority = priority;
+        self
+    }
+
+    // whether blocks and challenges are taken together, or challenges first
+    pub fn reaction_window(mut self, window: ReactionWindow) -> ScenarioBuilder {
+        self.window = window;
+        self
+    }
+
+    pub fn build(self) -> Result<CoupGame<Wait>, ScenarioError> {
+        if !(2..=6).contains(&self.names.len()) {
+            return Err(ScenarioError::PlayerCount(self.names.len()));
+        }
+        if let Some(id) = self.unknown {
+            return Err(ScenarioError::UnknownPlayer(id));
+        }
+
+        let mut cards = Vec::new();
+        let mut players = Vec::new();
+        for ((id, name), seat) in PlayerId::iter().zip(self.names).zip(self.seats) {
+            match seat {
+                Seat::Alive { hand: None, .. } => return Err(ScenarioError::MissingHand(id)),
+                Seat::Alive {
+                    hand: Some(hand),
+                    coins,
+                } => {
+                    match hand {
+                        Hand::Full(c1, c2) => cards.extend([c1, c2]),
+                        Hand::Last { alive, dead } => cards.extend([alive, dead]),
+                    }
+                    players.push(Player::alive(name, PlayerCoins::new(coins), hand));
+                }
+                Seat::Dead(revealed) => {
+                    cards.extend(revealed);
+                    players.push(Player::dead(name, revealed));
+                }
+            }
+        }
+
+        let alive: Vec<PlayerId> = PlayerId::iter()
+            .zip(&players)
+            .filter(|(_, player)| matches!(player, Player::Alive(..)))
+            .map(|(id, _)| id)
+            .collect();
+        if alive.len() < 2 {
+            return Err(ScenarioError::GameOver);
+        }
+        let current = self.current.unwrap_or(alive[0]);
+        if !alive.contains(&current) {
+            return Err(ScenarioError::DeadCurrentPlayer(current));
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let deck = match self.deck {
+            Some(mut deck) => {
+                cards.extend(&deck);
+                // the deck draws from the end
+                deck.reverse();
+                Deck::from_cards(deck)
+            }
+            None => {
+                let mut rest: Vec<Card> = STARTING_DECK.into();
+                for card in &cards {
+                    if let Some(index) = rest.iter().position(|c| c == card) {
+                        rest.swap_remove(index);
+                    }
+                }
+                cards.extend(&rest);
+                let mut deck = Deck::from_cards(rest);
+                deck.shuffle(&mut rng);
+                deck
+            }
+        };
+        for card in Card::iter() {
+            let count = |cards: &[Card]| cards.iter().filter(|c| **c == card).count();
+            let (expected, found) = (count(&STARTING_DECK), count(&cards));
+            if expected != found {
+                return Err(ScenarioError::CardCount {
+                    card,
+                    expected,
+                    found,
+                });
+            }
+        }
+
+        let held: u32 = players
+            .iter()
+            .map(|player| match player {
+                Player::Alive(data) => u32::from(data.coins().amount()),
+                Player::Dead(..) => 0,
+            })
+            .sum();
+        let expected = u32::from(STARTING_COINS);
+        let treasury = match self.treasury {
+            Some(treasury) => u32::from(treasury),
+            None => expected.saturating_sub(held),
+        };
+        if held + treasury != expected {
+            return Err(ScenarioError::CoinCount {
+                expected,
+                found: held + treasury,
+            });
+        }
+
+        let players = PlayerMap::with_position(players, current);
+        let possible_actions = players.generate_actions_for(current);
+        let data = Box::new(CoupData {
+            players,
+            coins: CoinPile::new(treasury as u8),
+            deck,
+            seed: self.seed,
+            rng,
+            observers: self.observers,
+            undo: UndoStack::new(self.undo_limit),
+            priority: self.priority,
+            window: self.window,
+        });
+
+        Ok(CoupGame {
+            data,
+            state: Wait { possible_actions },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::WaitState;
+
+    fn two_players() -> ScenarioBuilder {
+        ScenarioBuilder::new(["Dave", "Garry"])
+            .hand(PlayerId::One, Hand::Full(Card::Duke, Card::Duke))
+            .hand(
+                PlayerId::Two,
+                Hand::Last {
+                    alive: Card::Captain,
+                    dead: Card::Assassin,
+                },
+            )
+    }
+
+    #[test]
+    fn builds_position() {
+        let game = two_players()
+            .coins(PlayerId::Two, 9)
+            .current(PlayerId::Two)
+            .build()
+            .unwrap();
+        let info = game.info();
+
+        assert_eq!(info.current_player, PlayerId::Two);
+        assert_eq!(info.coins_remaining, 50 - 2 - 9);
+        assert_eq!(info.deck.len(), 11);
+        assert!(!game.actions().coups().is_empty());
+    }
+
+    #[test]
+    fn exact_deck_is_drawn_in_order() {
+        let mut deck = vec![Card::Contessa, Card::Ambassador];
+        deck.extend([Card::Ambassador, Card::Ambassador, Card::Duke]);
+        deck.extend([Card::Contessa; 2]);
+        deck.extend([Card::Assassin; 2]);
+        deck.extend([Card::Captain; 2]);
+
+        let game = two_players().deck(deck).build().unwrap();
+        assert_eq!(game.info().deck.last(), Some(&Card::Contessa));
+    }
+
+    #[test]
+    fn totals_are_checked() {
+        let error = two_players()
+            .hand(PlayerId::Two, Hand::Full(Card::Duke, Card::Duke))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ScenarioError::CardCount {
+                card: Card::Duke,
+                expected: 3,
+                found: 4
+            }
+        );
+
+        let error = two_players().treasury(10).build().unwrap_err();
+        assert_eq!(
+            error,
+            ScenarioError::CoinCount {
+                expected: 50,
+                found: 14
+            }
+        );
+    }
+
+    #[test]
+    fn positions_are_checked() {
+        let error = two_players()
+            .dead(PlayerId::Two, [Card::Captain, Card::Assassin])
+            .build()
+            .unwrap_err();
+        assert_eq!(error, ScenarioError::GameOver);
+
+        let error = two_players()
+            .hand(PlayerId::Three, Hand::Full(Card::Duke, Card::Captain))
+            .build()
+            .unwrap_err();
+        assert_eq!(error, ScenarioError::UnknownPlayer(PlayerId::Three));
+
+        let error = ScenarioBuilder::new(["Dave", "Garry"]).build().unwrap_err();
+        assert_eq!(error, ScenarioError::MissingHand(PlayerId::One));
+    }
+}
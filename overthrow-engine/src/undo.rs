@@ -0,0 +1,233 @@
+use crate::coins::CoinPile;
+use crate::deck::Deck;
+use crate::driver::Phase;
+use crate::machine::{
+    Block, ChooseOneFromThree, ChooseTwoFromFour, ChooseVictimCard, CoupData, CoupGame,
+    CoupGameState, OnlyBlockable, OnlyChallengeable, Reactable, Wait,
+};
+use crate::player_map::PlayerMap;
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
+
+// Rewinding to earlier decision points, for things like hotseat and teaching games. A game built
+// with an undo limit (see GameBuilder::undo_limit) keeps snapshots of up to that many checkpoints,
+// dropping the oldest ones first. The driver checkpoints before every decision, and anything
+// driving the state machine directly can call checkpoint itself (on whichever decision points it
+// wants to be able to go back to).
+//
+// Snapshots include the game's rng, so making the same decisions again after an undo draws the
+// same cards.
+
+// everything about a game that changes as it is played
+#[derive(Debug)]
+struct Snapshot {
+    players: PlayerMap,
+    coins: CoinPile,
+    deck: Deck,
+    rng: ChaCha8Rng,
+    state: Saved,
+}
+
+// decision point a snapshot was taken at
+#[derive(Debug)]
+enum Saved {
+    Wait(Wait),
+    OnlyChallengeable(OnlyChallengeable),
+    OnlyBlockable(OnlyBlockable),
+    Reactable(Reactable),
+    Block(Block),
+    ChooseVictimCard(ChooseVictimCard),
+    ChooseOneFromThree(ChooseOneFromThree),
+    ChooseTwoFromFour(ChooseTwoFromFour),
+}
+
+// a limit of 0 (the default) turns undo off
+#[derive(Debug, Default)]
+pub(crate) struct UndoStack {
+    limit: usize,
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl UndoStack {
+    pub(crate) fn new(limit: usize) -> UndoStack {
+        UndoStack {
+            limit,
+            snapshots: VecDeque::with_capacity(limit),
+        }
+    }
+
+    // forgets every checkpoint, for when going back would undo something that can't be
+    pub(crate) fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+impl CoupData {
+    fn save(&mut self, state: Saved) {
+        if self.undo.snapshots.len() == self.undo.limit {
+            self.undo.snapshots.pop_front();
+        }
+        self.undo.snapshots.push_back(Snapshot {
+            players: self.players.clone(),
+            coins: self.coins.clone(),
+            deck: self.deck.clone(),
+            rng: self.rng.clone(),
+            state,
+        });
+    }
+}
+
+// only decision points can be checkpointed, as those are the only states anyone waits in
+macro_rules! checkpoint {
+    ($($state:ident),*) => {$(
+        impl CoupGame<$state> {
+            // saves the game as it is now, to come back to with undo. Does nothing if the game
+            // was built without an undo limit
+            pub fn checkpoint(&mut self) {
+                if self.data.undo.limit == 0 {
+                    return;
+                }
+                let state = Saved::$state(self.state.clone());
+                self.data.save(state);
+            }
+        }
+    )*};
+}
+
+checkpoint!(
+    Wait,
+    OnlyChallengeable,
+    OnlyBlockable,
+    Reactable,
+    Block,
+    ChooseVictimCard,
+    ChooseOneFromThree,
+    ChooseTwoFromFour
+);
+
+impl<S: CoupGameState> CoupGame<S> {
+    // number of checkpoints that can be gone back to
+    pub fn undo_depth(&self) -> usize {
+        self.data.undo.snapshots.len()
+    }
+
+    // goes back to the latest checkpoint, or gives the game back unchanged if there isn't one
+    pub fn undo(mut self) -> Result<Phase, CoupGame<S>> {
+        let Some(snapshot) = self.data.undo.snapshots.pop_back() else {
+            return Err(self);
+        };
+
+        let mut data = self.data;
+        data.players = snapshot.players;
+        data.coins = snapshot.coins;
+        data.deck = snapshot.deck;
+        data.rng = snapshot.rng;
+        data.observers.notify(|o| o.undone());
+
+        let phase = match snapshot.state {
+            Saved::Wait(state) => Phase::Wait(CoupGame { data, state }),
+            Saved::OnlyChallengeable(state) => Phase::OnlyChallengeable(CoupGame { data, state }),
+            Saved::OnlyBlockable(state) => Phase::OnlyBlockable(CoupGame { data, state }),
+            Saved::Reactable(state) => Phase::Reactable(CoupGame { data, state }),
+            Saved::Block(state) => Phase::Block(CoupGame { data, state }),
+            Saved::ChooseVictimCard(state) => Phase::ChooseVictimCard(CoupGame { data, state }),
+            Saved::ChooseOneFromThree(state) => Phase::ChooseOneFromThree(CoupGame { data, state }),
+            Saved::ChooseTwoFromFour(state) => Phase::ChooseTwoFromFour(CoupGame { data, state }),
+        };
+        Ok(phase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameBuilder;
+    use crate::driver::Decision;
+    use crate::machine::WaitState;
+
+    fn first_option(phase: Phase) -> Phase {
+        let decision = phase.options().into_iter().next().expect("Game isn't over");
+        phase
+            .decide(decision)
+            .expect("Options are always valid")
+            .phase
+    }
+
+    #[test]
+    fn undo_goes_back_a_decision() {
+        let phase = Phase::from(
+            GameBuilder::new(["Dave", "Garry"])
+                .seed(4)
+                .undo_limit(10)
+                .build(),
+        );
+        let before = format!("{:?}", phase.info().players);
+        let actor = phase.info().current_player;
+
+        let phase = first_option(phase);
+        assert_eq!(phase.undo_depth(), 1);
+
+        let phase = phase.undo().expect("Decision was checkpointed");
+        assert!(matches!(phase, Phase::Wait(..)));
+        assert_eq!(phase.info().current_player, actor);
+        assert_eq!(format!("{:?}", phase.info().players), before);
+        assert!(phase.undo().is_err());
+    }
+
+    #[test]
+    fn redoing_draws_the_same_cards() {
+        let builder = || {
+            GameBuilder::new(["Dave", "Garry", "Maxine"])
+                .seed(2)
+                .undo_limit(3)
+        };
+        let mut phase = Phase::from(builder().build());
+
+        // play until an exchange, which draws from the deck and shuffles it afterwards
+        let exchange = loop {
+            let exchange = phase.options().into_iter().find(|d| {
+                matches!(d, Decision::Act(action) if action.kind() == crate::action::Act::Exchange)
+            });
+            if let Some(exchange) = exchange {
+                break exchange;
+            }
+            phase = first_option(phase);
+        };
+
+        let play = |phase: Phase| {
+            let phase = phase.decide(exchange.clone()).unwrap().phase;
+            let phase = phase.decide(Decision::Pass).unwrap().phase;
+            let phase = first_option(phase);
+            (
+                format!("{:?}", phase.info().players),
+                phase.info().deck.to_vec(),
+                phase,
+            )
+        };
+
+        let (players, deck, phase) = play(phase);
+        // back to before the exchange, which is as far as the limit goes
+        let phase = phase.undo().unwrap().undo().unwrap().undo().unwrap();
+        assert_eq!(phase.undo_depth(), 0);
+        let (again, deck_again, _) = play(phase);
+        assert_eq!(players, again);
+        assert_eq!(deck, deck_again);
+    }
+
+    #[test]
+    fn oldest_checkpoints_are_dropped() {
+        let mut phase = Phase::from(
+            GameBuilder::new(["Dave", "Garry"])
+                .seed(4)
+                .undo_limit(3)
+                .build(),
+        );
+        for _ in 0..5 {
+            phase = first_option(phase);
+        }
+        assert_eq!(phase.undo_depth(), 3);
+
+        let games_without_undo = Phase::from(CoupGame::with_seed(["Dave", "Garry"], 4));
+        assert_eq!(first_option(games_without_undo).undo_depth(), 0);
+    }
+}
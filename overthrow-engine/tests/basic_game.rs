@@ -1,14 +1,22 @@
 use overthrow_engine::action::Act;
+use overthrow_engine::action::Reaction;
+use overthrow_engine::deck::Card;
+use overthrow_engine::deck::Hand;
 use overthrow_engine::machine::ActionKind;
+use overthrow_engine::machine::BlockState;
+use overthrow_engine::machine::ChallengeState;
+use overthrow_engine::machine::ChooseTwoFromFourState;
 use overthrow_engine::machine::ChooseVictimCardState;
 use overthrow_engine::machine::CoupGame;
 use overthrow_engine::machine::GameState;
 use overthrow_engine::machine::OnlyChallengeableState;
+use overthrow_engine::machine::Outcome;
 use overthrow_engine::machine::ReactableState;
 use overthrow_engine::machine::SafeState;
 use overthrow_engine::machine::Wait;
 use overthrow_engine::machine::WaitState;
 use overthrow_engine::players::PlayerId;
+use overthrow_engine::scenario::ScenarioBuilder;
 
 #[test]
 fn basic_round() {
@@ -165,3 +173,172 @@ fn basic_game() {
         panic!("Should finish game")
     };
 }
+
+// with a set position we know exactly what an exchange draws, and that a bluff on the last card
+// ends the game
+#[test]
+fn exact_position() {
+    let game = ScenarioBuilder::new(["Dave", "Garry"])
+        .hand(PlayerId::One, Hand::Full(Card::Duke, Card::Contessa))
+        .hand(
+            PlayerId::Two,
+            Hand::Last {
+                alive: Card::Captain,
+                dead: Card::Assassin,
+            },
+        )
+        .deck([
+            Card::Ambassador,
+            Card::Assassin,
+            Card::Ambassador,
+            Card::Ambassador,
+            Card::Assassin,
+            Card::Captain,
+            Card::Captain,
+            Card::Contessa,
+            Card::Contessa,
+            Card::Duke,
+            Card::Duke,
+        ])
+        .build()
+        .expect("Position is consistent");
+    assert_eq!(game.info().current_player, PlayerId::One);
+
+    // Player 1
+    let action = game
+        .actions()
+        .basic()
+        .iter()
+        .find(|action| action.kind() == Act::Exchange)
+        .expect("Always possible")
+        .clone();
+
+    let ActionKind::OnlyChallengeable(game) = game.play(action) else {
+        panic!("Should be a challengeable action")
+    };
+
+    let GameState::ChooseTwoFromFour(game) = game.advance() else {
+        panic!("Should choose cards to keep")
+    };
+
+    assert_eq!(
+        game.choices(),
+        [Card::Ambassador, Card::Assassin, Card::Duke, Card::Contessa]
+    );
+    let game = game.advance([Card::Assassin, Card::Duke]);
+
+    // Player 2
+    let action = game
+        .actions()
+        .basic()
+        .iter()
+        .find(|action| action.kind() == Act::Tax)
+        .expect("Always possible if you lie ;)")
+        .clone();
+
+    let ActionKind::OnlyChallengeable(game) = game.play(action) else {
+        panic!("Should be a challengeable action")
+    };
+
+    let challenge = game.challenges().all()[&PlayerId::One].clone();
+    let game = game.challenge(challenge);
+
+    let GameState::End(game) = game.advance() else {
+        panic!("Should finish game")
+    };
+    assert_eq!(game.winner(), PlayerId::One);
+}
+
+// the victim of an assassination blocks it by claiming Contessa, so holding one proves the block
+#[test]
+fn blocking_assassination_claims_contessa() {
+    let game = ScenarioBuilder::new(["Dave", "Garry"])
+        .hand(PlayerId::One, Hand::Full(Card::Assassin, Card::Duke))
+        .hand(PlayerId::Two, Hand::Full(Card::Contessa, Card::Captain))
+        .coins(PlayerId::One, 3)
+        .build()
+        .expect("Position is consistent");
+
+    let action = game
+        .actions()
+        .assassinations()
+        .iter()
+        .find(|action| {
+            action.kind()
+                == Act::Assassinate {
+                    victim: PlayerId::Two,
+                }
+        })
+        .expect("Player 1 can afford it")
+        .clone();
+
+    let ActionKind::Reactable(game) = game.play(action) else {
+        panic!("Should be a reactable action")
+    };
+
+    let reactions = game.reactions().all();
+    let block = reactions[&PlayerId::Two]
+        .iter()
+        .find_map(|reaction| match reaction {
+            Reaction::Block(block) => Some(block.clone()),
+            Reaction::Challenge(_) => None,
+        })
+        .expect("Victim can block");
+    assert_eq!(block.claim(), Card::Contessa);
+
+    let game = game.block(block);
+    let challenge = game.challenges().all()[&PlayerId::One].clone();
+    let game = game.challenge(challenge);
+    assert!(matches!(
+        game.outcome(),
+        Outcome::LosesInfluence {
+            victim: PlayerId::One
+        }
+    ));
+}
+
+// a steal is blocked by the victim claiming either Captain or Ambassador
+#[test]
+fn steal_victim_can_block_with_either_card() {
+    let game = ScenarioBuilder::new(["Dave", "Garry", "Harry"])
+        .hand(PlayerId::One, Hand::Full(Card::Captain, Card::Duke))
+        .hand(PlayerId::Two, Hand::Full(Card::Contessa, Card::Assassin))
+        .hand(PlayerId::Three, Hand::Full(Card::Duke, Card::Contessa))
+        .build()
+        .expect("Position is consistent");
+
+    let action = game
+        .actions()
+        .steal()
+        .iter()
+        .find(|action| {
+            action.kind()
+                == Act::Steal {
+                    victim: PlayerId::Two,
+                }
+        })
+        .expect("Player 2 has coins to steal")
+        .clone();
+
+    let ActionKind::Reactable(game) = game.play(action) else {
+        panic!("Should be a reactable action")
+    };
+
+    let reactions = game.reactions().all();
+    let mut claims: Vec<Card> = reactions[&PlayerId::Two]
+        .iter()
+        .filter_map(|reaction| match reaction {
+            Reaction::Block(block) => Some(block.claim()),
+            Reaction::Challenge(_) => None,
+        })
+        .collect();
+    claims.sort_by_key(|card| *card as u8);
+    assert_eq!(claims, [Card::Ambassador, Card::Captain]);
+
+    // nobody else can block it
+    assert!(
+        reactions[&PlayerId::Three]
+            .iter()
+            .all(|reaction| matches!(reaction, Reaction::Challenge(_)))
+    );
+}
@@ -0,0 +1,35 @@
+use overthrow_engine::notation::Notation;
+use std::fs;
+use std::path::Path;
+
+// every game in tests/scenarios has to parse, replay as written and print back the same way
+#[test]
+fn scenarios_replay() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenarios");
+    let mut count = 0;
+
+    for entry in fs::read_dir(dir).expect("Scenarios directory exists") {
+        let path = entry.unwrap().path();
+        let text = fs::read_to_string(&path).unwrap();
+        let notation: Notation = text
+            .parse()
+            .unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+
+        let (phase, history) = notation
+            .replay()
+            .unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+        assert_eq!(phase.is_over(), notation.result.is_some());
+
+        let printed = Notation::from_history(
+            notation.seed,
+            notation.rules,
+            notation.players.clone(),
+            &history,
+        )
+        .expect("Replayed history is valid");
+        assert_eq!(printed, notation);
+        count += 1;
+    }
+
+    assert!(count > 0);
+}
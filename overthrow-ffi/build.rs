@@ -0,0 +1,25 @@
+use std::env;
+use std::path::PathBuf;
+
+// generates the header into OUT_DIR on every build. The copy checked in at include/overthrow.h is
+// only rewritten when OVERTHROW_UPDATE_HEADER is set, so builds don't touch the source tree
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("cbindgen.toml should be valid");
+
+    let bindings = cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Header should generate");
+    bindings.write_to_file(out_dir.join("overthrow.h"));
+    if env::var_os("OVERTHROW_UPDATE_HEADER").is_some() {
+        bindings.write_to_file(crate_dir.join("include/overthrow.h"));
+    }
+
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=OVERTHROW_UPDATE_HEADER");
+}
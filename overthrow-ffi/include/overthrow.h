@@ -0,0 +1,128 @@
+#ifndef OVERTHROW_H
+#define OVERTHROW_H
+
+/* Generated by cbindgen from overthrow-ffi, do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The decision point a game is waiting on.
+typedef enum OverthrowPhase {
+  OVERTHROW_PHASE_WAIT,
+  OVERTHROW_PHASE_ONLY_CHALLENGEABLE,
+  OVERTHROW_PHASE_ONLY_BLOCKABLE,
+  OVERTHROW_PHASE_REACTABLE,
+  OVERTHROW_PHASE_BLOCK,
+  OVERTHROW_PHASE_CHOOSE_VICTIM_CARD,
+  OVERTHROW_PHASE_CHOOSE_ONE_FROM_THREE,
+  OVERTHROW_PHASE_CHOOSE_TWO_FROM_FOUR,
+  OVERTHROW_PHASE_END,
+  // A previous call panicked, the game can only be freed.
+  OVERTHROW_PHASE_POISONED,
+} OverthrowPhase;
+
+// Result of calls that change a game.
+typedef enum OverthrowStatus {
+  OVERTHROW_STATUS_OK,
+  OVERTHROW_STATUS_NULL_ARGUMENT,
+  OVERTHROW_STATUS_INVALID_UTF8,
+  OVERTHROW_STATUS_INVALID_JSON,
+  // The decision is not one of the options of the current phase.
+  OVERTHROW_STATUS_INVALID_DECISION,
+  OVERTHROW_STATUS_POISONED,
+  OVERTHROW_STATUS_PANICKED,
+} OverthrowStatus;
+
+// Opaque handle to a single game.
+typedef struct OverthrowGame OverthrowGame;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Creates a game with `count` players named by `names`, shuffled with `seed`. Returns null if
+// there aren't between 2 and 6 players or a name is not valid UTF-8.
+//
+// # Safety
+// `names` must point to `count` valid, nul terminated strings.
+struct OverthrowGame *overthrow_game_new(uint64_t seed, const char *const *names, size_t count);
+
+// Frees a game created by `overthrow_game_new`. Passing null does nothing.
+//
+// # Safety
+// `game` must be null or a handle that has not been freed yet.
+void overthrow_game_free(struct OverthrowGame *game);
+
+// Frees a string returned by the library. Passing null does nothing.
+//
+// # Safety
+// `string` must be null or a string returned by this library that has not been freed yet.
+void overthrow_string_free(char *string);
+
+// Decision point the game is at, `Poisoned` for a null handle.
+//
+// # Safety
+// `game` must be null or a valid handle.
+enum OverthrowPhase overthrow_game_phase(const struct OverthrowGame *game);
+
+// Seat (1 to 6) of the player whose turn it is, or 0 if the game is poisoned.
+//
+// # Safety
+// `game` must be null or a valid handle.
+uint8_t overthrow_game_current_player(const struct OverthrowGame *game);
+
+// Seat of the winner, or 0 while the game is still going.
+//
+// # Safety
+// `game` must be null or a valid handle.
+uint8_t overthrow_game_winner(const struct OverthrowGame *game);
+
+// Options of the current phase as a JSON array of decisions. With a `seat` of 0 every option is
+// returned, otherwise only those of that seat (including `"Pass"` in reaction windows). Returns
+// null on error.
+//
+// # Safety
+// `game` must be null or a valid handle.
+char *overthrow_game_options(const struct OverthrowGame *game, uint8_t seat);
+
+// The game as seen from `seat` (1 to 6) as a JSON object. Only that seat's hand is included.
+// Returns null on error.
+//
+// # Safety
+// `game` must be null or a valid handle.
+char *overthrow_game_view(const struct OverthrowGame *game, uint8_t seat);
+
+// Applies a decision given as JSON, in the same format as `overthrow_game_options`.
+//
+// # Safety
+// `game` must be null or a valid handle, and `decision` null or a nul terminated string.
+enum OverthrowStatus overthrow_game_submit(struct OverthrowGame *game, const char *decision);
+
+// Applies the option at `index` of the current phase (as listed by `overthrow_game_options`
+// with a seat of 0).
+//
+// # Safety
+// `game` must be null or a valid handle.
+enum OverthrowStatus overthrow_game_submit_option(struct OverthrowGame *game, size_t index);
+
+// Outcome of the last decision as JSON, `null` if it didn't resolve anything. Returns null on
+// error.
+//
+// # Safety
+// `game` must be null or a valid handle.
+char *overthrow_game_outcome(const struct OverthrowGame *game);
+
+// Every decision applied so far, with its outcome, as a JSON array. Returns null on error.
+//
+// # Safety
+// `game` must be null or a valid handle.
+char *overthrow_game_history(const struct OverthrowGame *game);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* OVERTHROW_H */
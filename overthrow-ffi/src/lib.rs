@@ -0,0 +1,519 @@
+//! C API for the overthrow engine.
+//!
+//! Games are opaque handles created with `overthrow_game_new` and owned by the caller until they
+//! are passed to `overthrow_game_free`. Strings returned by the library are owned by the caller
+//! and must be released with `overthrow_string_free`. Strings passed in are only borrowed for the
+//! duration of the call. Panics never cross the boundary: a call that panics returns its error
+//! value instead, and a game that panicked while changing state is poisoned.
+
+use overthrow_engine::deck::{Card, Hand};
+use overthrow_engine::driver::{Decision, Phase, Record};
+use overthrow_engine::machine::{CoupGame, WaitState};
+use overthrow_engine::player_map::Player;
+use overthrow_engine::players::PlayerId;
+use serde::Serialize;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// Opaque handle to a single game.
+pub struct OverthrowGame {
+    // None once a panic left the game in an unknown state
+    phase: Option<Phase>,
+    history: Vec<Record>,
+}
+
+/// The decision point a game is waiting on.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverthrowPhase {
+    Wait,
+    OnlyChallengeable,
+    OnlyBlockable,
+    Reactable,
+    Block,
+    ChooseVictimCard,
+    ChooseOneFromThree,
+    ChooseTwoFromFour,
+    End,
+    /// A previous call panicked, the game can only be freed.
+    Poisoned,
+}
+
+/// Result of calls that change a game.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverthrowStatus {
+    Ok,
+    NullArgument,
+    InvalidUtf8,
+    InvalidJson,
+    /// The decision is not one of the options of the current phase.
+    InvalidDecision,
+    Poisoned,
+    Panicked,
+}
+
+// runs f, turning any panic into fallback
+fn guard<T>(fallback: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(fallback)
+}
+
+fn to_c_string(value: &impl Serialize) -> *mut c_char {
+    serde_json::to_string(value)
+        .ok()
+        .and_then(|json| CString::new(json).ok())
+        .map_or(ptr::null_mut(), CString::into_raw)
+}
+
+fn seat(seat: u8) -> Option<PlayerId> {
+    PlayerId::iter().nth(usize::from(seat).checked_sub(1)?)
+}
+
+impl OverthrowGame {
+    fn phase(&self) -> Option<&Phase> {
+        self.phase.as_ref()
+    }
+
+    fn submit(&mut self, decision: Decision) -> OverthrowStatus {
+        let Some(phase) = self.phase.take() else {
+            return OverthrowStatus::Poisoned;
+        };
+
+        match phase.decide(decision.clone()) {
+            Ok(step) => {
+                self.phase = Some(step.phase);
+                self.history.push(Record {
+                    decision,
+                    outcome: step.outcome,
+                });
+                OverthrowStatus::Ok
+            }
+            Err(invalid) => {
+                self.phase = Some(invalid.phase);
+                OverthrowStatus::InvalidDecision
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SeatView<'a> {
+    id: PlayerId,
+    name: &'a str,
+    alive: bool,
+    coins: u8,
+    // face down cards, only filled in for the seat the view is for
+    hand: Vec<Card>,
+    hidden: usize,
+    revealed: Vec<Card>,
+}
+
+#[derive(Debug, Serialize)]
+struct View<'a> {
+    seat: PlayerId,
+    current_player: PlayerId,
+    coins_remaining: u8,
+    deck: usize,
+    // seats that can decide in the current phase
+    deciders: Vec<PlayerId>,
+    players: Vec<SeatView<'a>>,
+}
+
+fn view(phase: &Phase, me: PlayerId) -> View<'_> {
+    let info = phase.info();
+    let players = info
+        .players
+        .all()
+        .map(|(id, player)| {
+            let (hidden, revealed) = match player {
+                Player::Alive(data) => match data.hand() {
+                    Hand::Full(c1, c2) => (vec![c1, c2], vec![]),
+                    Hand::Last { alive, dead } => (vec![alive], vec![dead]),
+                },
+                Player::Dead(data) => (vec![], data.revealed().to_vec()),
+            };
+
+            SeatView {
+                id,
+                name: player.name(),
+                alive: matches!(player, Player::Alive(..)),
+                coins: match player {
+                    Player::Alive(data) => data.coins().amount(),
+                    Player::Dead(..) => 0,
+                },
+                hidden: hidden.len(),
+                hand: if id == me { hidden } else { Vec::new() },
+                revealed,
+            }
+        })
+        .collect();
+
+    View {
+        seat: me,
+        current_player: info.current_player,
+        coins_remaining: info.coins_remaining,
+        deck: info.deck.len(),
+        deciders: phase.deciders(),
+        players,
+    }
+}
+
+/// Creates a game with `count` players named by `names`, shuffled with `seed`. Returns null if
+/// there aren't between 2 and 6 players or a name is not valid UTF-8.
+///
+/// # Safety
+/// `names` must point to `count` valid, nul terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_game_new(
+    seed: u64,
+    names: *const *const c_char,
+    count: usize,
+) -> *mut OverthrowGame {
+    if names.is_null() || !(2..=6).contains(&count) {
+        return ptr::null_mut();
+    }
+
+    guard(ptr::null_mut(), || {
+        // SAFETY: caller guarantees there are count names
+        let names = unsafe { std::slice::from_raw_parts(names, count) };
+        let names: Option<Vec<&str>> = names
+            .iter()
+            .map(|name| {
+                if name.is_null() {
+                    return None;
+                }
+                // SAFETY: caller guarantees every name is a valid C string
+                unsafe { CStr::from_ptr(*name) }.to_str().ok()
+            })
+            .collect();
+        let Some(names) = names else {
+            return ptr::null_mut();
+        };
+
+        let game = OverthrowGame {
+            phase: Some(Phase::from(CoupGame::with_seed(names, seed))),
+            history: Vec::new(),
+        };
+        Box::into_raw(Box::new(game))
+    })
+}
+
+/// Frees a game created by `overthrow_game_new`. Passing null does nothing.
+///
+/// # Safety
+/// `game` must be null or a handle that has not been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_game_free(game: *mut OverthrowGame) {
+    if !game.is_null() {
+        // SAFETY: caller hands ownership back
+        drop(unsafe { Box::from_raw(game) });
+    }
+}
+
+/// Frees a string returned by the library. Passing null does nothing.
+///
+/// # Safety
+/// `string` must be null or a string returned by this library that has not been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        // SAFETY: string was created by CString::into_raw
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+/// Decision point the game is at, `Poisoned` for a null handle.
+///
+/// # Safety
+/// `game` must be null or a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_game_phase(game: *const OverthrowGame) -> OverthrowPhase {
+    // SAFETY: caller guarantees game is valid
+    let Some(game) = (unsafe { game.as_ref() }) else {
+        return OverthrowPhase::Poisoned;
+    };
+
+    match game.phase() {
+        Some(Phase::Wait(..)) => OverthrowPhase::Wait,
+        Some(Phase::OnlyChallengeable(..)) => OverthrowPhase::OnlyChallengeable,
+        Some(Phase::OnlyBlockable(..)) => OverthrowPhase::OnlyBlockable,
+        Some(Phase::Reactable(..)) => OverthrowPhase::Reactable,
+        Some(Phase::Block(..)) => OverthrowPhase::Block,
+        Some(Phase::ChooseVictimCard(..)) => OverthrowPhase::ChooseVictimCard,
+        Some(Phase::ChooseOneFromThree(..)) => OverthrowPhase::ChooseOneFromThree,
+        Some(Phase::ChooseTwoFromFour(..)) => OverthrowPhase::ChooseTwoFromFour,
+        Some(Phase::End(..)) => OverthrowPhase::End,
+        None => OverthrowPhase::Poisoned,
+    }
+}
+
+/// Seat (1 to 6) of the player whose turn it is, or 0 if the game is poisoned.
+///
+/// # Safety
+/// `game` must be null or a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_game_current_player(game: *const OverthrowGame) -> u8 {
+    // SAFETY: caller guarantees game is valid
+    let phase = unsafe { game.as_ref() }.and_then(OverthrowGame::phase);
+    guard(0, || {
+        phase.map_or(0, |phase| phase.info().current_player as u8)
+    })
+}
+
+/// Seat of the winner, or 0 while the game is still going.
+///
+/// # Safety
+/// `game` must be null or a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_game_winner(game: *const OverthrowGame) -> u8 {
+    // SAFETY: caller guarantees game is valid
+    let phase = unsafe { game.as_ref() }.and_then(OverthrowGame::phase);
+    guard(0, || match phase {
+        Some(Phase::End(game)) => game.winner() as u8,
+        _ => 0,
+    })
+}
+
+/// Options of the current phase as a JSON array of decisions. With a `seat` of 0 every option is
+/// returned, otherwise only those of that seat (including `"Pass"` in reaction windows). Returns
+/// null on error.
+///
+/// # Safety
+/// `game` must be null or a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_game_options(
+    game: *const OverthrowGame,
+    seat: u8,
+) -> *mut c_char {
+    // SAFETY: caller guarantees game is valid
+    let Some(phase) = unsafe { game.as_ref() }.and_then(OverthrowGame::phase) else {
+        return ptr::null_mut();
+    };
+
+    guard(ptr::null_mut(), || {
+        if seat == 0 {
+            return to_c_string(&phase.options());
+        }
+
+        let Some(id) = self::seat(seat) else {
+            return ptr::null_mut();
+        };
+        let mut options = phase.options_for(id);
+        if phase.is_reaction_window() && !options.is_empty() {
+            options.push(Decision::Pass);
+        }
+        to_c_string(&options)
+    })
+}
+
+/// The game as seen from `seat` (1 to 6) as a JSON object. Only that seat's hand is included.
+/// Returns null on error.
+///
+/// # Safety
+/// `game` must be null or a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_game_view(game: *const OverthrowGame, seat: u8) -> *mut c_char {
+    // SAFETY: caller guarantees game is valid
+    let Some(phase) = unsafe { game.as_ref() }.and_then(OverthrowGame::phase) else {
+        return ptr::null_mut();
+    };
+
+    guard(ptr::null_mut(), || match self::seat(seat) {
+        Some(id) if usize::from(seat) <= phase.info().players.count() => {
+            to_c_string(&view(phase, id))
+        }
+        _ => ptr::null_mut(),
+    })
+}
+
+/// Applies a decision given as JSON, in the same format as `overthrow_game_options`.
+///
+/// # Safety
+/// `game` must be null or a valid handle, and `decision` null or a nul terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_game_submit(
+    game: *mut OverthrowGame,
+    decision: *const c_char,
+) -> OverthrowStatus {
+    // SAFETY: caller guarantees game is valid
+    let Some(game) = (unsafe { game.as_mut() }) else {
+        return OverthrowStatus::NullArgument;
+    };
+    if decision.is_null() {
+        return OverthrowStatus::NullArgument;
+    }
+
+    guard(OverthrowStatus::Panicked, || {
+        // SAFETY: caller guarantees decision is a valid C string
+        let Ok(decision) = unsafe { CStr::from_ptr(decision) }.to_str() else {
+            return OverthrowStatus::InvalidUtf8;
+        };
+        let Ok(decision) = serde_json::from_str::<Decision>(decision) else {
+            return OverthrowStatus::InvalidJson;
+        };
+
+        game.submit(decision)
+    })
+}
+
+/// Applies the option at `index` of the current phase (as listed by `overthrow_game_options`
+/// with a seat of 0).
+///
+/// # Safety
+/// `game` must be null or a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_game_submit_option(
+    game: *mut OverthrowGame,
+    index: usize,
+) -> OverthrowStatus {
+    // SAFETY: caller guarantees game is valid
+    let Some(game) = (unsafe { game.as_mut() }) else {
+        return OverthrowStatus::NullArgument;
+    };
+
+    guard(OverthrowStatus::Panicked, || {
+        let Some(phase) = game.phase() else {
+            return OverthrowStatus::Poisoned;
+        };
+        match phase.options().into_iter().nth(index) {
+            Some(decision) => game.submit(decision),
+            None => OverthrowStatus::InvalidDecision,
+        }
+    })
+}
+
+/// Outcome of the last decision as JSON, `null` if it didn't resolve anything. Returns null on
+/// error.
+///
+/// # Safety
+/// `game` must be null or a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_game_outcome(game: *const OverthrowGame) -> *mut c_char {
+    // SAFETY: caller guarantees game is valid
+    let Some(game) = (unsafe { game.as_ref() }) else {
+        return ptr::null_mut();
+    };
+
+    guard(ptr::null_mut(), || {
+        to_c_string(&game.history.last().and_then(|entry| entry.outcome))
+    })
+}
+
+/// Every decision applied so far, with its outcome, as a JSON array. Returns null on error.
+///
+/// # Safety
+/// `game` must be null or a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn overthrow_game_history(game: *const OverthrowGame) -> *mut c_char {
+    // SAFETY: caller guarantees game is valid
+    let Some(game) = (unsafe { game.as_ref() }) else {
+        return ptr::null_mut();
+    };
+
+    guard(ptr::null_mut(), || to_c_string(&game.history))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_game(seed: u64) -> *mut OverthrowGame {
+        let names = [c"Dave", c"Garry", c"Steve"].map(CStr::as_ptr);
+        unsafe { overthrow_game_new(seed, names.as_ptr(), names.len()) }
+    }
+
+    // takes ownership of a string returned by the library
+    fn take(string: *mut c_char) -> String {
+        assert!(!string.is_null());
+        let owned = unsafe { CStr::from_ptr(string) }
+            .to_str()
+            .unwrap()
+            .to_owned();
+        unsafe { overthrow_string_free(string) };
+        owned
+    }
+
+    #[test]
+    fn plays_to_the_end() {
+        let game = new_game(7);
+        assert!(!game.is_null());
+
+        let mut decisions = 0;
+        while unsafe { overthrow_game_phase(game) } != OverthrowPhase::End {
+            assert_eq!(
+                unsafe { overthrow_game_submit_option(game, 0) },
+                OverthrowStatus::Ok
+            );
+            decisions += 1;
+        }
+
+        let history: Vec<serde_json::Value> =
+            serde_json::from_str(&take(unsafe { overthrow_game_history(game) })).unwrap();
+        assert_eq!(history.len(), decisions);
+        assert_ne!(unsafe { overthrow_game_winner(game) }, 0);
+
+        unsafe { overthrow_game_free(game) };
+    }
+
+    #[test]
+    fn submits_json() {
+        let game = new_game(1);
+        let seat = unsafe { overthrow_game_current_player(game) };
+
+        let options: Vec<Decision> =
+            serde_json::from_str(&take(unsafe { overthrow_game_options(game, seat) })).unwrap();
+        let income = options
+            .iter()
+            .find(|d| matches!(d, Decision::Act(action) if action.claim().is_none()))
+            .expect("Income or foreign aid is always possible");
+        let json = CString::new(serde_json::to_string(income).unwrap()).unwrap();
+
+        assert_eq!(
+            unsafe { overthrow_game_submit(game, c"{}".as_ptr()) },
+            OverthrowStatus::InvalidJson
+        );
+        assert_eq!(
+            unsafe { overthrow_game_submit(game, json.as_ptr()) },
+            OverthrowStatus::Ok
+        );
+        // can't play the same turn twice
+        assert_eq!(
+            unsafe { overthrow_game_submit(game, json.as_ptr()) },
+            OverthrowStatus::InvalidDecision
+        );
+
+        unsafe { overthrow_game_free(game) };
+    }
+
+    #[test]
+    fn views_hide_other_hands() {
+        let game = new_game(3);
+        let view: serde_json::Value =
+            serde_json::from_str(&take(unsafe { overthrow_game_view(game, 2) })).unwrap();
+
+        let players = view["players"].as_array().unwrap();
+        assert_eq!(players.len(), 3);
+        assert_eq!(players[1]["hand"].as_array().unwrap().len(), 2);
+        assert!(players[0]["hand"].as_array().unwrap().is_empty());
+
+        // only three seats
+        assert!(unsafe { overthrow_game_view(game, 4) }.is_null());
+        unsafe { overthrow_game_free(game) };
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let names = [c"Dave"].map(CStr::as_ptr);
+        assert!(unsafe { overthrow_game_new(0, names.as_ptr(), 1) }.is_null());
+        assert!(unsafe { overthrow_game_new(0, ptr::null(), 2) }.is_null());
+        assert_eq!(
+            unsafe { overthrow_game_phase(ptr::null()) },
+            OverthrowPhase::Poisoned
+        );
+        assert_eq!(
+            unsafe { overthrow_game_submit(ptr::null_mut(), ptr::null()) },
+            OverthrowStatus::NullArgument
+        );
+    }
+}
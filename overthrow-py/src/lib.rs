@@ -0,0 +1,232 @@
+use overthrow_engine::driver::{Decision, Phase, Record};
+use overthrow_engine::machine::{CoupGame, WaitState};
+use overthrow_engine::players::PlayerId;
+use overthrow_sim::agent::AgentKind;
+use overthrow_sim::runner::{self, Config};
+use overthrow_sim::stats::Report;
+use overthrow_types::Info;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+// values cross into Python through the same serde JSON the server sends, so records from either
+// can be handled the same way
+fn to_py<'py>(py: Python<'py>, value: &impl Serialize) -> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+fn from_py<T: DeserializeOwned>(value: &Bound<'_, PyAny>) -> PyResult<T> {
+    let json: String = value
+        .py()
+        .import("json")?
+        .call_method1("dumps", (value,))?
+        .extract()?;
+    serde_json::from_str(&json).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn seat(seat: u8, players: usize) -> PyResult<PlayerId> {
+    PlayerId::iter()
+        .take(players)
+        .nth(usize::from(seat).wrapping_sub(1))
+        .ok_or_else(|| PyValueError::new_err(format!("No player in seat {seat}")))
+}
+
+// A game that is stepped through one decision at a time. Decisions are given either as an index
+// into options(), or in the same format options() returns them in
+#[pyclass(module = "overthrow")]
+struct Game {
+    // None once a panic left the game in an unknown state
+    phase: Option<Phase>,
+    history: Vec<Record>,
+}
+
+impl Game {
+    fn phase(&self) -> PyResult<&Phase> {
+        self.phase
+            .as_ref()
+            .ok_or_else(|| PyRuntimeError::new_err("Game is in an unknown state after a panic"))
+    }
+}
+
+#[pymethods]
+impl Game {
+    #[new]
+    #[pyo3(signature = (names, seed=None))]
+    fn new(names: Vec<String>, seed: Option<u64>) -> PyResult<Game> {
+        if !(2..=6).contains(&names.len()) {
+            return Err(PyValueError::new_err("Games need between 2 and 6 players"));
+        }
+
+        let game = match seed {
+            Some(seed) => CoupGame::with_seed(names, seed),
+            None => CoupGame::with_player_names(names),
+        };
+        Ok(Game {
+            phase: Some(Phase::from(game)),
+            history: Vec::new(),
+        })
+    }
+
+    #[getter(phase)]
+    fn phase_name(&self) -> PyResult<&'static str> {
+        Ok(self.phase()?.name())
+    }
+
+    #[getter]
+    fn current_player(&self) -> PyResult<u8> {
+        Ok(self.phase()?.info().current_player as u8)
+    }
+
+    #[getter]
+    fn is_over(&self) -> PyResult<bool> {
+        Ok(self.phase()?.is_over())
+    }
+
+    #[getter]
+    fn winner(&self) -> PyResult<Option<u8>> {
+        Ok(match self.phase()? {
+            Phase::End(game) => Some(game.winner() as u8),
+            _ => None,
+        })
+    }
+
+    // seats that can decide in the current phase, in turn order
+    #[getter]
+    fn deciders(&self) -> PyResult<Vec<u8>> {
+        Ok(self
+            .phase()?
+            .deciders()
+            .into_iter()
+            .map(|id| id as u8)
+            .collect())
+    }
+
+    // every option of the current phase, or only those of seat (with "Pass" in reaction windows)
+    #[pyo3(signature = (seat=None))]
+    fn options<'py>(&self, py: Python<'py>, seat: Option<u8>) -> PyResult<Bound<'py, PyAny>> {
+        let phase = self.phase()?;
+        let Some(seat) = seat else {
+            return to_py(py, &phase.options());
+        };
+
+        let id = self::seat(seat, phase.info().players.count())?;
+        let mut options = phase.options_for(id);
+        if phase.is_reaction_window() && !options.is_empty() {
+            options.push(Decision::Pass);
+        }
+        to_py(py, &options)
+    }
+
+    // applies a decision, returning the outcome it resolved to (if any)
+    fn decide<'py>(
+        &mut self,
+        py: Python<'py>,
+        decision: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let phase = self.phase()?;
+        let decision = match decision.extract::<usize>() {
+            Ok(index) => phase
+                .options()
+                .into_iter()
+                .nth(index)
+                .ok_or_else(|| PyValueError::new_err(format!("No option at index {index}")))?,
+            Err(..) => from_py(decision)?,
+        };
+
+        let phase = self.phase.take().expect("Phase was checked above");
+        match phase.decide(decision.clone()) {
+            Ok(step) => {
+                self.phase = Some(step.phase);
+                self.history.push(Record {
+                    decision,
+                    outcome: step.outcome,
+                });
+                to_py(py, &step.outcome)
+            }
+            Err(invalid) => {
+                let message = invalid.to_string();
+                self.phase = Some(invalid.phase);
+                Err(PyValueError::new_err(message))
+            }
+        }
+    }
+
+    // the game as seen by seat, in the same format as the server's Info message
+    fn view<'py>(&self, py: Python<'py>, seat: u8) -> PyResult<Bound<'py, PyAny>> {
+        let info = self.phase()?.info();
+        let id = self::seat(seat, info.players.count())?;
+        to_py(py, &Info::new(id, &info))
+    }
+
+    // every decision applied so far, along with its outcome
+    #[getter]
+    fn history<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_py(py, &self.history)
+    }
+}
+
+fn agents(agents: Vec<String>) -> PyResult<Vec<AgentKind>> {
+    agents
+        .iter()
+        .map(|agent| {
+            serde_json::from_value(agent.as_str().into())
+                .map_err(|_| PyValueError::new_err(format!("Unknown agent: {agent}")))
+        })
+        .collect()
+}
+
+// plays a batch of games between the simulator's agents and returns the aggregated report
+#[pyfunction]
+#[pyo3(signature = (games=1000, players=4, agents=vec!["random".to_owned()], seed=0, max_turns=500))]
+fn simulate<'py>(
+    py: Python<'py>,
+    games: u64,
+    players: usize,
+    agents: Vec<String>,
+    seed: u64,
+    max_turns: u32,
+) -> PyResult<Bound<'py, PyAny>> {
+    if !(2..=6).contains(&players) || agents.is_empty() {
+        return Err(PyValueError::new_err(
+            "Simulations need between 2 and 6 players and at least one agent",
+        ));
+    }
+
+    let config = Config {
+        games,
+        players,
+        agents: self::agents(agents)?,
+        seed,
+        max_turns,
+    };
+    let records = py.allow_threads(|| runner::run(&config));
+    to_py(py, &Report::from_records(&records))
+}
+
+// plays a single game between agents (one per seat) and returns its record
+#[pyfunction]
+#[pyo3(signature = (agents, seed=0, max_turns=500))]
+fn simulate_game<'py>(
+    py: Python<'py>,
+    agents: Vec<String>,
+    seed: u64,
+    max_turns: u32,
+) -> PyResult<Bound<'py, PyAny>> {
+    if !(2..=6).contains(&agents.len()) {
+        return Err(PyValueError::new_err("Games need between 2 and 6 players"));
+    }
+
+    let seats = self::agents(agents)?;
+    let record = py.allow_threads(|| runner::simulate_game(seed, seats, max_turns));
+    to_py(py, &record)
+}
+
+#[pymodule]
+fn overthrow(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Game>()?;
+    m.add_function(wrap_pyfunction!(simulate, m)?)?;
+    m.add_function(wrap_pyfunction!(simulate_game, m)?)?;
+    Ok(())
+}
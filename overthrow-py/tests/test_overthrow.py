@@ -0,0 +1,56 @@
+import pytest
+
+import overthrow
+
+
+def play_to_end(game):
+    while not game.is_over:
+        game.decide(0)
+
+
+def test_seeded_games_repeat():
+    first = overthrow.Game(["Dave", "Garry", "Steve"], seed=7)
+    second = overthrow.Game(["Dave", "Garry", "Steve"], seed=7)
+    play_to_end(first)
+    play_to_end(second)
+
+    assert first.history == second.history
+    assert first.winner == second.winner
+    assert first.phase == "End"
+
+
+def test_decisions_match_options():
+    game = overthrow.Game(["Dave", "Garry"], seed=1)
+    seat = game.current_player
+    options = game.options(seat)
+    assert options == game.options()
+
+    income = next(d for d in options if d["Act"]["kind"] == "Income")
+    outcome = game.decide(income)
+    assert outcome == {"GainCoins": {"actor": income["Act"]["actor"], "amount": 1}}
+    assert game.history == [{"decision": income, "outcome": outcome}]
+
+    with pytest.raises(ValueError):
+        game.decide(income)
+
+
+def test_views_hide_other_hands():
+    game = overthrow.Game(["Dave", "Garry", "Steve"], seed=3)
+    views = game.view(1)["player_views"]
+
+    assert "Me" in views["One"]
+    assert "Other" in views["Two"]
+    with pytest.raises(ValueError):
+        game.view(4)
+
+
+def test_simulator():
+    report = overthrow.simulate(games=20, players=3, agents=["random", "honest", "bluffer"])
+    assert report["games"] == 20
+    assert not report["panics"]
+
+    record = overthrow.simulate_game(["honest", "bluffer"], seed=5)
+    assert record["seats"] == ["honest", "bluffer"]
+
+    with pytest.raises(ValueError):
+        overthrow.simulate(agents=["nobody"])
@@ -0,0 +1,241 @@
+use crate::dispatcher::LobbySettings;
+use crate::{AdminRequest, AppState};
+
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Request, State},
+    http::{StatusCode, header::AUTHORIZATION},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use jiff::Timestamp;
+use overthrow_types::{Commitment, Difficulty, Info, Summary};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::{Instant, timeout};
+use uuid::Uuid;
+
+// how long the dispatcher has to answer before the server is reported as not ready
+const READY_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Lobby,
+    Playing,
+    // until the game is archived, after which it is only under /games
+    Over,
+}
+
+// a lobby or game as the server's operator sees it
+#[derive(Debug, Serialize)]
+pub struct GameOverview {
+    pub id: Uuid,
+    pub code: String,
+    pub stage: Stage,
+    pub paused: bool,
+    pub created: Timestamp,
+    // in seat order
+    pub members: Vec<MemberOverview>,
+    pub spectators: Vec<SocketAddr>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberOverview {
+    pub id: Uuid,
+    pub name: String,
+    pub ready: bool,
+    pub left: bool,
+    pub connected: bool,
+    // where the player first connected from, which is what they are kicked by. None for bots
+    pub addr: Option<SocketAddr>,
+    pub bot: Option<Difficulty>,
+}
+
+// everything about a lobby or game, for debugging
+#[derive(Debug, Serialize)]
+pub struct GameDetails {
+    #[serde(flatten)]
+    pub overview: GameOverview,
+    pub settings: LobbySettings,
+    pub commitment: Commitment,
+    // how many players sent entropy, until the game starts
+    pub entropy: usize,
+    // the table at the start of the latest turn, with every hand showing
+    pub info: Option<Info>,
+    pub summary: Option<Summary>,
+}
+
+#[derive(Debug, Serialize)]
+struct Health {
+    uptime: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Readiness {
+    lobbies: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Started(Instant);
+
+// endpoints for the server's operator, which all need the token as a bearer token
+pub fn router(token: String, state: AppState) -> Router {
+    Router::new()
+        .route("/games", get(games))
+        .route("/games/{id}", get(game))
+        .route("/games/{id}/cancel", post(cancel))
+        .route("/games/{id}/pause", post(pause))
+        .route("/games/{id}/resume", post(resume))
+        .route("/connections/{addr}/kick", post(kick))
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            authorize,
+        ))
+        .layer(Extension(state))
+        .layer(Extension(Started(Instant::now())))
+}
+
+async fn authorize(
+    State(token): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let given = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match given {
+        Some(given) if same(given, &token) => Ok(next.run(request).await),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+// compares every byte, so how long it takes doesn't give away how much of the token was right
+fn same(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn ask<T>(state: &AppState, request: impl FnOnce(oneshot::Sender<T>) -> AdminRequest) -> T {
+    let (reply, replied) = oneshot::channel();
+    state
+        .admin
+        .send(request(reply))
+        .await
+        .expect("Dispatcher should always be available");
+    replied.await.expect("Should always reply")
+}
+
+// whether what was asked for was there to be done
+fn done(found: bool) -> StatusCode {
+    if found {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+async fn games(Extension(state): Extension<AppState>) -> impl IntoResponse {
+    Json(ask(&state, AdminRequest::Games).await)
+}
+
+async fn game(Path(id): Path<Uuid>, Extension(state): Extension<AppState>) -> impl IntoResponse {
+    let details = ask(&state, |reply| AdminRequest::Game(id, reply)).await;
+    details.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn cancel(Path(id): Path<Uuid>, Extension(state): Extension<AppState>) -> StatusCode {
+    done(ask(&state, |reply| AdminRequest::Cancel(id, reply)).await)
+}
+
+async fn pause(Path(id): Path<Uuid>, Extension(state): Extension<AppState>) -> StatusCode {
+    done(ask(&state, |reply| AdminRequest::Pause(id, true, reply)).await)
+}
+
+async fn resume(Path(id): Path<Uuid>, Extension(state): Extension<AppState>) -> StatusCode {
+    done(ask(&state, |reply| AdminRequest::Pause(id, false, reply)).await)
+}
+
+async fn kick(Path(addr): Path<SocketAddr>, Extension(state): Extension<AppState>) -> StatusCode {
+    done(ask(&state, |reply| AdminRequest::Kick(addr, reply)).await)
+}
+
+// the server is up, and for how many seconds
+async fn health(Extension(Started(started)): Extension<Started>) -> impl IntoResponse {
+    Json(Health {
+        uptime: started.elapsed().as_secs(),
+    })
+}
+
+// the server is taking connections, which it can as long as the dispatcher keeps up
+async fn ready(Extension(state): Extension<AppState>) -> impl IntoResponse {
+    match timeout(READY_TIMEOUT, ask(&state, AdminRequest::Games)).await {
+        Ok(games) => Ok(Json(Readiness {
+            lobbies: games.len(),
+        })),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    #[test]
+    fn tokens_are_compared_in_full() {
+        assert!(same("hunter2", "hunter2"));
+        assert!(!same("hunter3", "hunter2"));
+        assert!(!same("hunter", "hunter2"));
+        assert!(!same("hunter22", "hunter2"));
+        assert!(!same("", "hunter2"));
+    }
+
+    // answers with the status a request with authorization gets, behind the same check as the
+    // real endpoints
+    async fn status(authorization: Option<&str>) -> StatusCode {
+        let router =
+            Router::new()
+                .route("/", get(|| async {}))
+                .route_layer(middleware::from_fn_with_state(
+                    Arc::<str>::from("hunter2"),
+                    authorize,
+                ));
+        let mut request = Request::builder().uri("/");
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        let request = request.body(Body::empty()).unwrap();
+        router.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn only_the_token_is_let_in() {
+        assert_eq!(status(Some("Bearer hunter2")).await, StatusCode::OK);
+        assert_eq!(status(None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status(Some("Bearer hunter3")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status(Some("hunter2")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status(Some("Basic hunter2")).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
@@ -0,0 +1,363 @@
+use crate::game::{BroadcastMessage, View};
+use crate::metrics::METRICS;
+use crate::spectate::spectated;
+
+use jiff::Timestamp;
+use overthrow_types::{ClientMessage, GameRecord, GameResult, Replay, ReplayEvent, Reveal};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{RwLock, watch};
+use tokio::time::interval;
+use tokio::{fs, select};
+use tracing::instrument;
+use uuid::Uuid;
+
+// how often games past the retention period are looked for
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+// games that are over, each kept as a file holding its replay. The records are also kept in
+// memory, so games can be listed without reading every file
+#[derive(Debug)]
+pub struct Archive {
+    dir: PathBuf,
+    // how long games are kept after they end, forever if None
+    retention: Option<Duration>,
+    games: RwLock<HashMap<Uuid, GameRecord>>,
+}
+
+impl Archive {
+    // opens the archive in dir, creating it if it doesn't exist yet
+    pub async fn open(dir: PathBuf, retention: Option<Duration>) -> Result<Archive, ArchiveError> {
+        fs::create_dir_all(&dir).await?;
+
+        let mut games = HashMap::new();
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+
+            // one bad file shouldn't keep the server from starting
+            let replay = fs::read(&path)
+                .await
+                .map_err(ArchiveError::from)
+                .and_then(|bytes| Ok(serde_json::from_slice::<Replay>(&bytes)?));
+            match replay {
+                Ok(replay) => {
+                    games.insert(replay.game.id, replay.game);
+                }
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), error = %e, "Skipping unreadable game")
+                }
+            }
+        }
+
+        tracing::debug!(dir = %dir.display(), games = games.len(), "Opened game archive");
+        Ok(Archive {
+            dir,
+            retention,
+            games: RwLock::new(games),
+        })
+    }
+
+    fn path(&self, id: Uuid) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    // every game, the latest to end first
+    pub async fn list(&self) -> Vec<GameRecord> {
+        let mut games: Vec<_> = self.games.read().await.values().cloned().collect();
+        games.sort_by_key(|game| Reverse(game.ended));
+        games
+    }
+
+    pub async fn get(&self, id: Uuid) -> Option<GameRecord> {
+        self.games.read().await.get(&id).cloned()
+    }
+
+    pub async fn replay(&self, id: Uuid) -> Result<Option<Replay>, ArchiveError> {
+        if !self.games.read().await.contains_key(&id) {
+            return Ok(None);
+        }
+
+        match fs::read(self.path(id)).await {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            // removed since it was looked up
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn store(&self, replay: Replay) -> Result<(), ArchiveError> {
+        let id = replay.game.id;
+        let bytes = serde_json::to_vec(&replay)?;
+
+        // written out in full before taking the place of the real file, so nothing half written
+        // is ever read back
+        let path = self.path(id);
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, bytes).await?;
+        fs::rename(&partial, &path).await?;
+
+        self.games.write().await.insert(id, replay.game);
+        Ok(())
+    }
+
+    // removes every game that ended longer ago than the retention period
+    pub async fn prune(&self) {
+        let Some(retention) = self.retention else {
+            return;
+        };
+        let cutoff = Timestamp::now() - retention;
+
+        let expired: Vec<Uuid> = {
+            let mut games = self.games.write().await;
+            let expired = games
+                .values()
+                .filter(|game| game.ended < cutoff)
+                .map(|game| game.id)
+                .collect();
+            games.retain(|_, game| game.ended >= cutoff);
+            expired
+        };
+
+        for id in &expired {
+            if let Err(e) = fs::remove_file(self.path(*id)).await
+                && e.kind() != ErrorKind::NotFound
+            {
+                tracing::warn!(game_id = %id, error = %e, "Failed to remove expired game");
+            }
+        }
+        if !expired.is_empty() {
+            tracing::debug!(games = expired.len(), "Removed expired games");
+        }
+    }
+}
+
+// keeps removing games past the retention period, for as long as the server runs
+pub async fn prune_regularly(archive: Arc<Archive>) {
+    let mut interval = interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        archive.prune().await;
+    }
+}
+
+// what is known about a game when it starts, to be archived along with how it went
+#[derive(Debug)]
+pub struct Recording {
+    pub id: Uuid,
+    pub players: Vec<String>,
+    pub reveal: Reveal,
+    pub created: Timestamp,
+    pub started: Timestamp,
+}
+
+// follows a game the way a spectator seeing every hand would, until it is over, then stores it
+#[instrument(skip_all, fields(game_id = %recording.id))]
+pub async fn record(
+    archive: Arc<Archive>,
+    recording: Recording,
+    mut broadcasts: broadcast::Receiver<BroadcastMessage>,
+    mut view: watch::Receiver<Option<View>>,
+) {
+    let event = |message| ReplayEvent {
+        at: Timestamp::now(),
+        message,
+    };
+    let info = |view: &Option<View>| {
+        view.as_ref()
+            .map(|view| ClientMessage::Info(view.omniscient.clone()))
+    };
+
+    let mut events = Vec::new();
+    let mut missed = 0;
+    let result = loop {
+        select! {
+            // broadcasts are taken before views, since a turn's outcome is broadcast before the
+            // next turn's view is
+            biased;
+            broadcast = broadcasts.recv() => match broadcast {
+                // the view is updated right before the end is broadcast, so the last one is kept
+                // with it
+                Ok(BroadcastMessage::End(summary, reveal)) => {
+                    let last = info(&view.borrow_and_update());
+                    events.extend(last.map(event));
+                    events.push(event(ClientMessage::Reveal(reveal)));
+                    events.push(event(ClientMessage::End(summary)));
+                    break GameResult::Finished(summary);
+                }
+                // cancelled games are revealed too, so they can still be checked
+                Ok(BroadcastMessage::GameCancelled(..)) | Err(RecvError::Closed) => {
+                    events.push(event(ClientMessage::Reveal(recording.reveal.clone())));
+                    events.push(event(ClientMessage::GameCancelled));
+                    break GameResult::Cancelled;
+                }
+                Ok(broadcast) => events.extend(spectated(broadcast).map(event)),
+                // the replay is kept anyway, but marked as missing them
+                Err(RecvError::Lagged(lagged)) => {
+                    tracing::warn!(missed = lagged, "Recording fell behind the game");
+                    missed += lagged;
+                }
+            },
+            Ok(()) = view.changed() => {
+                let latest = info(&view.borrow_and_update());
+                events.extend(latest.map(event));
+            },
+        }
+    };
+
+    METRICS.game_ended(matches!(result, GameResult::Finished(..)));
+    let game = GameRecord {
+        id: recording.id,
+        players: recording.players,
+        seed: recording.reveal.seed(),
+        reveal: recording.reveal,
+        created: recording.created,
+        started: recording.started,
+        ended: Timestamp::now(),
+        result,
+        missed,
+    };
+    tracing::debug!(events = events.len(), result = ?game.result, "Archiving game");
+    if let Err(e) = archive.store(Replay { game, events }).await {
+        tracing::error!(error = %e, "Failed to archive game");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use overthrow_engine::commitment::Secret;
+    use overthrow_types::{PlayerId, Summary};
+
+    fn replay(ended: Timestamp) -> Replay {
+        let reveal = Reveal::new(Secret::random(), vec!["dave".to_owned()]);
+        Replay {
+            game: GameRecord {
+                id: Uuid::now_v7(),
+                players: vec!["Dave".to_owned(), "Garry".to_owned()],
+                seed: reveal.seed(),
+                reveal: reveal.clone(),
+                created: ended,
+                started: ended,
+                ended,
+                result: GameResult::Finished(Summary {
+                    winner: PlayerId::Two,
+                }),
+                missed: 0,
+            },
+            events: vec![ReplayEvent {
+                at: ended,
+                message: ClientMessage::Reveal(reveal),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn stored_games_are_opened_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Archive::open(dir.path().join("games"), None).await.unwrap();
+        let older = replay(Timestamp::now() - Duration::from_secs(60));
+        let newer = replay(Timestamp::now());
+        archive.store(older.clone()).await.unwrap();
+        archive.store(newer.clone()).await.unwrap();
+
+        // files that aren't games are left alone
+        fs::write(dir.path().join("games/notes.txt"), "hi")
+            .await
+            .unwrap();
+        fs::write(dir.path().join("games/broken.json"), "{")
+            .await
+            .unwrap();
+
+        let archive = Archive::open(dir.path().join("games"), None).await.unwrap();
+        let ids: Vec<_> = archive.list().await.iter().map(|game| game.id).collect();
+        assert_eq!(ids, [newer.game.id, older.game.id]);
+
+        let stored = archive.replay(older.game.id).await.unwrap().unwrap();
+        assert_eq!(stored.game.reveal, older.game.reveal);
+        assert_eq!(stored.events.len(), 1);
+        assert!(archive.replay(Uuid::now_v7()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn expired_games_are_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let retention = Duration::from_secs(60 * 60);
+        let archive = Archive::open(dir.path().to_owned(), Some(retention))
+            .await
+            .unwrap();
+        let expired = replay(Timestamp::now() - retention * 2);
+        let kept = replay(Timestamp::now());
+        archive.store(expired.clone()).await.unwrap();
+        archive.store(kept.clone()).await.unwrap();
+
+        archive.prune().await;
+        assert!(archive.get(expired.game.id).await.is_none());
+        assert!(archive.get(kept.game.id).await.is_some());
+        assert!(!archive.path(expired.game.id).exists());
+        assert!(archive.path(kept.game.id).exists());
+    }
+
+    #[tokio::test]
+    async fn games_are_kept_forever_without_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Archive::open(dir.path().to_owned(), None).await.unwrap();
+        let old = replay(Timestamp::now() - Duration::from_secs(60 * 60 * 24 * 365));
+        archive.store(old.clone()).await.unwrap();
+
+        archive.prune().await;
+        assert!(archive.get(old.game.id).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn replays_that_fell_behind_are_marked() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Arc::new(Archive::open(dir.path().to_owned(), None).await.unwrap());
+        let reveal = Reveal::new(Secret::random(), Vec::new());
+        let recording = Recording {
+            id: Uuid::now_v7(),
+            players: vec!["Dave".to_owned(), "Garry".to_owned()],
+            reveal: reveal.clone(),
+            created: Timestamp::now(),
+            started: Timestamp::now(),
+        };
+        let id = recording.id;
+
+        // more is sent than the channel holds before the recording gets to any of it
+        let (broadcaster, broadcasts) = broadcast::channel(2);
+        let (_view, view) = watch::channel(None);
+        for _ in 0..3 {
+            broadcaster.send(BroadcastMessage::Undo(true)).unwrap();
+        }
+        let summary = Summary {
+            winner: PlayerId::One,
+        };
+        broadcaster
+            .send(BroadcastMessage::End(summary, reveal))
+            .unwrap();
+        record(archive.clone(), recording, broadcasts, view).await;
+
+        let replay = archive.replay(id).await.unwrap().unwrap();
+        assert_eq!(replay.game.missed, 2);
+        assert!(matches!(replay.game.result, GameResult::Finished(..)));
+        // the undo that was kept, then the reveal and the end
+        assert_eq!(replay.events.len(), 3);
+    }
+}
@@ -0,0 +1,538 @@
+use crate::dispatcher::ClientChannels;
+use crate::game::{Choices, GameMessage, Pass, PlayerGameInfo};
+
+use jiff::Timestamp;
+use overthrow_engine::action::{Act, Blocks, Challenge, Reaction};
+use overthrow_engine::deck::{Card, Hand};
+use overthrow_engine::players::PlayerId;
+use overthrow_types::{Action, Difficulty, Info, PlayerView};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::mpsc::Receiver;
+use tokio::time::sleep;
+use tracing::{debug, instrument};
+
+// milliseconds bots take to decide, so players can follow what they do
+const THINK_TIME: RangeInclusive<u64> = 500..=2500;
+// how long before the deadline bots answer at the latest
+const THINK_MARGIN: Duration = Duration::from_secs(1);
+// most worth keeping first
+const CARD_RANKING: [Card; 5] = [
+    Card::Duke,
+    Card::Assassin,
+    Card::Captain,
+    Card::Contessa,
+    Card::Ambassador,
+];
+
+// income, unless a coup has to be made
+pub fn default_action(actions: &[Action]) -> Action {
+    actions
+        .iter()
+        .find(|action| action.kind() == Act::Income)
+        .or_else(|| actions.choose(&mut rand::thread_rng()))
+        .cloned()
+        .expect("There is always an action to take")
+}
+
+pub fn random_card(cards: &[Card]) -> Card {
+    *cards
+        .choose(&mut rand::thread_rng())
+        .expect("There is always a card to choose")
+}
+
+pub fn random_pair(cards: &[Card]) -> [Card; 2] {
+    let mut picked = cards.choose_multiple(&mut rand::thread_rng(), 2).copied();
+    [(); 2].map(|_| picked.next().expect("There are always two cards to choose"))
+}
+
+// plays a seat nobody is at anymore, by taking the same moves players get when they run out of
+// time, straight away. It keeps going until the game is over, or takes the seat away
+#[instrument(skip_all)]
+pub async fn autopilot(
+    id: PlayerId,
+    senders: Arc<ClientChannels>,
+    messages: Receiver<GameMessage>,
+    info: Receiver<Info>,
+) {
+    debug!("Bot took over seat");
+    Bot::new(id, None).run(&senders, messages, info).await;
+}
+
+// plays a seat the server was asked to fill, the same way a client would
+#[instrument(skip_all, fields(id = ?game_info.id, ?difficulty))]
+pub async fn play(difficulty: Difficulty, game_info: PlayerGameInfo) {
+    let PlayerGameInfo {
+        id,
+        info,
+        channels: (senders, messages),
+        ..
+    } = game_info;
+
+    debug!("Bot took its seat");
+    Bot::new(id, Some(difficulty))
+        .run(&senders, messages, info)
+        .await;
+}
+
+struct Bot {
+    id: PlayerId,
+    // taken over seats play the timeout moves instead
+    difficulty: Option<Difficulty>,
+    // the latest view of the game, for our hand and everyone's coins
+    info: Option<Info>,
+    rng: StdRng,
+}
+
+impl Bot {
+    fn new(id: PlayerId, difficulty: Option<Difficulty>) -> Bot {
+        Bot {
+            id,
+            difficulty,
+            info: None,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    async fn run(
+        mut self,
+        senders: &ClientChannels,
+        mut messages: Receiver<GameMessage>,
+        mut info: Receiver<Info>,
+    ) {
+        loop {
+            select! {
+                Some(info) = info.recv() => self.info = Some(info),
+                Some(message) = messages.recv() => {
+                    self.think(deadline(&message)).await;
+                    self.respond(senders, message).await;
+                }
+                else => break,
+            }
+        }
+    }
+
+    async fn think(&mut self, deadline: Timestamp) {
+        if self.difficulty.is_none() {
+            return;
+        }
+
+        let time = Duration::from_millis(self.rng.gen_range(THINK_TIME));
+        let left = Duration::try_from(deadline.duration_since(Timestamp::now()))
+            .unwrap_or_default()
+            .saturating_sub(THINK_MARGIN);
+        sleep(time.min(left)).await;
+    }
+
+    // the game may have given up on the seat by the time a response is sent, so errors are ignored
+    async fn respond(&mut self, senders: &ClientChannels, message: GameMessage) {
+        match message {
+            GameMessage::ChooseAction(Choices::Actions(actions, _)) => {
+                let _ = senders.action.send(self.action(&actions)).await;
+            }
+            GameMessage::ChooseAction(Choices::Challenge(challenge, _)) => {
+                if self.challenges(&challenge) {
+                    let _ = senders.challenge.send(challenge).await;
+                } else {
+                    let _ = senders.pass.send(Pass).await;
+                }
+            }
+            GameMessage::ChooseAction(Choices::Block(blocks, _)) => {
+                let blocks = match blocks {
+                    Blocks::Other(block) => vec![Reaction::Block(block)],
+                    Blocks::Steal(b1, b2) => vec![Reaction::Block(b1), Reaction::Block(b2)],
+                };
+                self.send_reaction(senders, &blocks).await;
+            }
+            GameMessage::ChooseAction(Choices::Reactions(reactions, ..)) => {
+                self.send_reaction(senders, &reactions).await;
+            }
+            GameMessage::ChooseVictim(cards, _) => {
+                let [keep] = self.keep(&cards);
+                let lose = if cards[0] == keep { cards[1] } else { cards[0] };
+                let _ = senders.victim_card.send(lose).await;
+            }
+            GameMessage::ChooseOneFromThree(cards, _) => {
+                let [keep] = self.keep(&cards);
+                let _ = senders.choose_one.send(keep).await;
+            }
+            GameMessage::ChooseTwoFromFour(cards, _) => {
+                let _ = senders.choose_two.send(self.keep(&cards)).await;
+            }
+            // bots don't mind turns being taken back
+            GameMessage::ConfirmUndo(..) if self.difficulty.is_some() => {
+                let _ = senders.allow_undo.send(true).await;
+            }
+            GameMessage::ConfirmUndo(..) => {
+                let _ = senders.pass.send(Pass).await;
+            }
+        }
+    }
+
+    async fn send_reaction(&mut self, senders: &ClientChannels, reactions: &[Reaction]) {
+        match self.react(reactions) {
+            Some(Reaction::Block(block)) => {
+                let _ = senders.block.send(block).await;
+            }
+            Some(Reaction::Challenge(challenge)) => {
+                let _ = senders.challenge.send(challenge).await;
+            }
+            None => {
+                let _ = senders.pass.send(Pass).await;
+            }
+        }
+    }
+
+    fn action(&mut self, actions: &[Action]) -> Action {
+        match self.difficulty {
+            None => default_action(actions),
+            Some(Difficulty::Easy) => actions
+                .choose(&mut self.rng)
+                .cloned()
+                .expect("There is always an action to take"),
+            Some(Difficulty::Medium) => self.best_action(
+                actions
+                    .iter()
+                    .filter(|action| action.claim().is_none_or(|card| self.holds(card))),
+            ),
+            Some(Difficulty::Hard) => self.best_action(actions.iter()),
+        }
+    }
+
+    // prefers the strongest action, then the richest victim
+    fn best_action<'a>(&self, actions: impl Iterator<Item = &'a Action>) -> Action {
+        actions
+            .max_by_key(|action| {
+                let (strength, victim) = match action.kind() {
+                    Act::Coup { victim } => (7, Some(victim)),
+                    Act::Assassinate { victim } => (6, Some(victim)),
+                    Act::Tax => (5, None),
+                    Act::Steal { victim } => (4, Some(victim)),
+                    Act::Exchange => (3, None),
+                    Act::ForeignAid => (2, None),
+                    Act::Income => (1, None),
+                };
+
+                (strength, victim.map_or(0, |victim| self.coins_of(victim)))
+            })
+            .cloned()
+            .expect("Income is always possible, unless a coup has to be made")
+    }
+
+    fn challenges(&mut self, challenge: &Challenge) -> bool {
+        match self.difficulty {
+            None | Some(Difficulty::Medium) => false,
+            Some(Difficulty::Easy) => self.rng.gen_bool(0.5),
+            // more likely to call it when we know they can't have the card
+            Some(Difficulty::Hard) => {
+                let odds = if self.holds(challenge.kind().into()) {
+                    0.5
+                } else {
+                    0.15
+                };
+                self.rng.gen_bool(odds)
+            }
+        }
+    }
+
+    fn react(&mut self, reactions: &[Reaction]) -> Option<Reaction> {
+        match self.difficulty {
+            None => None,
+            // passing is as likely as any of the reactions
+            Some(Difficulty::Easy) => {
+                let choice = self.rng.gen_range(0..=reactions.len());
+                reactions.get(choice).cloned()
+            }
+            Some(Difficulty::Medium) => reactions
+                .iter()
+                .find(|reaction| match reaction {
+                    Reaction::Block(block) => self.holds(block.claim()),
+                    Reaction::Challenge(..) => false,
+                })
+                .cloned(),
+            Some(Difficulty::Hard) => {
+                let block = reactions
+                    .iter()
+                    .find(|reaction| matches!(reaction, Reaction::Block(..)));
+                if let Some(block) = block {
+                    return Some(block.clone());
+                }
+
+                reactions
+                    .iter()
+                    .find_map(|reaction| match reaction {
+                        Reaction::Challenge(challenge) => Some(challenge.clone()),
+                        Reaction::Block(..) => None,
+                    })
+                    .filter(|challenge| self.challenges(challenge))
+                    .map(Reaction::Challenge)
+            }
+        }
+    }
+
+    // the cards to hold on to out of those offered
+    fn keep<const N: usize>(&mut self, cards: &[Card]) -> [Card; N] {
+        let mut cards = cards.to_vec();
+        match self.difficulty {
+            None | Some(Difficulty::Easy) => cards.shuffle(&mut self.rng),
+            Some(..) => cards.sort_by_key(|card| CARD_RANKING.iter().position(|c| c == card)),
+        }
+
+        let mut kept = cards.into_iter();
+        [(); N].map(|_| kept.next().expect("More cards are offered than kept"))
+    }
+
+    fn hand(&self) -> Option<&Hand> {
+        match self.info.as_ref()?.player_views.get(&self.id)? {
+            PlayerView::Me { hand, .. } => Some(hand),
+            PlayerView::Other { .. } => None,
+        }
+    }
+
+    fn holds(&self, card: Card) -> bool {
+        self.hand().is_some_and(|hand| hand.has_card(card))
+    }
+
+    fn coins_of(&self, player: PlayerId) -> u8 {
+        match self
+            .info
+            .as_ref()
+            .and_then(|info| info.player_views.get(&player))
+        {
+            Some(PlayerView::Me { coins, .. } | PlayerView::Other { coins, .. }) => *coins,
+            None => 0,
+        }
+    }
+}
+
+fn deadline(message: &GameMessage) -> Timestamp {
+    match message {
+        GameMessage::ChooseAction(
+            Choices::Actions(_, deadline)
+            | Choices::Challenge(_, deadline)
+            | Choices::Block(_, deadline)
+            | Choices::Reactions(.., deadline),
+        )
+        | GameMessage::ChooseVictim(_, deadline)
+        | GameMessage::ChooseOneFromThree(_, deadline)
+        | GameMessage::ChooseTwoFromFour(_, deadline)
+        | GameMessage::ConfirmUndo(_, deadline) => *deadline,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::UndoRequest;
+    use overthrow_engine::GameBuilder;
+    use overthrow_engine::action::{Block, ReactionStage};
+    use overthrow_engine::driver::{Decision, Phase};
+    use overthrow_engine::machine::{
+        ChooseOneFromThreeState, ChooseTwoFromFourState, ChooseVictimCardState,
+    };
+    use overthrow_engine::priority::ReactionWindow;
+    use std::collections::HashMap;
+    use tokio::sync::mpsc::{self, error::TryRecvError};
+
+    // what the game task would receive from a seat
+    struct Responses {
+        action: Receiver<Action>,
+        challenge: Receiver<Challenge>,
+        block: Receiver<Block>,
+        victim_card: Receiver<Card>,
+        choose_one: Receiver<Card>,
+        choose_two: Receiver<[Card; 2]>,
+        pass: Receiver<Pass>,
+        _undo: Receiver<UndoRequest>,
+        allow_undo: Receiver<bool>,
+    }
+
+    impl Responses {
+        // the one response sent since the last call, as the decision it stands for
+        fn take(&mut self) -> Decision {
+            let mut decisions = [
+                self.action.try_recv().map(Decision::Act),
+                self.challenge.try_recv().map(Decision::Challenge),
+                self.block.try_recv().map(Decision::Block),
+                self.victim_card.try_recv().map(Decision::ChooseVictim),
+                self.choose_one.try_recv().map(Decision::ExchangeOne),
+                self.choose_two.try_recv().map(|mut pair| {
+                    // options are offered in card order
+                    pair.sort_by_key(|card| *card as u8);
+                    Decision::ExchangeTwo(pair)
+                }),
+                self.pass.try_recv().map(|Pass| Decision::Pass),
+            ]
+            .into_iter()
+            .filter_map(Result::ok);
+
+            let decision = decisions.next().expect("Bots always respond");
+            assert!(decisions.next().is_none(), "Bots respond only once");
+            decision
+        }
+    }
+
+    fn channels() -> (ClientChannels, Responses) {
+        let (action, action_rx) = mpsc::channel(1);
+        let (challenge, challenge_rx) = mpsc::channel(1);
+        let (block, block_rx) = mpsc::channel(1);
+        let (victim_card, victim_card_rx) = mpsc::channel(1);
+        let (choose_one, choose_one_rx) = mpsc::channel(1);
+        let (choose_two, choose_two_rx) = mpsc::channel(1);
+        let (pass, pass_rx) = mpsc::channel(1);
+        let (undo, undo_rx) = mpsc::channel(1);
+        let (allow_undo, allow_undo_rx) = mpsc::channel(1);
+
+        let senders = ClientChannels {
+            action,
+            challenge,
+            block,
+            victim_card,
+            choose_one,
+            choose_two,
+            pass,
+            undo,
+            allow_undo,
+        };
+        let responses = Responses {
+            action: action_rx,
+            challenge: challenge_rx,
+            block: block_rx,
+            victim_card: victim_card_rx,
+            choose_one: choose_one_rx,
+            choose_two: choose_two_rx,
+            pass: pass_rx,
+            _undo: undo_rx,
+            allow_undo: allow_undo_rx,
+        };
+        (senders, responses)
+    }
+
+    // the choice player is sent in phase, the way the game task offers it
+    fn message(phase: &Phase, player: PlayerId) -> GameMessage {
+        let deadline = Timestamp::now();
+        let options = phase.options_for(player);
+        let reactions: Vec<_> = options
+            .iter()
+            .filter_map(|decision| match decision {
+                Decision::Challenge(challenge) => Some(Reaction::Challenge(challenge.clone())),
+                Decision::Block(block) => Some(Reaction::Block(block.clone())),
+                _ => None,
+            })
+            .collect();
+
+        match phase {
+            Phase::Wait(..) => {
+                let actions = options.into_iter().filter_map(|decision| match decision {
+                    Decision::Act(action) => Some(action),
+                    _ => None,
+                });
+                Choices::Actions(actions.collect(), deadline).into()
+            }
+            Phase::ChooseVictimCard(game) => GameMessage::ChooseVictim(game.choices(), deadline),
+            Phase::ChooseOneFromThree(game) => {
+                GameMessage::ChooseOneFromThree(game.choices(), deadline)
+            }
+            Phase::ChooseTwoFromFour(game) => {
+                GameMessage::ChooseTwoFromFour(game.choices(), deadline)
+            }
+            _ => match reactions.as_slice() {
+                [Reaction::Challenge(challenge)] => {
+                    Choices::Challenge(challenge.clone(), deadline).into()
+                }
+                [Reaction::Block(block)] => {
+                    Choices::Block(Blocks::Other(block.clone()), deadline).into()
+                }
+                [Reaction::Block(b1), Reaction::Block(b2)] => {
+                    Choices::Block(Blocks::Steal(b1.clone(), b2.clone()), deadline).into()
+                }
+                _ => Choices::Reactions(reactions, ReactionStage::All, deadline).into(),
+            },
+        }
+    }
+
+    // plays a game where every seat is a bot, checking each response against what was offered
+    async fn play_bots(seed: u64, seats: [Option<Difficulty>; 4]) -> Phase {
+        let game = GameBuilder::new(["Dave", "Garry", "Steve", "Maxine"])
+            .seed(seed)
+            .reaction_window(ReactionWindow::TwoStage)
+            .build();
+        let mut phase = Phase::from(game);
+        let mut bots: HashMap<_, _> = PlayerId::iter()
+            .zip(seats)
+            .map(|(id, difficulty)| (id, (Bot::new(id, difficulty), channels())))
+            .collect();
+
+        for _ in 0..1000 {
+            if phase.is_over() {
+                break;
+            }
+
+            // the first reaction is taken, as the game task would
+            let mut decided = Decision::Pass;
+            for player in phase.deciders() {
+                let (bot, (senders, responses)) = bots.get_mut(&player).unwrap();
+                bot.info = Some(Info::new(player, &phase.info()));
+                bot.respond(senders, message(&phase, player)).await;
+
+                let decision = responses.take();
+                let options = phase.options_for(player);
+                assert!(
+                    options.contains(&decision)
+                        || (decision == Decision::Pass && phase.is_reaction_window()),
+                    "{:?} bot chose {decision} out of {options:?} in {}",
+                    bot.difficulty,
+                    phase.name()
+                );
+                if decided == Decision::Pass {
+                    decided = decision;
+                }
+            }
+
+            phase = phase
+                .decide(decided)
+                .expect("Bots only make valid decisions")
+                .phase;
+        }
+
+        phase
+    }
+
+    #[tokio::test]
+    async fn bots_only_make_offered_choices() {
+        use Difficulty::*;
+        // taken over seats only ever take income, so they are seated with bots that coup
+        let tables = [
+            [Some(Easy); 4],
+            [Some(Medium); 4],
+            [Some(Hard); 4],
+            [None, Some(Easy), Some(Medium), Some(Hard)],
+        ];
+        for seats in tables {
+            for seed in 0..20 {
+                let phase = play_bots(seed, seats).await;
+                assert!(phase.is_over(), "{seats:?} didn't finish");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn bots_never_leave_an_undo_unanswered() {
+        for difficulty in [None, Some(Difficulty::Easy)] {
+            let (senders, mut responses) = channels();
+            let mut bot = Bot::new(PlayerId::One, difficulty);
+            let message = GameMessage::ConfirmUndo(PlayerId::Two, Timestamp::now());
+            bot.respond(&senders, message).await;
+
+            let allowed = responses.allow_undo.try_recv();
+            let passed = responses.pass.try_recv();
+            assert_ne!(allowed.is_ok(), passed.is_ok());
+            assert!(matches!(allowed, Ok(true) | Err(TryRecvError::Empty)));
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{AppState, Chat};
+
+use overthrow_types::ClientError;
+use std::time::Duration;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+// longest chat message, in characters
+const MAX_CHAT_LEN: usize = 280;
+
+// connections can send a few messages in a row, after which they get one more every interval
+const CHAT_BURST: u32 = 5;
+const CHAT_INTERVAL: Duration = Duration::from_secs(2);
+
+// how much chat a connection has left to send right now
+#[derive(Debug)]
+pub struct ChatLimit {
+    allowance: u32,
+    // when the allowance last went up, or was last full
+    refilled: Instant,
+}
+
+impl Default for ChatLimit {
+    fn default() -> ChatLimit {
+        ChatLimit {
+            allowance: CHAT_BURST,
+            refilled: Instant::now(),
+        }
+    }
+}
+
+impl ChatLimit {
+    // uses up one message of the allowance, if there is any left
+    fn take(&mut self) -> bool {
+        let now = Instant::now();
+        let earned = (now - self.refilled).as_millis() / CHAT_INTERVAL.as_millis();
+        let earned = u32::try_from(earned).unwrap_or(CHAT_BURST).min(CHAT_BURST);
+        self.allowance = (self.allowance + earned).min(CHAT_BURST);
+        // nothing more is earned while the allowance is full
+        self.refilled = if self.allowance == CHAT_BURST {
+            now
+        } else {
+            self.refilled + CHAT_INTERVAL * earned
+        };
+
+        let Some(allowance) = self.allowance.checked_sub(1) else {
+            return false;
+        };
+        self.allowance = allowance;
+        true
+    }
+}
+
+// passes text from a member on to the dispatcher to be sent to everyone, as long as it isn't too
+// long and the member hasn't been sending too much
+pub async fn chat(
+    state: &AppState,
+    game_id: Uuid,
+    member: Uuid,
+    limit: &mut ChatLimit,
+    text: String,
+) -> Result<(), ClientError> {
+    let text = text.trim();
+    if text.is_empty() || text.chars().count() > MAX_CHAT_LEN {
+        return Err(ClientError::InvalidChat);
+    }
+    if !limit.take() {
+        return Err(ClientError::ChatTooFast);
+    }
+
+    let chat = Chat {
+        game_id,
+        member,
+        text: text.to_owned(),
+    };
+    state
+        .chat
+        .send(chat)
+        .await
+        .expect("Dispatcher should always be available");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time;
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_then_refill() {
+        let mut limit = ChatLimit::default();
+        for _ in 0..CHAT_BURST {
+            assert!(limit.take());
+        }
+        assert!(!limit.take());
+
+        // one more is earned each interval
+        time::advance(CHAT_INTERVAL / 2).await;
+        assert!(!limit.take());
+        time::advance(CHAT_INTERVAL / 2).await;
+        assert!(limit.take());
+        assert!(!limit.take());
+
+        // up to a full burst, after waiting long enough
+        time::advance(CHAT_INTERVAL * CHAT_BURST * 10).await;
+        for _ in 0..CHAT_BURST {
+            assert!(limit.take());
+        }
+        assert!(!limit.take());
+    }
+
+    // time spent with a full allowance doesn't count towards the next refill
+    #[tokio::test(start_paused = true)]
+    async fn nothing_is_earned_while_full() {
+        let mut limit = ChatLimit::default();
+        time::advance(CHAT_INTERVAL * 3).await;
+        for _ in 0..CHAT_BURST {
+            assert!(limit.take());
+        }
+        assert!(!limit.take());
+    }
+}
@@ -1,7 +1,10 @@
 use crate::{
-    Disconnected,
-    dispatcher::ClientChannels,
-    game::{BroadcastMessage, Choices, GameMessage, Pass, PlayerGameInfo},
+    Connection, Disconnected, Entropy, LobbyAction, LobbyRequest, Resume,
+    bot::{self, default_action, random_card, random_pair},
+    chat::{self, ChatLimit},
+    dispatcher::{ClientChannels, Join, Joined, LobbySettings, Registration},
+    game::{BroadcastMessage, Choices, GameMessage, Pass, PlayerGameInfo, UndoRequest},
+    metrics::{ChoiceKind, METRICS},
 };
 
 use super::AppState;
@@ -18,18 +21,32 @@ use overthrow_engine::{
     match_to_indices,
 };
 use thiserror::Error;
+use uuid::Uuid;
 
 use overthrow_types::*;
 
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use std::{net::SocketAddr, time::Duration};
-use tokio::{select, time::timeout_at};
-use tokio::{sync::oneshot, time::Instant};
+use std::{future, net::SocketAddr, time::Duration};
+use tokio::{select, time::sleep_until};
+use tokio::{
+    sync::{
+        broadcast,
+        mpsc::{self, error::SendError},
+        oneshot,
+    },
+    time::Instant,
+};
 use tracing::{debug, instrument, trace};
-use uuid::Uuid;
 
-fn serialize<T: Serialize>(value: T) -> Utf8Bytes {
+// longest entropy a client can add to the shuffle seed, in bytes
+const MAX_ENTROPY_LEN: usize = 256;
+
+// how long a player who lost their connection mid-game has to come back before the game is given
+// up on
+const RECONNECT_GRACE: Duration = Duration::from_secs(60);
+
+pub fn serialize<T: Serialize>(value: T) -> Utf8Bytes {
     serde_json::to_string(&value).unwrap().into()
 }
 
@@ -45,147 +62,396 @@ enum Error {
     GameCancelled,
     #[error("Client disconnected")]
     Disconnected,
+    #[error("Client was kicked")]
+    Kicked,
+}
+
+// the game stopped listening for this seat, either because it took the seat out or because the
+// game was cancelled
+impl<T> From<SendError<T>> for Error {
+    fn from(_: SendError<T>) -> Self {
+        Error::GameCancelled
+    }
 }
 
 // TODO: properly handle axum errors, for now we treat it as a disconnection
 impl From<AxumError> for Error {
     fn from(_: AxumError) -> Self {
+        METRICS.websocket_error();
         Error::Disconnected
     }
 }
 
+// the next message from the client, which never comes while they are disconnected
+async fn next_message(
+    receiver: &mut Option<SplitStream<WebSocket>>,
+) -> Option<Result<Message, AxumError>> {
+    let message = match receiver {
+        Some(receiver) => receiver.next().await,
+        None => future::pending().await,
+    };
+    if let Some(Err(_)) = message {
+        METRICS.websocket_error();
+    }
+    message
+}
+
+// finishes once a disconnected client has run out of time to come back
+async fn grace_over(grace: Option<Instant>) {
+    match grace {
+        Some(grace) => sleep_until(grace).await,
+        None => future::pending().await,
+    }
+}
+
 #[derive(Debug)]
 struct ClientHandle<'state> {
-    player_sender: &'state mut SplitSink<WebSocket, Message>,
-    player_receiver: &'state mut SplitStream<WebSocket>,
+    state: &'state AppState,
+    game_id: Uuid,
+    // who the client is in the lobby, which is who they chat as
+    member: Uuid,
+    chat: ChatLimit,
+    id: PlayerId,
+    // both None while the client is disconnected
+    player_sender: Option<SplitSink<WebSocket, Message>>,
+    player_receiver: Option<SplitStream<WebSocket>>,
+    // new connections from the client taking their seat back
+    reconnections: mpsc::Receiver<WebSocket>,
+    // the server's operator taking the client out of the game
+    kicks: mpsc::Receiver<()>,
+    // when the seat is given up on, while the client is disconnected
+    grace: Option<Instant>,
     senders: Arc<ClientChannels>,
+    broadcasts: broadcast::Receiver<BroadcastMessage>,
+    // whether the lobby allows taking back turns
+    undo: bool,
+    // sent again when the client comes back, so they can carry on where they left off
+    commitment: Commitment,
+    info: Option<Info>,
+    waiting: Option<ClientMessage>,
+    pending: Option<ClientMessage>,
 }
 
 impl<'state> ClientHandle<'state> {
-    async fn send_to_client(&mut self, message: ClientMessage) -> Result<(), AxumError> {
-        let message = Message::Text(serialize(message));
-        self.player_sender.send(message).await
+    // messages are dropped while the client is disconnected, as they are caught up on coming back
+    async fn send<T: Serialize>(&mut self, message: T) {
+        let Some(sender) = &mut self.player_sender else {
+            return;
+        };
+
+        if sender
+            .send(Message::Text(serialize(message)))
+            .await
+            .is_err()
+        {
+            METRICS.websocket_error();
+            self.disconnect().await;
+        }
     }
 
-    async fn send_game_cancelled(&mut self) -> Result<(), AxumError> {
-        self.send_to_client(ClientMessage::GameCancelled).await
+    // lets the client know what they sent can't be used, so they can try again
+    async fn send_invalid_response(&mut self) {
+        METRICS.invalid_response();
+        self.send(ClientError::InvalidResponse).await
     }
 
-    async fn send_not_ready(&mut self) -> Result<(), AxumError> {
-        let not_ready = Message::Text(serialize(ClientError::NotReady));
-        self.player_sender.send(not_ready).await
+    async fn send_to_client(&mut self, message: ClientMessage) {
+        self.send(message).await
     }
 
-    async fn send_invalid_response(&mut self) -> Result<(), AxumError> {
-        let err = Message::Text(serialize(ClientError::InvalidResponse));
-        self.player_sender.send(err).await
+    async fn send_info(&mut self, info: Info) {
+        self.info = Some(info.clone());
+        self.send_to_client(ClientMessage::Info(info)).await
     }
 
-    // tries to parse message and have response_handler process it. If the message provided by the client is invalid in some way,
-    // the function will send the client an invalid response message and try again until it suceeds, the timeout completes
-    // or the client disconnects
-    async fn handle_timed_client_response<M, H>(
-        &mut self,
-        message_builder: M,
-        mut response_handler: H,
-    ) -> Result<(), Error>
-    where
-        M: FnOnce(Timestamp) -> ClientMessage,
-        // unfortunately Arc is required because of a bug with AsyncFn(Mut) bounds
-        H: AsyncFnMut(Arc<ClientChannels>, ClientResponse) -> Result<(), ClientError>,
-    {
-        // get our initial countdown time
-        let (countdown_end, deadline) = get_countdown_time();
-        trace!(countdown_end=%countdown_end, "Set countdown timeout");
+    // keeps the seat for a while, letting everyone else know the client lost their connection
+    async fn disconnect(&mut self) {
+        // only the first error on a connection counts
+        if self.player_sender.take().is_none() {
+            return;
+        }
+        self.player_receiver = None;
+
+        debug!(player_id = ?self.id, "Client lost their connection");
+        self.grace = Some(Instant::now() + RECONNECT_GRACE);
+        let connection = Connection {
+            game_id: self.game_id,
+            player: self.id,
+            given_up: Some(Timestamp::now() + RECONNECT_GRACE),
+        };
+        self.state
+            .connection
+            .send(connection)
+            .await
+            .expect("Dispatcher should always be available");
+    }
 
-        // send out initial message
-        self.send_to_client(message_builder(countdown_end)).await?;
+    // swaps in a new connection from the client (even if the old one still seems to be up, as it
+    // may not have noticed it is gone yet), and catches them up on the game
+    async fn reattach(&mut self, socket: WebSocket) {
+        debug!(player_id = ?self.id, "Client took their seat back");
+        let (sender, receiver) = socket.split();
+        self.player_sender = Some(sender);
+        self.player_receiver = Some(receiver);
+
+        if self.grace.take().is_some() {
+            let connection = Connection {
+                game_id: self.game_id,
+                player: self.id,
+                given_up: None,
+            };
+            self.state
+                .connection
+                .send(connection)
+                .await
+                .expect("Dispatcher should always be available");
+        }
 
-        loop {
-            let result = match timeout_at(deadline, self.player_receiver.next()).await {
-                Ok(message) => {
-                    // if the client disconnects, the message is not a valid websocket message, it is not text,
-                    // and/or it doesn't deserialize correctly, the response is invalid and we return Err
-                    // Otherwise, we pass the response to the handler and see if it returns Ok
-                    use ClientError as E;
-                    let Ok(response) = message
-                        .ok_or(Error::Disconnected)?
-                        .and_then(|msg| msg.into_text())
-                        .map_err(|_| E::InvalidResponse)
-                        .and_then(|text| deserialize(&text))
-                    else {
-                        self.send_invalid_response().await?;
-                        continue;
-                    };
+        self.send_to_client(ClientMessage::GameId(self.game_id))
+            .await;
+        self.send_to_client(ClientMessage::Commitment(self.commitment))
+            .await;
+        self.send_to_client(ClientMessage::PlayerId(self.id)).await;
+        let catch_up = [
+            self.info.clone().map(ClientMessage::Info),
+            self.waiting.clone(),
+            self.pending.clone(),
+        ];
+        for message in catch_up.into_iter().flatten() {
+            self.send_to_client(message).await;
+        }
+    }
 
-                    response_handler(Arc::clone(&self.senders), response).await
-                }
-                // timeout reached, send pass
-                Err(_) => {
-                    self.senders.pass.send(Pass).await.unwrap();
-                    Ok(())
+    // closes the connection once the game is over, if the client is still around
+    async fn close(self) {
+        if let (Some(sender), Some(receiver)) = (self.player_sender, self.player_receiver) {
+            // ignore error if client disconnects right before sending this
+            let _ = receiver
+                .reunite(sender)
+                .expect("Should always reunite")
+                .send(Message::Close(None))
+                .await;
+        }
+    }
+
+    async fn kicked(&mut self) -> Error {
+        debug!(player_id = ?self.id, "Client was kicked");
+        self.send(ClientError::Kicked).await;
+        Error::Kicked
+    }
+
+    // passes a broadcast from the game on to the client, returning whether the game is over
+    async fn forward_broadcast(&mut self, broadcast: BroadcastMessage) -> Result<bool, Error> {
+        let message = match broadcast {
+            BroadcastMessage::End(summary, reveal) => {
+                self.send_to_client(ClientMessage::Reveal(reveal)).await;
+                self.send_to_client(ClientMessage::End(summary)).await;
+                return Ok(true);
+            }
+            BroadcastMessage::GameCancelled(reveal) => {
+                if let Some(reveal) = reveal {
+                    self.send_to_client(ClientMessage::Reveal(reveal)).await;
                 }
+                self.send_to_client(ClientMessage::GameCancelled).await;
+                return Err(Error::GameCancelled);
+            }
+            BroadcastMessage::Lobby(lobby) => ClientMessage::Lobby(lobby),
+            BroadcastMessage::Outcome(outcome) => ClientMessage::Outcome(outcome),
+            BroadcastMessage::Declared(action) => ClientMessage::ActionDeclared(action),
+            BroadcastMessage::Reacted(reaction) => ClientMessage::ReactionDeclared(reaction),
+            BroadcastMessage::Undo(true) => ClientMessage::Undone,
+            BroadcastMessage::Undo(false) => ClientMessage::UndoDeclined,
+            BroadcastMessage::WaitingFor(deciders, deadline) => {
+                let waiting = ClientMessage::WaitingFor(deciders, deadline);
+                self.waiting = Some(waiting.clone());
+                waiting
+            }
+            BroadcastMessage::Disconnected(player, given_up) => {
+                ClientMessage::PlayerDisconnected(player, given_up)
+            }
+            BroadcastMessage::Reconnected(player) => ClientMessage::PlayerReconnected(player),
+            BroadcastMessage::Left(player) => ClientMessage::PlayerLeft(player),
+            BroadcastMessage::Chat(chat) => ClientMessage::Chat(chat),
+            BroadcastMessage::Paused(true) => ClientMessage::GamePaused,
+            BroadcastMessage::Paused(false) => ClientMessage::GameResumed,
+        };
+        self.send_to_client(message).await;
+        Ok(false)
+    }
+
+    // waits for the client's next response, passing on broadcasts in the meantime (so players
+    // still see what everyone else is doing while deciding). If the message provided by the client
+    // is invalid in some way, the client is sent an invalid response message and we keep waiting.
+    // None once the deadline is reached, which keeps running while the client is disconnected
+    async fn next_response(&mut self, deadline: Instant) -> Result<Option<ClientResponse>, Error> {
+        let timeout = sleep_until(deadline);
+        tokio::pin!(timeout);
+
+        loop {
+            let message = select! {
+                message = next_message(&mut self.player_receiver) => message,
+                Ok(broadcast) = self.broadcasts.recv() => {
+                    self.forward_broadcast(broadcast).await?;
+                    continue;
+                },
+                Some(socket) = self.reconnections.recv() => {
+                    self.reattach(socket).await;
+                    continue;
+                },
+                Some(()) = self.kicks.recv() => return Err(self.kicked().await),
+                _ = grace_over(self.grace) => return Err(Error::Disconnected),
+                _ = &mut timeout => return Ok(None),
             };
 
-            // we only loop if the message is invalid
-            if result.is_ok() {
-                break Ok(());
+            // if the client disconnects we wait for them to come back, otherwise the message has
+            // to be valid websocket text that deserializes correctly
+            let Some(Ok(message)) =
+                message.filter(|message| !matches!(message, Ok(Message::Close(_))))
+            else {
+                self.disconnect().await;
+                continue;
+            };
+
+            let Ok(response) = message
+                .into_text()
+                .map_err(|_| ClientError::InvalidResponse)
+                .and_then(|text| deserialize::<ClientResponse>(&text))
+            else {
+                self.send_invalid_response().await;
+                continue;
+            };
+            let Some(response) = self.handle_anytime(response).await else {
+                continue;
+            };
+
+            break Ok(Some(response));
+        }
+    }
+
+    // deals with responses that can be sent at any point, whatever the client is asked for,
+    // handing back any other response
+    async fn handle_anytime(&mut self, response: ClientResponse) -> Option<ClientResponse> {
+        match response {
+            ClientResponse::Chat(text) => {
+                let sent = chat::chat(self.state, self.game_id, self.member, &mut self.chat, text);
+                if let Err(e) = sent.await {
+                    self.send(e).await;
+                }
+                None
             }
+            response if is_late_entropy(&response) => None,
+            response => Some(response),
         }
     }
 
-    // tries to parse message and have handler process it. If the message provided by the client is invalid in some way,
-    // the function will send the client an invalid response message and try again until it suceeds or the client disconnects
-    async fn handle_client_response<H>(
+    // sends message, and has response_handler process responses until one is valid or the
+    // deadline is reached (which plays the default response for them). The message is sent again
+    // if the client comes back after losing their connection in the meantime. Invalid responses
+    // come back from response_handler as client errors, anything else ends the wait
+    async fn handle_timed_client_response<H>(
         &mut self,
         message: ClientMessage,
+        deadline: Timestamp,
+        default: ClientResponse,
         mut response_handler: H,
     ) -> Result<(), Error>
     where
         // unfortunately Arc is required because of a bug with AsyncFn(Mut) bounds
-        H: AsyncFnMut(Arc<ClientChannels>, ClientResponse) -> Result<(), ClientError>,
+        H: AsyncFnMut(Arc<ClientChannels>, ClientResponse) -> Result<(), Error>,
     {
+        trace!(deadline = %deadline, "Waiting on timed response");
+        // the deadline is set by the game, so it is the same for every player
+        let remaining =
+            Duration::try_from(deadline.duration_since(Timestamp::now())).unwrap_or(Duration::ZERO);
+        let deadline = Instant::now() + remaining;
+
         // send out initial message
-        self.send_to_client(message).await?;
+        let kind = ChoiceKind::of(&message);
+        self.pending = Some(message.clone());
+        self.send_to_client(message).await;
+        let asked = Instant::now();
 
         loop {
-            let message = self.player_receiver.next().await;
-
-            // if the client disconnects, the message is not a valid websocket message, it is not text,
-            // and/or it doesn't deserialize correctly, the response is invalid and we return
-            // Otherwise, we pass the response to the handler and see if it returns
-            use ClientError as E;
-
-            let Ok(response) = message
-                .ok_or(Error::Disconnected)?
-                .and_then(|msg| msg.into_text())
-                .map_err(|_| E::InvalidResponse)
-                .and_then(|text| deserialize::<ClientResponse>(&text))
-            else {
-                self.send_invalid_response().await?;
-                continue;
+            let response = match self.next_response(deadline).await {
+                Ok(Some(response)) => response,
+                Err(e) if !matches!(e, Error::Disconnected) => return Err(e),
+                // out of time (or gone for good, as the game would otherwise be held up), so a
+                // choice is made for them
+                left => {
+                    debug!(default = ?default, "Client ran out of time");
+                    if let (Ok(None), Some(kind)) = (&left, kind) {
+                        METRICS.timed_out(kind);
+                    }
+                    self.pending = None;
+                    // default responses are always valid
+                    response_handler(Arc::clone(&self.senders), default).await?;
+                    return left.map(|_| ());
+                }
             };
 
-            let result = response_handler(Arc::clone(&self.senders), response).await;
-
             // we only loop if the message is invalid
-            if result.is_ok() {
-                break Ok(());
+            match response_handler(Arc::clone(&self.senders), response).await {
+                Ok(()) => {
+                    METRICS.responded(asked.elapsed());
+                    break;
+                }
+                Err(Error::Client(_)) => self.send_invalid_response().await,
+                Err(e) => {
+                    self.pending = None;
+                    return Err(e);
+                }
             }
         }
+
+        self.pending = None;
+        Ok(())
     }
 
     async fn handle_game_message(&mut self, message: GameMessage) -> Result<(), Error> {
         match message {
             GameMessage::ChooseAction(choices) => self.handle_action_choices(choices).await,
-            GameMessage::ChooseVictim(choices) => self.handle_choosing_victim(choices).await,
-            GameMessage::ChooseOneFromThree(choices) => self.handle_choose_one(choices).await,
-            GameMessage::ChooseTwoFromFour(choices) => self.handle_choose_two(choices).await,
+            GameMessage::ChooseVictim(choices, deadline) => {
+                self.handle_choosing_victim(choices, deadline).await
+            }
+            GameMessage::ChooseOneFromThree(choices, deadline) => {
+                self.handle_choose_one(choices, deadline).await
+            }
+            GameMessage::ChooseTwoFromFour(choices, deadline) => {
+                self.handle_choose_two(choices, deadline).await
+            }
+            GameMessage::ConfirmUndo(requester, deadline) => {
+                self.handle_undo_request(requester, deadline).await
+            }
         }
     }
 
-    async fn handle_choose_two(&mut self, choices: [Card; 4]) -> Result<(), Error> {
-        let message = ClientMessage::TwoFromFourChoices(choices);
+    async fn handle_undo_request(
+        &mut self,
+        requester: PlayerId,
+        deadline: Timestamp,
+    ) -> Result<(), Error> {
+        let message = ClientMessage::UndoRequested(requester, deadline);
+
+        let response_handler = async move |senders: Arc<ClientChannels>, msg| {
+            match msg {
+                ClientResponse::Pass => senders.pass.send(Pass).await?,
+                ClientResponse::AllowUndo(allow) => senders.allow_undo.send(allow).await?,
+                _ => return Err(ClientError::InvalidResponse.into()),
+            }
+            Ok(())
+        };
+
+        self.handle_timed_client_response(message, deadline, ClientResponse::Pass, response_handler)
+            .await
+    }
+
+    async fn handle_choose_two(
+        &mut self,
+        choices: [Card; 4],
+        deadline: Timestamp,
+    ) -> Result<(), Error> {
+        let message = ClientMessage::TwoFromFourChoices(choices, deadline);
+        let default = ClientResponse::ExchangeTwo(random_pair(&choices));
 
         // matching found == chosen cards are valid
         let are_valid_choices = move |cards| match_to_indices(cards, choices).is_some();
@@ -194,144 +460,169 @@ impl<'state> ClientHandle<'state> {
             if let ClientResponse::ExchangeTwo(cards) = msg
                 && are_valid_choices(cards)
             {
-                senders.choose_two.send(cards).await.unwrap();
+                senders.choose_two.send(cards).await?;
                 Ok(())
             } else {
-                Err(ClientError::InvalidResponse)
+                Err(ClientError::InvalidResponse.into())
             }
         };
 
-        self.handle_client_response(message, response_handler).await
+        self.handle_timed_client_response(message, deadline, default, response_handler)
+            .await
     }
 
-    async fn handle_choose_one(&mut self, choices: [Card; 3]) -> Result<(), Error> {
-        let message = ClientMessage::OneFromThreeChoices(choices);
+    async fn handle_choose_one(
+        &mut self,
+        choices: [Card; 3],
+        deadline: Timestamp,
+    ) -> Result<(), Error> {
+        let message = ClientMessage::OneFromThreeChoices(choices, deadline);
+        let default = ClientResponse::ExchangeOne(random_card(&choices));
 
         let response_handler = async move |senders: Arc<ClientChannels>, msg| {
             if let ClientResponse::ExchangeOne(card) = msg
                 && choices.contains(&card)
             {
-                senders.choose_one.send(card).await.unwrap();
+                senders.choose_one.send(card).await?;
                 Ok(())
             } else {
-                Err(ClientError::InvalidResponse)
+                Err(ClientError::InvalidResponse.into())
             }
         };
 
-        self.handle_client_response(message, response_handler).await
+        self.handle_timed_client_response(message, deadline, default, response_handler)
+            .await
     }
 
-    async fn handle_choosing_victim(&mut self, choices: [Card; 2]) -> Result<(), Error> {
-        let message = ClientMessage::VictimChoices(choices);
+    async fn handle_choosing_victim(
+        &mut self,
+        choices: [Card; 2],
+        deadline: Timestamp,
+    ) -> Result<(), Error> {
+        let message = ClientMessage::VictimChoices(choices, deadline);
+        // a random surviving card is lost
+        let default = ClientResponse::ChooseVictim(random_card(&choices));
 
         let response_handler = async move |senders: Arc<ClientChannels>, msg| {
             if let ClientResponse::ChooseVictim(card) = msg
                 && choices.contains(&card)
             {
-                senders.victim_card.send(card).await.unwrap();
+                senders.victim_card.send(card).await?;
                 Ok(())
             } else {
-                Err(ClientError::InvalidResponse)
+                Err(ClientError::InvalidResponse.into())
             }
         };
 
-        self.handle_client_response(message, response_handler).await
+        self.handle_timed_client_response(message, deadline, default, response_handler)
+            .await
     }
 
     #[instrument(skip(self, choices))]
     async fn handle_action_choices(&mut self, choices: Choices) -> Result<(), Error> {
         match choices {
-            Choices::Actions(actions) => self.handle_actions(actions).await,
-            Choices::Challenge(challenge) => self.handle_challenge(challenge).await,
-            Choices::Block(blocks) => self.handle_blocks(blocks).await,
-            Choices::Reactions(reactions) => self.handle_reactions(reactions).await,
+            Choices::Actions(actions, deadline) => self.handle_actions(actions, deadline).await,
+            Choices::Challenge(challenge, deadline) => {
+                self.handle_challenge(challenge, deadline).await
+            }
+            Choices::Block(blocks, deadline) => self.handle_blocks(blocks, deadline).await,
+            Choices::Reactions(reactions, stage, deadline) => {
+                self.handle_reactions(reactions, stage, deadline).await
+            }
         }
     }
 
-    async fn handle_actions(&mut self, actions: Vec<Action>) -> Result<(), Error> {
-        let message = ClientMessage::ActionChoices(actions.clone());
+    async fn handle_actions(
+        &mut self,
+        actions: Vec<Action>,
+        deadline: Timestamp,
+    ) -> Result<(), Error> {
+        let message = ClientMessage::ActionChoices(actions.clone(), deadline);
+        let default = ClientResponse::Act(default_action(&actions));
+        let undo = self.undo;
 
         let response_handler = async move |senders: Arc<ClientChannels>, msg| {
-            if let ClientResponse::Act(action) = msg
-                && actions.contains(&action)
-            {
-                senders.action.send(action).await.unwrap();
-                Ok(())
-            } else {
-                Err(ClientError::InvalidResponse)
+            match msg {
+                ClientResponse::Act(action) if actions.contains(&action) => {
+                    senders.action.send(action).await?
+                }
+                ClientResponse::Undo if undo => senders.undo.send(UndoRequest).await?,
+                _ => return Err(ClientError::InvalidResponse.into()),
             }
+            Ok(())
         };
 
-        self.handle_client_response(message, response_handler).await
+        self.handle_timed_client_response(message, deadline, default, response_handler)
+            .await
     }
 
-    async fn handle_blocks(&mut self, blocks: Blocks) -> Result<(), Error> {
-        // FIXME: used to resolve higher-kinded lifetime errors
-        let message_blocks = blocks.clone();
-        let message_builder =
-            move |timestamp| ClientMessage::BlockChoices(message_blocks, timestamp);
+    async fn handle_blocks(&mut self, blocks: Blocks, deadline: Timestamp) -> Result<(), Error> {
+        let message = ClientMessage::BlockChoices(blocks.clone(), deadline);
 
         let response_handler = async move |senders: Arc<ClientChannels>, msg| {
             match msg {
-                ClientResponse::Pass => senders.pass.send(Pass).await.unwrap(),
+                ClientResponse::Pass => senders.pass.send(Pass).await?,
                 ClientResponse::Block(block_as) if blocks.claims(block_as) => {
-                    ClientHandle::handle_block(senders, blocks.clone(), block_as).await
+                    ClientHandle::handle_block(senders, blocks.clone(), block_as).await?
                 }
-                _ => return Err(ClientError::InvalidResponse),
+                _ => return Err(ClientError::InvalidResponse.into()),
             }
             Ok(())
         };
 
-        self.handle_timed_client_response(message_builder, response_handler)
+        self.handle_timed_client_response(message, deadline, ClientResponse::Pass, response_handler)
             .await
     }
 
-    async fn handle_challenge(&mut self, challenge: Challenge) -> Result<(), Error> {
-        // FIXME: used to resolve higher-kinded lifetime errors
-        let builder_challenge = challenge.clone();
-        let builder =
-            move |countdown_end| ClientMessage::ChallengeChoice(builder_challenge, countdown_end);
+    async fn handle_challenge(
+        &mut self,
+        challenge: Challenge,
+        deadline: Timestamp,
+    ) -> Result<(), Error> {
+        let message = ClientMessage::ChallengeChoice(challenge.clone(), deadline);
 
         let response_handler = async move |senders: Arc<ClientChannels>, msg| {
             match msg {
-                ClientResponse::Pass => senders.pass.send(Pass).await.unwrap(),
-                ClientResponse::Challenge => {
-                    senders.challenge.send(challenge.clone()).await.unwrap()
-                }
-                _ => return Err(ClientError::InvalidResponse),
+                ClientResponse::Pass => senders.pass.send(Pass).await?,
+                ClientResponse::Challenge => senders.challenge.send(challenge.clone()).await?,
+                _ => return Err(ClientError::InvalidResponse.into()),
             }
             Ok(())
         };
 
-        self.handle_timed_client_response(builder, response_handler)
+        self.handle_timed_client_response(message, deadline, ClientResponse::Pass, response_handler)
             .await
     }
 
-    async fn handle_reactions(&mut self, reactions: Vec<Reaction>) -> Result<(), Error> {
-        // FIXME: used to resolve higher-kinded lifetime errors
-        let builder_reactions = reactions.clone();
-        let message_builder =
-            move |timestamp| ClientMessage::ReactionChoices(builder_reactions, timestamp);
+    async fn handle_reactions(
+        &mut self,
+        reactions: Vec<Reaction>,
+        stage: ReactionStage,
+        deadline: Timestamp,
+    ) -> Result<(), Error> {
+        let message = ClientMessage::ReactionChoices(reactions.clone(), stage, deadline);
 
         let response_handler = async move |senders: Arc<ClientChannels>, msg| {
             match msg {
-                ClientResponse::Pass => senders.pass.send(Pass).await.unwrap(),
+                ClientResponse::Pass => senders.pass.send(Pass).await?,
                 ClientResponse::React(react) if reactions.contains(&react) => match react {
-                    Reaction::Block(block) => senders.block.send(block).await.unwrap(),
-                    Reaction::Challenge(challenge) => {
-                        senders.challenge.send(challenge).await.unwrap()
-                    }
+                    Reaction::Block(block) => senders.block.send(block).await?,
+                    Reaction::Challenge(challenge) => senders.challenge.send(challenge).await?,
                 },
-                _ => return Err(ClientError::InvalidResponse),
+                _ => return Err(ClientError::InvalidResponse.into()),
             }
             Ok(())
         };
 
-        self.handle_timed_client_response(message_builder, response_handler)
+        self.handle_timed_client_response(message, deadline, ClientResponse::Pass, response_handler)
             .await
     }
 
-    async fn handle_block(senders: Arc<ClientChannels>, blocks: Blocks, block_as: Card) {
+    async fn handle_block(
+        senders: Arc<ClientChannels>,
+        blocks: Blocks,
+        block_as: Card,
+    ) -> Result<(), Error> {
         let block = match blocks {
             Blocks::Other(block) => block,
             Blocks::Steal(b1, b2) => {
@@ -342,147 +633,311 @@ impl<'state> ClientHandle<'state> {
                 }
             }
         };
-        senders.block.send(block).await.unwrap();
+        senders.block.send(block).await?;
+        Ok(())
     }
 }
 
-#[instrument(skip(stream, state), fields(game_id))]
-pub async fn client_handler(addr: SocketAddr, stream: WebSocket, state: AppState) {
+#[instrument(skip(stream, state, join), fields(game_id))]
+pub async fn client_handler(
+    addr: SocketAddr,
+    stream: WebSocket,
+    state: AppState,
+    settings: LobbySettings,
+    join: Join,
+) {
+    // coming back to a game in progress instead of joining a lobby
+    if let Some(token) = join.resume {
+        resume(stream, token, &state).await;
+        return;
+    }
+
     // By splitting, we can send and receive at the same time.
-    let (mut client_sender, mut client_receiver) = stream.split();
+    let (mut client_sender, client_receiver) = stream.split();
 
     // register client with dispatcher
-    let (dispatch_sender, dispatch_receiver) = oneshot::channel();
-    let (game_id_sender, game_id_receiver) = oneshot::channel();
+    let (game, dispatch_receiver) = oneshot::channel();
+    let (joined, joined_receiver) = oneshot::channel();
     tracing::debug!("Registering new client with dispatcher");
     state
         .register
-        .send((dispatch_sender, game_id_sender))
+        .send(Registration {
+            addr,
+            settings,
+            join,
+            game,
+            joined,
+        })
         .await
         .expect("Should never fail to send to dispatcher");
 
-    let game_id = game_id_receiver.await.expect("Should always send game id");
+    let joined = match joined_receiver.await.expect("Should always reply") {
+        Ok(joined) => joined,
+        Err(e) => {
+            // let the client know why they couldn't join before closing
+            tracing::debug!("Client couldn't join lobby: {e}");
+            let _ = client_sender.send(Message::Text(serialize(e))).await;
+            let _ = client_sender.send(Message::Close(None)).await;
+            return;
+        }
+    };
+    let game_id = joined.game_id;
     // add game_id to context when logging
     tracing::Span::current().record("game_id", game_id.to_string());
 
     if client_handler_inner(
-        game_id,
+        addr,
+        joined,
+        &state,
         dispatch_receiver,
-        &mut client_sender,
-        &mut client_receiver,
+        client_sender,
+        client_receiver,
     )
     .await
     .is_err()
     {
-        tracing::error!("Player has disconnected");
+        tracing::error!("Player has left");
         state
             .disconnected
             .send(Disconnected { addr, game_id })
             .await
             .expect("Dispatcher should always be available");
-    } else {
-        // game is over and client is still connected, so we can close cleanly (ignore error if client disconnects right before sending this)
-        let _ = client_receiver
-            .reunite(client_sender)
-            .expect("Should always reunite")
-            .send(Message::Close(None))
-            .await;
     }
 }
 
+// hands a new connection over to the seat it wants to take back, if there is still one to go to
+async fn resume(mut stream: WebSocket, token: Uuid, state: &AppState) {
+    let (reply, seat) = oneshot::channel();
+    state
+        .resume
+        .send(Resume { token, reply })
+        .await
+        .expect("Dispatcher should always be available");
+
+    if let Some(seat) = seat.await.expect("Should always reply") {
+        match seat.send(stream).await {
+            Ok(()) => return,
+            // the game finished (or was given up on) right before the connection got there
+            Err(SendError(returned)) => stream = returned,
+        }
+    }
+
+    tracing::debug!("Client couldn't take back their seat");
+    let _ = stream
+        .send(Message::Text(serialize(ClientError::CannotResume)))
+        .await;
+    let _ = stream.send(Message::Close(None)).await;
+}
+
 async fn client_handler_inner(
-    game_id: Uuid,
+    addr: SocketAddr,
+    Joined {
+        game_id,
+        member,
+        commitment,
+        mut broadcasts,
+        token,
+        reconnections,
+        mut kicks,
+    }: Joined,
+    state: &AppState,
     mut dispatch_receiver: oneshot::Receiver<PlayerGameInfo>,
-    client_sender: &mut SplitSink<WebSocket, Message>,
-    client_receiver: &mut SplitStream<WebSocket>,
+    mut client_sender: SplitSink<WebSocket, Message>,
+    mut client_receiver: SplitStream<WebSocket>,
 ) -> Result<(), Error> {
     // seng game id first
     client_sender
         .send(Message::Text(serialize(ClientMessage::GameId(game_id))))
         .await?;
+    client_sender
+        .send(Message::Text(serialize(ClientMessage::MemberId(member))))
+        .await?;
+    client_sender
+        .send(Message::Text(serialize(ClientMessage::ResumeToken(token))))
+        .await?;
+    client_sender
+        .send(Message::Text(serialize(ClientMessage::Commitment(
+            commitment,
+        ))))
+        .await?;
 
-    // while we are waiting to connect to a game
+    // while we are waiting in the lobby for the game to start
+    let mut chat_limit = ChatLimit::default();
     let PlayerGameInfo {
         id,
-        mut broadcast_receiver,
+        undo,
         mut info,
         channels: (tx, mut rx),
     } = loop {
         select! {
+            // the game is only broadcast to once everyone has their channels, so checking for them
+            // first means nothing from the game is mistaken for the lobby
+            biased;
             Ok(game_channel) = &mut dispatch_receiver => {
                 // now a game has started, so we can break out of the loop
                 break game_channel;
             }
-            Some(Ok(message)) = client_receiver.next() => {
-                if let Message::Text(text) = message {
-                    tracing::debug!("Client sent data before game started: {text}");
-                    let message = Message::Text(serialize(ClientError::NotReady));
-                    client_sender.send(message).await?;
-                }
+            Ok(broadcast) = broadcasts.recv() => {
+                let message = match broadcast {
+                    BroadcastMessage::Lobby(lobby) => ClientMessage::Lobby(lobby),
+                    BroadcastMessage::Chat(chat) => ClientMessage::Chat(chat),
+                    // the lobby was closed by the server's operator, before there was anything
+                    // to reveal
+                    BroadcastMessage::GameCancelled(..) => {
+                        client_sender.send(Message::Text(serialize(ClientMessage::GameCancelled))).await?;
+                        let _ = client_sender.send(Message::Close(None)).await;
+                        return Err(Error::GameCancelled);
+                    }
+                    _ => continue,
+                };
+                client_sender.send(Message::Text(serialize(message))).await?;
+            }
+            Some(()) = kicks.recv() => {
+                client_sender.send(Message::Text(serialize(ClientError::Kicked))).await?;
+                let _ = client_sender.send(Message::Close(None)).await;
+                return Err(Error::Kicked);
             }
-            else => {
-                return Err(Error::Disconnected);
+            message = client_receiver.next() => {
+                let message = match message {
+                    Some(Ok(message)) => message,
+                    Some(Err(e)) => return Err(e.into()),
+                    None => return Err(Error::Disconnected),
+                };
+                let Message::Text(text) = message else { continue };
+
+                // only entropy, chat, and getting ready or starting, can be sent before the game
+                // starts
+                let action = match deserialize::<ClientResponse>(&text) {
+                    Ok(ClientResponse::Chat(text)) => {
+                        if let Err(e) = chat::chat(state, game_id, member, &mut chat_limit, text).await {
+                            client_sender.send(Message::Text(serialize(e))).await?;
+                        }
+                        continue;
+                    }
+                    Ok(ClientResponse::Entropy(entropy)) if entropy.len() <= MAX_ENTROPY_LEN => {
+                        let entropy = Entropy { addr, game_id, entropy };
+                        state.entropy.send(entropy).await.expect("Dispatcher should always be available");
+                        continue;
+                    }
+                    Ok(ClientResponse::Entropy(..)) => {
+                        METRICS.invalid_response();
+                        let message = Message::Text(serialize(ClientError::InvalidResponse));
+                        client_sender.send(message).await?;
+                        continue;
+                    }
+                    Ok(ClientResponse::Ready(ready)) => LobbyAction::Ready(ready),
+                    Ok(response @ (ClientResponse::Start | ClientResponse::AddBot(..) | ClientResponse::RemoveBot(..))) => {
+                        let (reply, replied) = oneshot::channel();
+                        let action = match response {
+                            ClientResponse::AddBot(difficulty) => LobbyAction::AddBot(difficulty, reply),
+                            ClientResponse::RemoveBot(member) => LobbyAction::RemoveBot(member, reply),
+                            _ => LobbyAction::Start(reply),
+                        };
+                        let request = LobbyRequest { addr, game_id, action };
+                        state.lobby.send(request).await.expect("Dispatcher should always be available");
+
+                        if let Ok(Err(e)) = replied.await {
+                            client_sender.send(Message::Text(serialize(e))).await?;
+                        }
+                        continue;
+                    }
+                    _ => {
+                        tracing::debug!("Client sent data before game started: {text}");
+                        let message = Message::Text(serialize(ClientError::NotReady));
+                        client_sender.send(message).await?;
+                        continue;
+                    }
+                };
+
+                let request = LobbyRequest { addr, game_id, action };
+                state.lobby.send(request).await.expect("Dispatcher should always be available");
             }
         }
     };
 
-    tracing::trace!(player_id = ?id, "Sending client their assigned PlayerId");
-    // send client their assigned PlayerId
-    client_sender
-        .send(Message::Text(serialize(ClientMessage::PlayerId(id))))
-        .await?;
-
     let mut client = ClientHandle {
-        player_receiver: client_receiver,
-        player_sender: client_sender,
+        state,
+        game_id,
+        member,
+        chat: chat_limit,
+        id,
+        player_sender: Some(client_sender),
+        player_receiver: Some(client_receiver),
+        reconnections,
+        kicks,
+        grace: None,
         senders: Arc::new(tx),
+        broadcasts,
+        undo,
+        commitment,
+        info: None,
+        waiting: None,
+        pending: None,
     };
 
+    tracing::trace!(player_id = ?id, "Sending client their assigned PlayerId");
+    // send client their assigned PlayerId
+    client.send_to_client(ClientMessage::PlayerId(id)).await;
+
     // check for messages from the game itself, as there is nothing the player can do (yet)
-    loop {
+    let result = loop {
         select! {
-            // TODO: find way to encapsulate player_receiver
-            Some(Ok(message)) = client.player_receiver.next() => {
-                if matches!(message, Message::Close(_)) {
-                    break Err(Error::Disconnected);
+            message = next_message(&mut client.player_receiver) => {
+                let Some(Ok(message)) = message.filter(|message| {
+                    !matches!(message, Ok(Message::Close(_)))
+                }) else {
+                    client.disconnect().await;
+                    continue;
+                };
+
+                if let Message::Text(text) = &message
+                    && let Ok(response) = deserialize(text)
+                    && client.handle_anytime(response).await.is_none()
+                {
+                    continue;
                 }
 
                 debug!(player_id = ?id, "Received premature message from client: {message:?}");
-                client.send_not_ready().await?;
+                client.send(ClientError::NotReady).await;
             },
             // send client their views when we receive them
-            Some(info) = info.recv() => client.send_to_client(ClientMessage::Info(info)).await?,
+            Some(info) = info.recv() => client.send_info(info).await,
             Some(message) = rx.recv() => {
-                select! {
-                    res = client.handle_game_message(message) => res?,
-                    Ok(BroadcastMessage::GameCancelled) = broadcast_receiver.recv() => {
-                        client.send_game_cancelled().await?;
-                        break Err(Error::GameCancelled);
-                    },
+                if let Err(e) = client.handle_game_message(message).await {
+                    break Err(e);
                 }
             },
-            Ok(broadcast) = broadcast_receiver.recv() => {
-                match broadcast {
-                    BroadcastMessage::End(summary) => {
-                        client.send_to_client(ClientMessage::End(summary)).await?;
-                        todo!()
-                    }
-                    BroadcastMessage::Outcome(outcome) => client.send_to_client(ClientMessage::Outcome(outcome)).await?,
-                    BroadcastMessage::GameCancelled => {
-                        client.send_to_client(ClientMessage::GameCancelled).await?;
-                        break Err(Error::GameCancelled);
-                    }
+            Ok(broadcast) = client.broadcasts.recv() => {
+                if client.forward_broadcast(broadcast).await? {
+                    break Ok(());
                 }
             },
+            Some(socket) = client.reconnections.recv() => client.reattach(socket).await,
+            Some(()) = client.kicks.recv() => break Err(client.kicked().await),
+            _ = grace_over(client.grace) => break Err(Error::Disconnected),
         }
+    };
+
+    match result {
+        // whoever left still has a seat, which is played for them until the game takes them out
+        // (or for the rest of the game, depending on the lobby)
+        Err(Error::Disconnected | Error::Kicked) => {
+            tokio::spawn(bot::autopilot(client.id, client.senders.clone(), rx, info));
+            // nothing is left to close unless they were kicked
+            client.close().await;
+        }
+        // game is over, so the client can be let go of cleanly
+        _ => client.close().await,
     }
+    result
 }
 
-fn get_countdown_time() -> (Timestamp, Instant) {
-    // getting countdown timestamp
-    let duration = Duration::from_secs(10);
-    let countdown_end = Timestamp::now() + duration;
-    let instant = Instant::now() + duration;
-
-    (countdown_end, instant)
+// entropy that arrives once the game has started can't be used anymore, but clients can't know
+// when that is, so it is dropped rather than treated as an invalid response
+fn is_late_entropy(response: &ClientResponse) -> bool {
+    let late = matches!(response, ClientResponse::Entropy(..));
+    if late {
+        debug!("Ignoring entropy sent after the game started");
+    }
+    late
 }
@@ -1,27 +1,178 @@
-use crate::Disconnected;
-use crate::game::{BroadcastMessage, Pass, PlayerCommunicationError, PlayerGameInfo};
+use crate::admin::{GameDetails, GameOverview, MemberOverview, Stage};
+use crate::archive::{self, Archive, Recording};
+use crate::game::{
+    BroadcastMessage, Pass, PlayerCommunicationError, PlayerGameInfo, Steering, UndoRequest, View,
+};
+use crate::{
+    AdminRequest, Chat, Connection, Disconnected, Entropy, LobbyAction, LobbyRequest, Resume,
+    Spectate,
+};
 
 use super::game::GameMessage;
 
+use super::bot;
 use super::game::coup_game;
+use axum::extract::ws::WebSocket;
+use jiff::Timestamp;
 use overthrow_engine::action::{Action, Block, Challenge};
+use overthrow_engine::commitment::{Commitment, Reveal, Secret};
 use overthrow_engine::deck::Card;
 use overthrow_engine::players::PlayerId;
-use overthrow_types::{Info, Summary};
+use overthrow_types::{
+    ChatMessage, ClientError, Difficulty, Info, Leave, LobbyState, Member, Summary,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::mem;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::select;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc::{self, Receiver, Sender};
-use tokio::sync::oneshot;
-use tokio::task::JoinHandle;
+use tokio::sync::{oneshot, watch};
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio::time::sleep;
 use tracing::instrument;
 use uuid::Uuid;
 
 pub type PlayerHalf = (ClientChannels, Receiver<GameMessage>);
 pub type GameHalf = (Sender<GameMessage>, GameChannels);
-pub type TaskReceiver = Receiver<(oneshot::Sender<PlayerGameInfo>, oneshot::Sender<Uuid>)>;
+// games need at least 2 players, and the deck only has enough cards for 6
+const MIN_PLAYERS: usize = 2;
+const MAX_PLAYERS: usize = 6;
+
+// join codes leave out characters that are easy to mix up (like 0 and O)
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 5;
+
+// longest name a player can go by, in characters
+const MAX_NAME_LEN: usize = 24;
+
+// quick matches start once this many players are in, or with bots in the empty seats after waiting
+const QUICK_MATCH_SEATS: usize = 4;
+const QUICK_MATCH_WAIT: Duration = Duration::from_secs(30);
+const QUICK_MATCH_DIFFICULTY: Difficulty = Difficulty::Medium;
+
+// a connection waiting to join a lobby, and later for its channels once the game starts
+#[derive(Debug)]
+pub struct Registration {
+    pub addr: SocketAddr,
+    pub settings: LobbySettings,
+    pub join: Join,
+    pub game: oneshot::Sender<PlayerGameInfo>,
+    pub joined: oneshot::Sender<Result<Joined, ClientError>>,
+}
+
+// the lobby a connection was put in
+#[derive(Debug)]
+pub struct Joined {
+    pub game_id: Uuid,
+    pub member: Uuid,
+    pub commitment: Commitment,
+    // lobby states until the game starts, then everything else broadcast during the game
+    pub broadcasts: broadcast::Receiver<BroadcastMessage>,
+    // for taking the seat back after losing the connection, along with where new connections
+    // for it are handed over to
+    pub token: Uuid,
+    pub reconnections: Receiver<WebSocket>,
+    // sent to when the server's operator takes the player out
+    pub kicks: Receiver<()>,
+}
+
+// what a spectator is passed on from a game they are let in to watch
+#[derive(Debug)]
+pub struct Watching {
+    pub broadcasts: broadcast::Receiver<BroadcastMessage>,
+    pub view: watch::Receiver<Option<View>>,
+    // seconds behind the game the lobby wants spectators to be at least
+    pub delay: u32,
+    // whether the lobby lets spectators see chat
+    pub chat: bool,
+    // sent to when the server's operator sends the spectator away
+    pub kicks: Receiver<()>,
+}
+
+// timers are kept long enough to decide in, but short enough that nobody can stall a game for long
+const MIN_TIME: u32 = 5;
+pub const MAX_TIME: u32 = 600;
+
+// what a player wants from their lobby, given as query parameters when connecting. These are only
+// used when creating a lobby, so everyone joining it can see them before getting ready
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LobbySettings {
+    // turns can be taken back, if every other player agrees
+    pub undo: bool,
+    // seconds players have to react (or vote on taking back a turn) before passing
+    pub reaction_time: u32,
+    // seconds the current player has to choose an action before taking income
+    pub action_time: u32,
+    // seconds players have to choose cards before random ones are chosen for them
+    pub choice_time: u32,
+    // what happens to the seats of players who leave mid-game
+    pub leave: Leave,
+    // spectators can watch with every hand showing, for teaching games
+    pub omniscient: bool,
+    // seconds spectators are kept behind the game, so they can't pass on what they see in time
+    pub spectator_delay: u32,
+    // spectators are sent what players say
+    pub spectator_chat: bool,
+}
+
+impl Default for LobbySettings {
+    fn default() -> Self {
+        LobbySettings {
+            undo: false,
+            reaction_time: 10,
+            action_time: 60,
+            choice_time: 30,
+            leave: Leave::Resign,
+            omniscient: false,
+            spectator_delay: 0,
+            spectator_chat: false,
+        }
+    }
+}
+
+impl LobbySettings {
+    fn clamped(self) -> LobbySettings {
+        let clamp = |time: u32| time.clamp(MIN_TIME, MAX_TIME);
+        LobbySettings {
+            reaction_time: clamp(self.reaction_time),
+            action_time: clamp(self.action_time),
+            choice_time: clamp(self.choice_time),
+            spectator_delay: self.spectator_delay.min(MAX_TIME),
+            ..self
+        }
+    }
+}
+
+// which lobby a player wants to be in, also given as query parameters. Without a code, a new lobby
+// is created with the player as its host
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Join {
+    pub code: Option<String>,
+    pub name: Option<String>,
+    // joins whichever quick match is waiting for players, instead of a new lobby
+    pub quick: bool,
+    // takes back a seat in a game instead of joining a lobby
+    pub resume: Option<Uuid>,
+}
+
+impl Join {
+    // names are trimmed down to something that fits in a player list
+    fn name(&self) -> String {
+        match self.name.as_deref().unwrap_or_default().trim() {
+            "" => "Anonymous".to_owned(),
+            name => name.chars().take(MAX_NAME_LEN).collect(),
+        }
+    }
+}
+
+pub type TaskReceiver = Receiver<Registration>;
 type Channels = (Vec<PlayerGameInfo>, HashMap<PlayerId, GameHalf>);
 
 // Each client has 6 senders and 1 receiver:
@@ -35,6 +186,8 @@ pub struct ClientChannels {
     pub choose_one: Sender<Card>,
     pub choose_two: Sender<[Card; 2]>,
     pub pass: Sender<Pass>,
+    pub undo: Sender<UndoRequest>,
+    pub allow_undo: Sender<bool>,
 }
 
 // The game task has 6 receivers and 1 sender per client
@@ -49,21 +202,358 @@ pub struct GameChannels {
     pub choose_two: Receiver<[Card; 2]>,
     pub info: Sender<Info>,
     pub pass: Receiver<Pass>,
+    pub undo: Receiver<UndoRequest>,
+    pub allow_undo: Receiver<bool>,
+}
+
+// a seat in a lobby, the first player in which is its host
+#[derive(Debug)]
+struct LobbyMember {
+    id: Uuid,
+    name: String,
+    ready: bool,
+    // whether they have left the game for good
+    left: bool,
+    // whether their connection is up right now, never for bots
+    connected: bool,
+    seat: Seat,
+}
+
+#[derive(Debug)]
+enum Seat {
+    Player {
+        addr: SocketAddr,
+        // taken once the game starts
+        sender: Option<oneshot::Sender<PlayerGameInfo>>,
+        // secret the player can take their seat back with, once the game has started
+        token: Uuid,
+        reattach: Sender<WebSocket>,
+        kick: Sender<()>,
+    },
+    // played by the server once the game starts
+    Bot(Difficulty),
+}
+
+impl LobbyMember {
+    fn is(&self, addr: SocketAddr) -> bool {
+        matches!(self.seat, Seat::Player { addr: player, .. } if player == addr)
+    }
+
+    fn is_player(&self) -> bool {
+        matches!(self.seat, Seat::Player { .. })
+    }
 }
 
 // information for a given game/lobby
 #[derive(Debug)]
 struct GameInfo {
-    channel_senders: Vec<oneshot::Sender<PlayerGameInfo>>,
+    code: String,
+    members: Vec<LobbyMember>,
     broadcaster: Arc<broadcast::Sender<BroadcastMessage>>,
-    handle: Option<JoinHandle<Result<Summary, PlayerCommunicationError>>>,
+    // the game task, once it has started
+    handle: Option<AbortHandle>,
+    // committed to when the lobby is created, and revealed once the game ends
+    secret: Secret,
+    // latest entropy from each player, in the order they first sent it
+    entropy: Vec<(SocketAddr, String)>,
+    // made out of the secret and entropy once the game starts, for revealing if it is cancelled
+    reveal: Option<Reveal>,
+    settings: LobbySettings,
+    // players who left the game for good are sent to it, once it has started
+    leaves: Option<Sender<PlayerId>>,
+    // when the empty seats are filled with bots, for quick matches
+    fill_at: Option<Timestamp>,
+    // latest state of the game for spectators, and who is watching it
+    view: Arc<watch::Sender<Option<View>>>,
+    spectators: Vec<(SocketAddr, Sender<()>)>,
+    created: Timestamp,
+    // set by the server's operator, to stop the game at the start of the next turn
+    paused: watch::Sender<bool>,
 }
 
-fn generate_channels(
-    len: usize,
-    broadcaster: Arc<broadcast::Sender<BroadcastMessage>>,
-) -> Channels {
-    // broadcast channel for general updates
+impl GameInfo {
+    fn host(&self) -> Option<&LobbyMember> {
+        self.members.iter().find(|member| member.is_player())
+    }
+
+    fn check_host(&self, addr: SocketAddr) -> Result<(), ClientError> {
+        match self.host() {
+            Some(host) if host.is(addr) => Ok(()),
+            _ => Err(ClientError::NotHost),
+        }
+    }
+
+    fn state(&self) -> LobbyState {
+        let members = self.members.iter().map(|member| Member {
+            id: member.id,
+            name: member.name.clone(),
+            ready: member.ready,
+            bot: match member.seat {
+                Seat::Player { .. } => None,
+                Seat::Bot(difficulty) => Some(difficulty),
+            },
+        });
+
+        LobbyState {
+            code: self.code.clone(),
+            host: self.host().map(|host| host.id).unwrap_or_default(),
+            members: members.collect(),
+            undo: self.settings.undo,
+            reaction_time: self.settings.reaction_time,
+            action_time: self.settings.action_time,
+            choice_time: self.settings.choice_time,
+            leave: self.settings.leave,
+            omniscient: self.settings.omniscient,
+            spectator_delay: self.settings.spectator_delay,
+            spectator_chat: self.settings.spectator_chat,
+            fill_at: self.fill_at,
+        }
+    }
+
+    fn broadcast_state(&self) {
+        // nobody might be listening yet
+        let _ = self.broadcaster.send(BroadcastMessage::Lobby(self.state()));
+    }
+
+    // starts the game if the host asked to and everyone is ready
+    fn start(
+        &mut self,
+        addr: SocketAddr,
+        game_id: Uuid,
+        archive: &Arc<Archive>,
+        archived: &Sender<Uuid>,
+        failed: &Sender<Uuid>,
+    ) -> Result<(), ClientError> {
+        self.check_host(addr)?;
+        if self.members.len() < MIN_PLAYERS || !self.members.iter().all(|member| member.ready) {
+            return Err(ClientError::PlayersNotReady);
+        }
+
+        tracing::debug!(game_id = %game_id, "Host started game");
+        self.launch(game_id, archive, archived, failed);
+        Ok(())
+    }
+
+    fn add_bot(&mut self, addr: SocketAddr, difficulty: Difficulty) -> Result<(), ClientError> {
+        self.check_host(addr)?;
+        if self.members.len() >= MAX_PLAYERS {
+            return Err(ClientError::LobbyFull);
+        }
+
+        self.seat_bot(difficulty);
+        Ok(())
+    }
+
+    fn remove_bot(&mut self, addr: SocketAddr, member: Uuid) -> Result<(), ClientError> {
+        self.check_host(addr)?;
+        let index = self
+            .members
+            .iter()
+            .position(|bot| bot.id == member && !bot.is_player())
+            .ok_or(ClientError::InvalidResponse)?;
+
+        self.members.remove(index);
+        Ok(())
+    }
+
+    fn seat_bot(&mut self, difficulty: Difficulty) {
+        // numbered so bots of the same difficulty can be told apart
+        let name = (1..)
+            .map(|number| format!("{difficulty:?} Bot {number}"))
+            .find(|name| self.members.iter().all(|member| member.name != *name))
+            .expect("There are only so many members");
+
+        self.members.push(LobbyMember {
+            id: Uuid::now_v7(),
+            name,
+            ready: true,
+            left: false,
+            connected: false,
+            seat: Seat::Bot(difficulty),
+        });
+    }
+
+    fn overview(&self, game_id: Uuid) -> GameOverview {
+        let stage = match &self.handle {
+            None => Stage::Lobby,
+            Some(handle) if handle.is_finished() => Stage::Over,
+            Some(..) => Stage::Playing,
+        };
+        let members = self.members.iter().map(|member| {
+            let (addr, bot) = match member.seat {
+                Seat::Player { addr, .. } => (Some(addr), None),
+                Seat::Bot(difficulty) => (None, Some(difficulty)),
+            };
+            MemberOverview {
+                id: member.id,
+                name: member.name.clone(),
+                ready: member.ready,
+                left: member.left,
+                connected: member.connected && !member.left,
+                addr,
+                bot,
+            }
+        });
+
+        GameOverview {
+            id: game_id,
+            code: self.code.clone(),
+            stage,
+            paused: *self.paused.borrow(),
+            created: self.created,
+            members: members.collect(),
+            spectators: self.spectators.iter().map(|(addr, _)| *addr).collect(),
+        }
+    }
+
+    // everything there is to know about the game, with every hand showing
+    fn details(&self, game_id: Uuid) -> GameDetails {
+        let view = self.view.borrow();
+        GameDetails {
+            overview: self.overview(game_id),
+            settings: self.settings,
+            commitment: self.secret.commitment(),
+            entropy: self.entropy.len(),
+            info: view.as_ref().map(|view| view.omniscient.clone()),
+            summary: view
+                .as_ref()
+                .and_then(|view| view.end.as_ref().map(|(summary, _)| *summary)),
+        }
+    }
+
+    // tells whoever is connected from addr, as a player or a spectator, that they were kicked.
+    // Their connection then leaves the way it would if it had closed
+    fn kick(&self, addr: SocketAddr) -> bool {
+        let players = self.members.iter().filter_map(|member| match &member.seat {
+            Seat::Player {
+                addr: player, kick, ..
+            } if *player == addr => Some(kick),
+            _ => None,
+        });
+        let spectators = self
+            .spectators
+            .iter()
+            .filter(|(spectator, _)| *spectator == addr)
+            .map(|(_, kick)| kick);
+
+        // players who already left aren't listening anymore. Every match is kicked, as the same
+        // connection can be both
+        players
+            .chain(spectators)
+            .fold(false, |kicked, kick| kick.try_send(()).is_ok() | kicked)
+    }
+
+    // hands every seat its channels and starts the game task, whether everyone is ready or not.
+    // The game is recorded as it goes, and once it is over and archived its id is sent to archived.
+    // If the game task fails instead, its id is sent to failed
+    fn launch(
+        &mut self,
+        game_id: Uuid,
+        archive: &Arc<Archive>,
+        archived: &Sender<Uuid>,
+        failed: &Sender<Uuid>,
+    ) {
+        let (player_half, game_half) = generate_channels(self.members.len(), self.settings);
+        let names: Vec<String> = self
+            .members
+            .iter()
+            .map(|member| member.name.clone())
+            .collect();
+        let entropy = mem::take(&mut self.entropy)
+            .into_iter()
+            .map(|(_, entropy)| entropy)
+            .collect();
+        let reveal = Reveal::new(self.secret, entropy);
+        self.reveal = Some(reveal.clone());
+        let (leaves, left) = mpsc::channel(MAX_PLAYERS);
+        self.leaves = Some(leaves);
+
+        // listening before the game starts, so nothing is missed
+        let recording = Recording {
+            id: game_id,
+            players: names.clone(),
+            reveal: reveal.clone(),
+            created: self.created,
+            started: Timestamp::now(),
+        };
+        let record = archive::record(
+            archive.clone(),
+            recording,
+            self.broadcaster.subscribe(),
+            self.view.subscribe(),
+        );
+        let archived = archived.clone();
+        tokio::spawn(async move {
+            record.await;
+            // the dispatcher outlives every game
+            let _ = archived.send(game_id).await;
+        });
+
+        // send back the player task's half of the channel, so it can communicate with the coup
+        // game task. This happens before the game starts, so players have their channels before
+        // anything from the game is broadcast
+        tracing::trace!(game_id = %game_id, "Sending players their channels");
+        for (member, channel) in self.members.iter_mut().zip(player_half) {
+            match &mut member.seat {
+                Seat::Player { sender, .. } => {
+                    let sender = sender.take().expect("Game has not started yet");
+                    // the player may have just left, with their Disconnected still on its way. The
+                    // seat is played for them until the game hears about it
+                    if let Err(PlayerGameInfo {
+                        id,
+                        info,
+                        channels: (senders, messages),
+                        ..
+                    }) = sender.send(channel)
+                    {
+                        tracing::debug!(game_id = %game_id, player = ?id, "Player left before the game started");
+                        tokio::spawn(bot::autopilot(id, Arc::new(senders), messages, info));
+                    }
+                }
+                Seat::Bot(difficulty) => {
+                    tokio::spawn(bot::play(*difficulty, channel));
+                }
+            }
+        }
+
+        // start the game task to run in the background
+        tracing::trace!(game_id = %game_id, "Starting coup game task with {} players", game_half.len());
+        let game = tokio::spawn(coup_game(
+            game_half,
+            names,
+            self.broadcaster.clone(),
+            reveal,
+            self.settings,
+            Steering {
+                left,
+                paused: self.paused.subscribe(),
+            },
+            self.view.clone(),
+        ));
+        self.handle = Some(game.abort_handle());
+        tokio::spawn(watch_for_failure(game_id, game, failed.clone()));
+    }
+}
+
+// sends game_id to failed if the game task ends without the game being over (or panics), so it
+// can be cancelled. Games that are aborted were cancelled already
+async fn watch_for_failure(
+    game_id: Uuid,
+    game: JoinHandle<Result<Summary, PlayerCommunicationError>>,
+    failed: Sender<Uuid>,
+) {
+    match game.await {
+        Ok(Ok(..)) => {}
+        Err(e) if e.is_cancelled() => {}
+        Ok(Err(..)) | Err(..) => {
+            tracing::warn!(game_id = %game_id, "Game task failed");
+            // the dispatcher outlives every game
+            let _ = failed.send(game_id).await;
+        }
+    }
+}
+
+fn generate_channels(len: usize, settings: LobbySettings) -> Channels {
     PlayerId::iter()
         .take(len)
         .map(|id| {
@@ -77,6 +567,8 @@ fn generate_channels(
             let (choose_two_tx, choose_two_rx) = mpsc::channel(1);
             let (info_tx, info_rx) = mpsc::channel(1);
             let (pass_tx, pass_rx) = mpsc::channel(1);
+            let (undo_tx, undo_rx) = mpsc::channel(1);
+            let (allow_undo_tx, allow_undo_rx) = mpsc::channel(1);
 
             let senders = ClientChannels {
                 action: action_tx,
@@ -86,6 +578,8 @@ fn generate_channels(
                 choose_one: choose_one_tx,
                 choose_two: choose_two_tx,
                 pass: pass_tx,
+                undo: undo_tx,
+                allow_undo: allow_undo_tx,
             };
 
             let receivers = GameChannels {
@@ -97,13 +591,15 @@ fn generate_channels(
                 choose_two: choose_two_rx,
                 info: info_tx,
                 pass: pass_rx,
+                undo: undo_rx,
+                allow_undo: allow_undo_rx,
             };
 
             let player_half = PlayerGameInfo {
                 id,
-                broadcast_receiver: broadcaster.subscribe(),
                 channels: (senders, player_rx),
                 info: info_rx,
+                undo: settings.undo,
             };
             let game_half = (id, (game_tx, receivers));
             (player_half, game_half)
@@ -111,87 +607,723 @@ fn generate_channels(
         .collect()
 }
 
-// finds a lobby to assign player to
-async fn assign_to_lobby(
+fn generate_code(lobbies: &HashMap<Uuid, GameInfo>) -> String {
+    let mut rng = rand::thread_rng();
+    loop {
+        let code: String = (0..CODE_LEN)
+            .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+            .collect();
+
+        if lobbies.values().all(|lobby| lobby.code != code) {
+            break code;
+        }
+    }
+}
+
+// creates a new lobby with settings, returning its id
+fn create_lobby(lobbies: &mut HashMap<Uuid, GameInfo>, settings: LobbySettings) -> Uuid {
+    let uuid = Uuid::now_v7();
+    let code = generate_code(lobbies);
+    tracing::debug!(game_id = %uuid, code, "Creating lobby");
+    lobbies.insert(
+        uuid,
+        GameInfo {
+            code,
+            members: Vec::new(),
+            // every response in a reaction window is broadcast (along with every claim), so there
+            // can be a few in flight
+            broadcaster: Arc::new(broadcast::channel(32).0),
+            // game hasn't started yet
+            handle: None,
+            secret: Secret::random(),
+            entropy: Vec::new(),
+            reveal: None,
+            settings: settings.clamped(),
+            leaves: None,
+            fill_at: None,
+            view: Arc::new(watch::Sender::new(None)),
+            spectators: Vec::new(),
+            created: Timestamp::now(),
+            paused: watch::Sender::new(false),
+        },
+    );
+
+    uuid
+}
+
+// creates a lobby that fills its empty seats with bots and starts after a wait, returning its id.
+// These always use the default settings, since players don't get to choose which one they are in
+fn create_quick_match(lobbies: &mut HashMap<Uuid, GameInfo>, fills: &Sender<Uuid>) -> Uuid {
+    let game_id = create_lobby(lobbies, LobbySettings::default());
+    let lobby = lobbies.get_mut(&game_id).expect("Guaranteed to exist");
+    lobby.fill_at = Some(Timestamp::now() + QUICK_MATCH_WAIT);
+
+    let fills = fills.clone();
+    tokio::spawn(async move {
+        sleep(QUICK_MATCH_WAIT).await;
+        // the dispatcher outlives every lobby
+        let _ = fills.send(game_id).await;
+    });
+
+    game_id
+}
+
+// finds a quick match that is still waiting for players, returning its id
+fn find_quick_match(lobbies: &HashMap<Uuid, GameInfo>) -> Option<Uuid> {
+    lobbies
+        .iter()
+        .find(|(_, lobby)| {
+            lobby.handle.is_none()
+                && lobby.fill_at.is_some()
+                && lobby.members.len() < QUICK_MATCH_SEATS
+        })
+        .map(|(game_id, _)| *game_id)
+}
+
+// finds the lobby with code that hasn't started its game, returning its id
+fn find_lobby(lobbies: &HashMap<Uuid, GameInfo>, code: &str) -> Result<Uuid, ClientError> {
+    let (game_id, lobby) = lobbies
+        .iter()
+        .find(|(_, lobby)| lobby.handle.is_none() && lobby.code.eq_ignore_ascii_case(code))
+        .ok_or(ClientError::LobbyNotFound)?;
+
+    if lobby.members.len() >= MAX_PLAYERS {
+        return Err(ClientError::LobbyFull);
+    }
+
+    Ok(*game_id)
+}
+
+// puts a player in the lobby they asked for, returning what they need to take part in it
+fn join_lobby(
     lobbies: &mut HashMap<Uuid, GameInfo>,
-    sender: oneshot::Sender<PlayerGameInfo>,
-) -> Uuid {
-    // find lobbies that haven't started their game and have space
-    let lobby = lobbies
-        .iter_mut()
-        .find(|(_, info)| info.handle.is_none() && info.channel_senders.len() < 6);
-
-    if let Some((game_id, info)) = lobby {
-        info.channel_senders.push(sender);
-        *game_id
-    } else {
-        // generate new lobby instead
-        let uuid = Uuid::now_v7();
-        lobbies.insert(
-            uuid,
-            GameInfo {
-                channel_senders: vec![sender],
-                broadcaster: Arc::new(broadcast::channel(2).0),
-                // game hasn't started yet
-                handle: None,
-            },
-        );
+    addr: SocketAddr,
+    settings: LobbySettings,
+    join: &Join,
+    game: oneshot::Sender<PlayerGameInfo>,
+    fills: &Sender<Uuid>,
+) -> Result<Joined, ClientError> {
+    let code = join
+        .code
+        .as_deref()
+        .map(str::trim)
+        .filter(|code| !code.is_empty());
+    let game_id = match code {
+        Some(code) => find_lobby(lobbies, code)?,
+        None if join.quick => {
+            find_quick_match(lobbies).unwrap_or_else(|| create_quick_match(lobbies, fills))
+        }
+        None => create_lobby(lobbies, settings),
+    };
 
-        uuid
+    let lobby = lobbies.get_mut(&game_id).expect("Guaranteed to exist");
+    let member = Uuid::now_v7();
+    // unlike member ids, tokens are never shown to anyone else, so they have to be unguessable
+    let token = uuid::Builder::from_random_bytes(rand::random()).into_uuid();
+    let (reattach, reconnections) = mpsc::channel(1);
+    let (kick, kicks) = mpsc::channel(1);
+    lobby.members.push(LobbyMember {
+        id: member,
+        name: join.name(),
+        ready: false,
+        left: false,
+        connected: true,
+        seat: Seat::Player {
+            addr,
+            sender: Some(game),
+            token,
+            reattach,
+            kick,
+        },
+    });
+
+    Ok(Joined {
+        game_id,
+        member,
+        commitment: lobby.secret.commitment(),
+        broadcasts: lobby.broadcaster.subscribe(),
+        token,
+        reconnections,
+        kicks,
+    })
+}
+
+// finds where to hand over a connection taking back the seat with token, if its game has started
+fn find_seat(lobbies: &HashMap<Uuid, GameInfo>, token: Uuid) -> Option<Sender<WebSocket>> {
+    lobbies
+        .values()
+        .filter(|lobby| lobby.handle.is_some())
+        .flat_map(|lobby| &lobby.members)
+        .find_map(|member| match &member.seat {
+            Seat::Player {
+                token: seat,
+                reattach,
+                ..
+            } if *seat == token => Some(reattach.clone()),
+            _ => None,
+        })
+}
+
+// lets a spectator watch the game with game_id, if it has started. Every hand is only shown once
+// the game is over, unless the lobby allows it
+fn watch_game(
+    lobbies: &mut HashMap<Uuid, GameInfo>,
+    addr: SocketAddr,
+    game_id: Uuid,
+    omniscient: bool,
+) -> Result<Watching, ClientError> {
+    let game = lobbies
+        .get_mut(&game_id)
+        .filter(|game| game.handle.is_some())
+        .ok_or(ClientError::GameNotFound)?;
+
+    let over = game
+        .view
+        .borrow()
+        .as_ref()
+        .is_some_and(|view| view.end.is_some());
+    if omniscient && !game.settings.omniscient && !over {
+        return Err(ClientError::NotOmniscient);
     }
+
+    let (kick, kicks) = mpsc::channel(1);
+    game.spectators.push((addr, kick));
+    tracing::debug!(addr = %addr, game_id = %game_id, spectators = game.spectators.len(), "Spectator started watching");
+    Ok(Watching {
+        broadcasts: game.broadcaster.subscribe(),
+        view: game.view.subscribe(),
+        delay: game.settings.spectator_delay,
+        chat: game.settings.spectator_chat,
+        kicks,
+    })
 }
 
-#[instrument(skip(task_receiver, disconnected))]
-pub async fn dispatcher(mut task_receiver: TaskReceiver, mut disconnected: Receiver<Disconnected>) {
+// ends the game with game_id (or closes its lobby) for everyone in it, returning whether there was
+// one to end
+fn cancel(lobbies: &mut HashMap<Uuid, GameInfo>, game_id: Uuid) -> bool {
+    // clean up should only happen once. The game is archived by its recording, which is done by now
+    // if the game finished
+    let Some(finished_game) = lobbies.remove(&game_id) else {
+        return false;
+    };
+
+    // nobody might be listening anymore. Games that started are revealed, so they can still be
+    // checked
+    let _ = finished_game
+        .broadcaster
+        .send(BroadcastMessage::GameCancelled(
+            finished_game.reveal.clone(),
+        ));
+
+    // abort game to make sure it doesn't keep waiting to progress
+    if let Some(handle) = &finished_game.handle {
+        tracing::trace!(game_id = %game_id, "Aborting game task");
+        handle.abort();
+    }
+    true
+}
+
+// answers the server's operator, who can see into (and step in on) every lobby and game
+fn handle_admin(lobbies: &mut HashMap<Uuid, GameInfo>, request: AdminRequest) {
+    match request {
+        AdminRequest::Games(reply) => {
+            let games = lobbies
+                .iter()
+                .map(|(game_id, game)| game.overview(*game_id))
+                .collect();
+            let _ = reply.send(games);
+        }
+        AdminRequest::Game(game_id, reply) => {
+            let _ = reply.send(lobbies.get(&game_id).map(|game| game.details(game_id)));
+        }
+        AdminRequest::Cancel(game_id, reply) => {
+            tracing::info!(game_id = %game_id, "Operator cancelled game");
+            let _ = reply.send(cancel(lobbies, game_id));
+        }
+        AdminRequest::Pause(game_id, paused, reply) => {
+            // only games that have started can be paused
+            let Some(game) = lobbies.get(&game_id).filter(|game| game.handle.is_some()) else {
+                let _ = reply.send(false);
+                return;
+            };
+
+            tracing::info!(game_id = %game_id, paused, "Operator paused or resumed game");
+            if game.paused.send_replace(paused) != paused {
+                let _ = game.broadcaster.send(BroadcastMessage::Paused(paused));
+            }
+            let _ = reply.send(true);
+        }
+        AdminRequest::Kick(addr, reply) => {
+            // a connection can be seated in one game while watching another
+            let kicked = lobbies
+                .values()
+                .fold(false, |kicked, game| game.kick(addr) | kicked);
+            tracing::info!(addr = %addr, kicked, "Operator kicked connection");
+            let _ = reply.send(kicked);
+        }
+    }
+}
+
+// everything connections ask of the dispatcher
+#[derive(Debug)]
+pub struct Inbox {
+    pub registrations: TaskReceiver,
+    pub disconnected: Receiver<Disconnected>,
+    pub entropy: Receiver<Entropy>,
+    pub lobby_requests: Receiver<LobbyRequest>,
+    pub resumes: Receiver<Resume>,
+    pub connections: Receiver<Connection>,
+    pub spectates: Receiver<Spectate>,
+    pub chats: Receiver<Chat>,
+    pub admin: Receiver<AdminRequest>,
+}
+
+#[instrument(skip_all)]
+pub async fn dispatcher(inbox: Inbox, archive: Arc<Archive>) {
+    let Inbox {
+        registrations: mut task_receiver,
+        mut disconnected,
+        mut entropy,
+        mut lobby_requests,
+        mut resumes,
+        mut connections,
+        mut spectates,
+        mut chats,
+        mut admin,
+    } = inbox;
     // mapping to each of the lobbies/games
     let mut lobbies: HashMap<Uuid, GameInfo> = HashMap::new();
-    let mut finished_games: HashMap<Uuid, GameInfo> = HashMap::new();
+    // quick matches that have waited long enough for players
+    let (fills_sender, mut fills) = mpsc::channel(16);
+    // games that are over and archived, which can be let go of
+    let (archived_sender, mut archived) = mpsc::channel(16);
+    // games whose task failed, which are cancelled so everyone hears about it and they are archived
+    let (failed_sender, mut failed) = mpsc::channel(16);
     loop {
         select! {
-            Some((info_sender, game_id_sender)) = task_receiver.recv() => {
-                // assign incoming player to a lobby
-                let game_id = assign_to_lobby(&mut lobbies, info_sender).await;
-                game_id_sender.send(game_id).expect("Receiver should never be dropped");
+            Some(Registration { addr, settings, join, game, joined }) = task_receiver.recv() => {
+                let result = join_lobby(&mut lobbies, addr, settings, &join, game, &fills_sender);
+                let game_id = result.as_ref().map(|joined| joined.game_id).ok();
+                joined.send(result).expect("Receiver should never be dropped");
 
-                let game = lobbies.get_mut(&game_id).expect("Guaranteed to exist");
+                // let everyone know who joined
+                let Some(game_id) = game_id else { continue };
+                let Some(lobby) = lobbies.get_mut(&game_id) else { continue };
+                lobby.broadcast_state();
 
-                // check if lobby player was assigned to is ready to start
-                if game.channel_senders.len() >= 2 {
-                    tracing::debug!(game_id = %game_id, "Sufficient players joined, starting game");
-                    let connections = mem::take(&mut game.channel_senders);
+                // quick matches start as soon as they are full
+                if lobby.fill_at.is_some() && lobby.members.len() >= QUICK_MATCH_SEATS {
+                    tracing::debug!(game_id = %game_id, "Quick match is full");
+                    lobby.launch(game_id, &archive, &archived_sender, &failed_sender);
+                }
+            },
+            Some(game_id) = fills.recv() => {
+                // everyone may have left, or the game started already
+                let Some(lobby) = lobbies.get_mut(&game_id).filter(|lobby| lobby.handle.is_none()) else { continue };
 
-                    let (player_half, game_half) = generate_channels(connections.len(), game.broadcaster.clone());
+                tracing::debug!(game_id = %game_id, "Filling quick match with bots");
+                while lobby.members.len() < QUICK_MATCH_SEATS {
+                    lobby.seat_bot(QUICK_MATCH_DIFFICULTY);
+                }
+                lobby.broadcast_state();
+                lobby.launch(game_id, &archive, &archived_sender, &failed_sender);
+            },
+            Some(game_id) = archived.recv() => {
+                // cancelled games are already gone
+                if lobbies.remove(&game_id).is_some() {
+                    tracing::debug!(game_id = %game_id, "Game is archived, letting go of it");
+                }
+            },
+            Some(game_id) = failed.recv() => {
+                tracing::debug!(game_id = %game_id, "Cancelling failed game");
+                cancel(&mut lobbies, game_id);
+            },
+            Some(LobbyRequest { addr, game_id, action }) = lobby_requests.recv() => {
+                // requests are only taken until the game starts
+                let lobby = lobbies.get_mut(&game_id).filter(|lobby| lobby.handle.is_none());
 
-                    // start the game task to run in the background
-                    tracing::trace!(game_id = %game_id, "Starting coup game task with {} players", game_half.len());
-                    game.handle = Some(tokio::spawn(coup_game(game_half, game.broadcaster.clone())));
+                match (lobby, action) {
+                    (Some(lobby), LobbyAction::Ready(ready)) => {
+                        let Some(member) = lobby.members.iter_mut().find(|member| member.is(addr)) else { continue };
+                        member.ready = ready;
+                        lobby.broadcast_state();
+                    }
+                    (Some(lobby), LobbyAction::Start(reply)) => {
+                        let _ = reply.send(lobby.start(addr, game_id, &archive, &archived_sender, &failed_sender));
+                    }
+                    (Some(lobby), LobbyAction::AddBot(difficulty, reply)) => {
+                        let result = lobby.add_bot(addr, difficulty);
+                        if result.is_ok() {
+                            lobby.broadcast_state();
+                        }
+                        let _ = reply.send(result);
+                    }
+                    (Some(lobby), LobbyAction::RemoveBot(member, reply)) => {
+                        let result = lobby.remove_bot(addr, member);
+                        if result.is_ok() {
+                            lobby.broadcast_state();
+                        }
+                        let _ = reply.send(result);
+                    }
+                    (None, LobbyAction::Start(reply) | LobbyAction::AddBot(_, reply) | LobbyAction::RemoveBot(_, reply)) => {
+                        let _ = reply.send(Err(ClientError::LobbyNotFound));
+                    }
+                    (None, LobbyAction::Ready(..)) => {}
+                }
+            },
+            Some(Resume { token, reply }) = resumes.recv() => {
+                let _ = reply.send(find_seat(&lobbies, token));
+            },
+            Some(Spectate { addr, game_id, omniscient, reply }) = spectates.recv() => {
+                let _ = reply.send(watch_game(&mut lobbies, addr, game_id, omniscient));
+            },
+            Some(Connection { game_id, player, given_up }) = connections.recv() => {
+                let Some(game) = lobbies.get_mut(&game_id) else { continue };
+                let index = PlayerId::iter().position(|id| id == player).expect("Every player has a seat");
+                if let Some(member) = game.members.get_mut(index) {
+                    member.connected = given_up.is_none();
+                }
 
-                    // send back the player task's half of the channel, so it can communicate
-                    // with the coup game task
-                    tracing::trace!(game_id = %game_id, "Sending players their channels");
-                    for (sender, channel) in connections.into_iter().zip(player_half) {
-                        sender.send(channel).unwrap();
+                let message = match given_up {
+                    Some(given_up) => {
+                        tracing::debug!(game_id = %game_id, player = ?player, "Player lost their connection");
+                        BroadcastMessage::Disconnected(player, given_up)
+                    }
+                    None => {
+                        tracing::debug!(game_id = %game_id, player = ?player, "Player reconnected");
+                        BroadcastMessage::Reconnected(player)
                     }
+                };
+                let _ = game.broadcaster.send(message);
+            },
+            Some(Chat { game_id, member, text }) = chats.recv() => {
+                // chat goes on from the lobby until the game is over
+                let Some(game) = lobbies.get(&game_id) else { continue };
+                let Some(sender) = game.members.iter().find(|sender| sender.id == member) else { continue };
+
+                tracing::trace!(game_id = %game_id, member = %member, "Passing on chat");
+                let chat = ChatMessage {
+                    sender: member,
+                    name: sender.name.clone(),
+                    sent: Timestamp::now(),
+                    text,
+                };
+                // nobody might be listening
+                let _ = game.broadcaster.send(BroadcastMessage::Chat(chat));
+            },
+            Some(Entropy { addr, game_id, entropy }) = entropy.recv() => {
+                // entropy is only taken until the game starts
+                let Some(game) = lobbies.get_mut(&game_id).filter(|game| game.handle.is_none()) else { continue };
+
+                tracing::trace!(addr = %addr, game_id = %game_id, "Received entropy for shuffle seed");
+                match game.entropy.iter_mut().find(|(sender, _)| *sender == addr) {
+                    Some((_, old)) => *old = entropy,
+                    None => game.entropy.push((addr, entropy)),
                 }
             },
             Some(Disconnected { addr, game_id }) = disconnected.recv() => {
-                tracing::error!(addr = %addr, game_id = %game_id, "Received player disconnect on dispatcher, ending game");
-                // clean up should only happen once
-                let Some(finished_game) = lobbies.remove(&game_id) else { continue };
+                // spectators come and go without anyone noticing
+                if let Some(game) = lobbies.get_mut(&game_id)
+                    && let Some(index) = game.spectators.iter().position(|(spectator, _)| *spectator == addr)
+                {
+                    game.spectators.swap_remove(index);
+                    tracing::debug!(addr = %addr, game_id = %game_id, spectators = game.spectators.len(), "Spectator stopped watching");
+                    continue;
+                }
 
-                if finished_game.broadcaster.send(BroadcastMessage::GameCancelled).is_err() {
-                    tracing::error!(culprit = %addr, game_id = %game_id, "No players left connected");
+                // players can leave lobbies freely until the game starts, passing on being host
+                // to whoever joined after them
+                if let Some(lobby) = lobbies.get_mut(&game_id).filter(|lobby| lobby.handle.is_none()) {
+                    tracing::debug!(addr = %addr, game_id = %game_id, "Player left lobby");
+                    lobby.members.retain(|member| !member.is(addr));
+                    lobby.entropy.retain(|(sender, _)| *sender != addr);
+
+                    // bots don't keep a lobby open by themselves
+                    if !lobby.members.iter().any(LobbyMember::is_player) {
+                        lobbies.remove(&game_id);
+                    } else {
+                        lobby.broadcast_state();
+                    }
+                    continue;
                 }
 
-                // abort game to make sure it doesn't keep waiting to progress
-                if let Some(handle) = &finished_game.handle {
-                    tracing::trace!(culprit = %addr, game_id = %game_id, "Aborting game task");
-                    handle.abort();
+                // otherwise the game carries on without them, unless nobody is left to play it
+                let Some(game) = lobbies.get_mut(&game_id) else { continue };
+                let Some(index) = game.members.iter().position(|member| member.is(addr)) else { continue };
+                let player = PlayerId::iter().nth(index).expect("Every member has a seat");
+                tracing::debug!(addr = %addr, game_id = %game_id, player = ?player, "Player left game");
+
+                game.members[index].left = true;
+                let _ = game.broadcaster.send(BroadcastMessage::Left(player));
+                if let Some(leaves) = &game.leaves {
+                    // the game may already be over
+                    let _ = leaves.try_send(player);
+                }
+                if game.members.iter().any(|member| member.is_player() && !member.left) {
+                    continue;
                 }
 
-                // add to finished games map
-                finished_games.insert(game_id, finished_game);
+                tracing::debug!(game_id = %game_id, "Every player left, ending game");
+                cancel(&mut lobbies, game_id);
             }
+            Some(request) = admin.recv() => handle_admin(&mut lobbies, request),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use overthrow_types::GameResult;
+    use std::future;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn join(code: Option<&str>, name: Option<&str>) -> Join {
+        Join {
+            code: code.map(str::to_owned),
+            name: name.map(str::to_owned),
+            ..Join::default()
+        }
+    }
+
+    // joins the lobby with code (or creates one), as a player at port
+    fn join_as(
+        lobbies: &mut HashMap<Uuid, GameInfo>,
+        port: u16,
+        code: Option<&str>,
+    ) -> Result<Joined, ClientError> {
+        let (fills, _) = mpsc::channel(1);
+        let (game, _) = oneshot::channel();
+        join_lobby(
+            lobbies,
+            addr(port),
+            LobbySettings::default(),
+            &join(code, None),
+            game,
+            &fills,
+        )
+    }
+
+    // stands in for a game task, which never finishes
+    fn start(lobbies: &mut HashMap<Uuid, GameInfo>, game_id: Uuid) {
+        lobbies.get_mut(&game_id).unwrap().handle =
+            Some(tokio::spawn(future::pending::<()>()).abort_handle());
+    }
+
+    #[test]
+    fn settings_are_clamped() {
+        let settings = LobbySettings {
+            reaction_time: 0,
+            action_time: u32::MAX,
+            choice_time: 30,
+            spectator_delay: u32::MAX,
+            undo: true,
+            ..LobbySettings::default()
+        }
+        .clamped();
+
+        assert_eq!(settings.reaction_time, MIN_TIME);
+        assert_eq!(settings.action_time, MAX_TIME);
+        assert_eq!(settings.choice_time, 30);
+        assert_eq!(settings.spectator_delay, MAX_TIME);
+        assert!(settings.undo);
+        // no delay is allowed, unlike the timers
+        assert_eq!(LobbySettings::default().clamped().spectator_delay, 0);
+    }
+
+    #[test]
+    fn names_are_trimmed() {
+        assert_eq!(join(None, None).name(), "Anonymous");
+        assert_eq!(join(None, Some("   ")).name(), "Anonymous");
+        assert_eq!(join(None, Some("  Dave ")).name(), "Dave");
+
+        let long = "é".repeat(MAX_NAME_LEN + 10);
+        let name = join(None, Some(&long)).name();
+        assert_eq!(name.chars().count(), MAX_NAME_LEN);
+    }
+
+    #[test]
+    fn lobbies_are_found_by_code() {
+        let mut lobbies = HashMap::new();
+        let game_id = create_lobby(&mut lobbies, LobbySettings::default());
+        let code = lobbies[&game_id].code.clone();
+
+        assert_eq!(find_lobby(&lobbies, &code).unwrap(), game_id);
+        assert_eq!(
+            find_lobby(&lobbies, &code.to_ascii_lowercase()).unwrap(),
+            game_id
+        );
+        assert!(matches!(
+            find_lobby(&lobbies, "NOPE!"),
+            Err(ClientError::LobbyNotFound)
+        ));
+    }
+
+    #[test]
+    fn full_lobbies_cant_be_joined() {
+        let mut lobbies = HashMap::new();
+        let host = join_as(&mut lobbies, 1, None).unwrap();
+        let code = lobbies[&host.game_id].code.clone();
+
+        for port in 2..=MAX_PLAYERS as u16 {
+            let joined = join_as(&mut lobbies, port, Some(&code)).unwrap();
+            assert_eq!(joined.game_id, host.game_id);
+        }
+        assert_eq!(lobbies[&host.game_id].members.len(), MAX_PLAYERS);
+
+        assert!(matches!(
+            join_as(&mut lobbies, 100, Some(&code)),
+            Err(ClientError::LobbyFull)
+        ));
+        assert_eq!(lobbies[&host.game_id].members.len(), MAX_PLAYERS);
+    }
+
+    #[tokio::test]
+    async fn started_games_cant_be_joined() {
+        let mut lobbies = HashMap::new();
+        let host = join_as(&mut lobbies, 1, None).unwrap();
+        let code = lobbies[&host.game_id].code.clone();
+        start(&mut lobbies, host.game_id);
+
+        assert!(matches!(
+            join_as(&mut lobbies, 2, Some(&code)),
+            Err(ClientError::LobbyNotFound)
+        ));
+        assert!(matches!(
+            join_as(&mut lobbies, 2, Some("NOPE!")),
+            Err(ClientError::LobbyNotFound)
+        ));
+        // the host is the only one in there
+        assert_eq!(lobbies[&host.game_id].members.len(), 1);
+    }
+
+    #[test]
+    fn blank_codes_create_lobbies() {
+        let mut lobbies = HashMap::new();
+        let first = join_as(&mut lobbies, 1, Some("  ")).unwrap();
+        let second = join_as(&mut lobbies, 2, None).unwrap();
+
+        assert_ne!(first.game_id, second.game_id);
+        assert_eq!(lobbies.len(), 2);
+        assert_eq!(lobbies[&first.game_id].members[0].name, "Anonymous");
+    }
+
+    #[tokio::test]
+    async fn only_started_games_can_be_watched() {
+        let mut lobbies = HashMap::new();
+        let host = join_as(&mut lobbies, 1, None).unwrap();
+
+        assert!(matches!(
+            watch_game(&mut lobbies, addr(2), host.game_id, false),
+            Err(ClientError::GameNotFound)
+        ));
+        assert!(matches!(
+            watch_game(&mut lobbies, addr(2), Uuid::now_v7(), false),
+            Err(ClientError::GameNotFound)
+        ));
+
+        start(&mut lobbies, host.game_id);
+        assert!(watch_game(&mut lobbies, addr(2), host.game_id, false).is_ok());
+        assert_eq!(lobbies[&host.game_id].spectators.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn every_hand_is_only_shown_when_allowed() {
+        let mut lobbies = HashMap::new();
+        let hidden = create_lobby(&mut lobbies, LobbySettings::default());
+        let shown = create_lobby(
+            &mut lobbies,
+            LobbySettings {
+                omniscient: true,
+                ..LobbySettings::default()
+            },
+        );
+        start(&mut lobbies, hidden);
+        start(&mut lobbies, shown);
+
+        assert!(matches!(
+            watch_game(&mut lobbies, addr(1), hidden, true),
+            Err(ClientError::NotOmniscient)
+        ));
+        assert!(watch_game(&mut lobbies, addr(1), shown, true).is_ok());
+
+        // until the game is over
+        let info = Info {
+            player_views: HashMap::new(),
+            current_player: PlayerId::One,
+            coins_remaining: 0,
+        };
+        lobbies[&hidden].view.send_replace(Some(View {
+            public: info.clone(),
+            omniscient: info,
+            end: Some((
+                Summary {
+                    winner: PlayerId::One,
+                },
+                Reveal::new(Secret::random(), Vec::new()),
+            )),
+        }));
+        assert!(watch_game(&mut lobbies, addr(1), hidden, true).is_ok());
+    }
+
+    #[tokio::test]
+    async fn failed_games_are_cancelled_and_archived() {
+        let mut lobbies = HashMap::new();
+        let host = join_as(&mut lobbies, 1, None).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Arc::new(Archive::open(dir.path().to_owned(), None).await.unwrap());
+
+        let lobby = lobbies.get_mut(&host.game_id).unwrap();
+        let reveal = Reveal::new(lobby.secret, Vec::new());
+        lobby.reveal = Some(reveal.clone());
+        let recording = Recording {
+            id: host.game_id,
+            players: vec!["Dave".to_owned()],
+            reveal,
+            created: lobby.created,
+            started: Timestamp::now(),
+        };
+        let record = tokio::spawn(archive::record(
+            archive.clone(),
+            recording,
+            lobby.broadcaster.subscribe(),
+            lobby.view.subscribe(),
+        ));
+        let game = tokio::spawn(async { Err(PlayerCommunicationError) });
+        lobby.handle = Some(game.abort_handle());
+
+        let (failed_sender, mut failed) = mpsc::channel(1);
+        watch_for_failure(host.game_id, game, failed_sender).await;
+        assert_eq!(failed.recv().await, Some(host.game_id));
+        assert!(cancel(&mut lobbies, host.game_id));
+
+        record.await.unwrap();
+        let game = archive.get(host.game_id).await.unwrap();
+        assert!(matches!(game.result, GameResult::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn only_failed_games_are_reported() {
+        let (failed_sender, mut failed) = mpsc::channel(3);
+        let finished = tokio::spawn(async {
+            Ok(Summary {
+                winner: PlayerId::One,
+            })
+        });
+        watch_for_failure(Uuid::now_v7(), finished, failed_sender.clone()).await;
+
+        let aborted = tokio::spawn(future::pending());
+        aborted.abort();
+        watch_for_failure(Uuid::now_v7(), aborted, failed_sender.clone()).await;
+
+        let game_id = Uuid::now_v7();
+        let panicked = tokio::spawn(async { panic!("game task panicked") });
+        watch_for_failure(game_id, panicked, failed_sender).await;
+
+        assert_eq!(failed.recv().await, Some(game_id));
+        assert!(failed.recv().await.is_none());
+    }
+}
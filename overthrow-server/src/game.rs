@@ -1,62 +1,131 @@
-use crate::dispatcher::PlayerHalf;
-use overthrow_types::{Info, PlayerView};
+use crate::dispatcher::{LobbySettings, PlayerHalf};
+use overthrow_types::{ChatMessage, Info, Leave, LobbyState};
 use tokio::select;
 use tokio::sync::mpsc::Receiver;
 
 use super::dispatcher::GameHalf;
-use futures::future::{join_all, select_all};
-use overthrow_engine::action::{Action, Block, Blocks, Challenge, Reaction};
-use overthrow_engine::deck::{Card, Hand};
+use futures::StreamExt;
+use futures::stream::FuturesUnordered;
+use jiff::Timestamp;
+use overthrow_engine::GameBuilder;
+use overthrow_engine::action::{Action, Block, Blocks, Challenge, Reaction, ReactionStage};
+use overthrow_engine::commitment::Reveal;
+use overthrow_engine::deck::Card;
+use overthrow_engine::driver::Phase;
 use overthrow_engine::machine::{
     ActionKind, BlockState, ChallengeState, ChooseOneFromThree, ChooseOneFromThreeState,
     ChooseTwoFromFour, ChooseTwoFromFourState, ChooseVictimCard, ChooseVictimCardState, CoupGame,
-    EndState, GameState as CoupGameState, OnlyBlockable, OnlyBlockableState, OnlyChallengeable,
-    OnlyChallengeableState, Outcome, Reactable, ReactableState, Safe, SafeState, Summary, Wait,
-    WaitState,
+    EndState, GameInfo, GameState as CoupGameState, OnlyBlockable, OnlyBlockableState,
+    OnlyChallengeable, OnlyChallengeableState, Outcome, Reactable, ReactableState, Safe, SafeState,
+    Summary, Wait, WaitState,
 };
-use overthrow_engine::player_map::PlayerMap;
+use overthrow_engine::observer::Observer;
+use overthrow_engine::player_map::ChallengeResult;
 use overthrow_engine::players::PlayerId;
+use overthrow_engine::priority::ReactionWindow;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{
     broadcast::{self, error::SendError as BroadcastError},
     mpsc::error::SendError as MpscError,
+    watch,
 };
 use tracing::{instrument, trace};
 
 #[derive(Debug)]
 pub struct Pass;
 
+// the current player wants to take back the last turn
+#[derive(Debug)]
+pub struct UndoRequest;
+
+// turns that can be taken back in lobbies that allow it
+const UNDO_LIMIT: usize = 10;
+
+// deadlines are set here rather than by each client handler, so every player is given the same one
+fn deadline(seconds: u32) -> Timestamp {
+    Timestamp::now() + Duration::from_secs(seconds.into())
+}
+
 #[derive(Debug)]
 pub struct PlayerGameInfo {
     pub id: PlayerId,
-    pub broadcast_receiver: broadcast::Receiver<BroadcastMessage>,
+    // whether the current player can ask to take back the last turn
+    pub undo: bool,
     pub info: Receiver<Info>,
     pub channels: PlayerHalf,
 }
 
+// the game as spectators see it, kept around for anyone who starts watching partway through
+#[derive(Debug, Clone)]
+pub struct View {
+    pub public: Info,
+    pub omniscient: Info,
+    // set once the game is over
+    pub end: Option<(Summary, Reveal)>,
+}
+
+impl View {
+    fn new(info: &GameInfo) -> View {
+        View {
+            public: Info::spectator(info, false),
+            omniscient: Info::spectator(info, true),
+            end: None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Choices {
-    Actions(Vec<Action>),
-    Challenge(Challenge),
-    Block(Blocks),
-    Reactions(Vec<Reaction>),
+    Actions(Vec<Action>, Timestamp),
+    Challenge(Challenge, Timestamp),
+    Block(Blocks, Timestamp),
+    Reactions(Vec<Reaction>, ReactionStage, Timestamp),
 }
 
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum GameMessage {
     ChooseAction(Choices),
-    ChooseVictim([Card; 2]),
-    ChooseOneFromThree([Card; 3]),
-    ChooseTwoFromFour([Card; 4]),
+    ChooseVictim([Card; 2], Timestamp),
+    ChooseOneFromThree([Card; 3], Timestamp),
+    ChooseTwoFromFour([Card; 4], Timestamp),
+    // another player asked to take back the last turn
+    ConfirmUndo(PlayerId, Timestamp),
 }
 
 #[derive(Debug, Clone)]
 pub enum BroadcastMessage {
+    // only sent before the game starts
+    Lobby(LobbyState),
     Outcome(Outcome),
-    End(Summary),
-    GameCancelled,
+    Declared(Action),
+    Reacted(Reaction),
+    Chat(ChatMessage),
+    // the game is revealed along with how it ended, or along with being cancelled if it had started
+    End(Summary, Reveal),
+    GameCancelled(Option<Reveal>),
+    // whether the last turn was taken back after someone asked to
+    Undo(bool),
+    // players the game is waiting on, and when they will be passed for if there is a time limit
+    WaitingFor(Vec<PlayerId>, Option<Timestamp>),
+    // a player lost their connection, and when their seat is given up on
+    Disconnected(PlayerId, Timestamp),
+    Reconnected(PlayerId),
+    // a player didn't come back in time, so their seat is taken out of the game or played by a bot
+    Left(PlayerId),
+    // an operator stopped the game at the start of the next turn, or let it carry on
+    Paused(bool),
+}
+
+// what the dispatcher can tell a game while it is being played
+#[derive(Debug)]
+pub struct Steering {
+    // players who left for good
+    pub left: Receiver<PlayerId>,
+    // the game waits at the start of each turn for as long as this is set
+    pub paused: watch::Receiver<bool>,
 }
 
 #[derive(Debug, Clone)]
@@ -81,26 +150,111 @@ impl From<Choices> for GameMessage {
     }
 }
 
+// traces every transition of a game, including private ones like exchanges
+struct Log;
+
+impl Observer for Log {
+    fn action_declared(&mut self, action: &Action) {
+        tracing::debug!(actor = ?action.actor(), act = ?action.kind(), "Action declared");
+    }
+
+    fn reaction_submitted(&mut self, reaction: &Reaction) {
+        tracing::debug!(reaction = ?reaction, "Reaction submitted");
+    }
+
+    fn challenge_resolved(&mut self, challenge: &Challenge, result: ChallengeResult) {
+        tracing::debug!(challenge = ?challenge, result = ?result, "Challenge resolved");
+    }
+
+    fn influence_lost(&mut self, victim: PlayerId, card: Card) {
+        tracing::debug!(victim = ?victim, card = ?card, "Influence lost");
+    }
+
+    fn coins_moved(&mut self, from: Option<PlayerId>, to: Option<PlayerId>, amount: u8) {
+        tracing::trace!(from = ?from, to = ?to, amount, "Coins moved");
+    }
+
+    fn cards_exchanged(&mut self, actor: PlayerId, kept: &[Card], returned: &[Card]) {
+        tracing::trace!(actor = ?actor, kept = ?kept, returned = ?returned, "Cards exchanged");
+    }
+
+    fn turn_ended(&mut self, next: PlayerId) {
+        tracing::trace!(next = ?next, "Turn ended");
+    }
+
+    fn game_ended(&mut self, winner: PlayerId) {
+        tracing::debug!(winner = ?winner, "Game ended");
+    }
+}
+
+// passes on what players claim and how others react, which everyone at the table would see
+struct Announcer(Arc<broadcast::Sender<BroadcastMessage>>);
+
+impl Observer for Announcer {
+    // nobody might be listening
+    fn action_declared(&mut self, action: &Action) {
+        let _ = self.0.send(BroadcastMessage::Declared(action.clone()));
+    }
+
+    fn reaction_submitted(&mut self, reaction: &Reaction) {
+        let _ = self.0.send(BroadcastMessage::Reacted(reaction.clone()));
+    }
+}
+
 #[derive(Debug)]
 struct ChannelHandles<'a> {
     player_channels: &'a mut HashMap<PlayerId, GameHalf>,
     broadcaster: &'a broadcast::Sender<BroadcastMessage>,
+    settings: LobbySettings,
 }
 
 // HashMap will contain senders and receivers for the corresponding PlayerId (which will in turn be attended to by a specific task)
 // This function will run until the game is over, where it will send an end game message to all player tasks
-#[instrument(skip(player_channels))]
+#[instrument(skip(player_channels, reveal, steering, view))]
 pub async fn coup_game(
     mut player_channels: HashMap<PlayerId, GameHalf>,
+    names: Vec<String>,
     broadcaster: Arc<broadcast::Sender<BroadcastMessage>>,
+    reveal: Reveal,
+    settings: LobbySettings,
+    mut steering: Steering,
+    view: Arc<watch::Sender<Option<View>>>,
 ) -> Result<Summary> {
-    let mut game_state = CoupGameState::Wait(CoupGame::with_count(player_channels.len()));
+    let undo_limit = if settings.undo { UNDO_LIMIT } else { 0 };
+    let game = GameBuilder::new(names)
+        .seed(reveal.seed())
+        .observer(Log)
+        .observer(Announcer(broadcaster.clone()))
+        .undo_limit(undo_limit)
+        // challenges, then blocks, like at the table
+        .reaction_window(ReactionWindow::TwoStage)
+        .build();
+    let mut game_state = CoupGameState::Wait(game);
 
     loop {
         use CoupGameState as State;
+        // players who left for good are taken out between turns, unless bots play for them
+        if settings.leave == Leave::Resign
+            && let State::Wait(game) = game_state
+        {
+            game_state = resign_leavers(game, &mut steering.left, &mut player_channels);
+        }
+
+        // the turn in progress is always finished before pausing, so nobody's time runs out
+        // while the game is paused
+        if let State::Wait(..) = game_state
+            && *steering.paused.borrow()
+        {
+            tracing::debug!("Game paused");
+            // the dispatcher outlives every game
+            let _ = steering.paused.wait_for(|paused| !paused).await;
+            tracing::debug!("Game resumed");
+        }
+
         let handles = ChannelHandles {
             player_channels: &mut player_channels,
             broadcaster: &broadcaster,
+            settings,
         };
 
         // round has started, so we can broadcast the game info to all of the players
@@ -109,16 +263,10 @@ pub async fn coup_game(
 
             tracing::trace!(info = ?info, "Broadcasting game info to each player");
             for (id, _) in info.players.alive() {
-                let views = get_player_views_for(id, info.players);
                 let (_, channels) = &handles.player_channels[&id];
-                let info = Info {
-                    player_views: views,
-                    current_player: info.current_player,
-                    coins_remaining: info.coins_remaining,
-                };
-
-                channels.info.send(info).await?;
+                channels.info.send(Info::new(id, &info)).await?;
             }
+            view.send_replace(Some(View::new(&info)));
         }
 
         let next_game_state = match game_state {
@@ -127,10 +275,18 @@ pub async fn coup_game(
             State::ChooseOneFromThree(coup_game) => choose_one(coup_game, handles).await,
             State::ChooseTwoFromFour(coup_game) => choose_two(coup_game, handles).await,
             State::End(coup_game) => {
+                let mut last_view = View::new(&coup_game.info());
                 let summary = coup_game.summary();
                 tracing::debug!(winner = ?summary.winner, "Game finished successfully");
+                // spectators see how the game was left before being told it is over
+                last_view.end = Some((summary, reveal.clone()));
+                view.send_replace(Some(last_view));
                 // end game for all players
-                if broadcaster.send(BroadcastMessage::End(summary)).is_err() {
+                // the seed can be revealed now that the game is over
+                if broadcaster
+                    .send(BroadcastMessage::End(summary, reveal))
+                    .is_err()
+                {
                     tracing::error!(
                         "Failed to broadcast info to players (probably all disconnected)"
                     );
@@ -149,40 +305,27 @@ pub async fn coup_game(
     }
 }
 
-fn get_player_views_for(player_id: PlayerId, players: &PlayerMap) -> HashMap<PlayerId, PlayerView> {
-    let alive_views = players.alive().map(|(id, player)| {
-        let revealed_cards = match player.hand() {
-            Hand::Full(..) => Vec::new(),
-            Hand::Last { dead, .. } => vec![dead],
-        };
-
-        let view = if player_id == id {
-            PlayerView::Me {
-                name: player.name().to_owned(),
-                coins: player.coins().amount(),
-                hand: player.hand().clone(),
-            }
-        } else {
-            PlayerView::Other {
-                name: player.name().to_owned(),
-                coins: player.coins().amount(),
-                revealed_cards,
-            }
-        };
-
-        (id, view)
-    });
+// takes every player who left since the last turn out of the game, along with their seat
+fn resign_leavers(
+    mut game: CoupGame<Wait>,
+    left: &mut Receiver<PlayerId>,
+    player_channels: &mut HashMap<PlayerId, GameHalf>,
+) -> CoupGameState {
+    while let Ok(player) = left.try_recv() {
+        player_channels.remove(&player);
+        // players who were already out just stop being sent anything
+        if !game.info().players.alive().any(|(id, _)| id == player) {
+            continue;
+        }
 
-    let dead_views = players.dead().map(|(id, player)| {
-        let view = PlayerView::Other {
-            name: player.name().to_owned(),
-            coins: 0,
-            revealed_cards: player.revealed().into(),
-        };
-        (id, view)
-    });
+        tracing::debug!(player = ?player, "Player resigned");
+        match game.resign(player) {
+            CoupGameState::Wait(next) => game = next,
+            over => return over,
+        }
+    }
 
-    alive_views.chain(dead_views).collect()
+    CoupGameState::Wait(game)
 }
 
 #[instrument(skip_all)]
@@ -193,13 +336,20 @@ async fn choose_victim_card(
     let choices = game.choices();
     let victim = game.victim();
     tracing::debug!(victim = ?victim, choices = ?choices, "Choosing victim card");
+    let deadline = deadline(handles.settings.choice_time);
+    handles.broadcaster.send(BroadcastMessage::WaitingFor(
+        game.deciders(),
+        Some(deadline),
+    ))?;
 
     let (sender, receivers) = handles
         .player_channels
         .get_mut(&victim)
         .expect("Must exist");
 
-    sender.send(GameMessage::ChooseVictim(choices)).await?;
+    sender
+        .send(GameMessage::ChooseVictim(choices, deadline))
+        .await?;
 
     let choice = receivers
         .victim_card
@@ -218,11 +368,16 @@ async fn choose_one(
     let choices = game.choices();
     let actor = game.actor();
     tracing::debug!(actor = ?actor, choices = ?choices, "Exchanging one card from three");
+    let deadline = deadline(handles.settings.choice_time);
+    handles.broadcaster.send(BroadcastMessage::WaitingFor(
+        game.deciders(),
+        Some(deadline),
+    ))?;
 
     let (sender, receivers) = handles.player_channels.get_mut(&actor).expect("Must exist");
 
     sender
-        .send(GameMessage::ChooseOneFromThree(choices))
+        .send(GameMessage::ChooseOneFromThree(choices, deadline))
         .await?;
 
     let choice = receivers
@@ -242,10 +397,17 @@ async fn choose_two(
     let choices = game.choices();
     let actor = game.actor();
     tracing::debug!(actor = ?actor, choices = ?choices, "Exchanging two cards from four");
+    let deadline = deadline(handles.settings.choice_time);
+    handles.broadcaster.send(BroadcastMessage::WaitingFor(
+        game.deciders(),
+        Some(deadline),
+    ))?;
 
     let (sender, receivers) = handles.player_channels.get_mut(&actor).expect("Must exist");
 
-    sender.send(GameMessage::ChooseTwoFromFour(choices)).await?;
+    sender
+        .send(GameMessage::ChooseTwoFromFour(choices, deadline))
+        .await?;
 
     let chosen = receivers
         .choose_two
@@ -256,7 +418,10 @@ async fn choose_two(
     Ok(CoupGameState::Wait(game.advance(chosen)))
 }
 
-async fn handle_wait(game: CoupGame<Wait>, handles: ChannelHandles<'_>) -> Result<CoupGameState> {
+async fn handle_wait(
+    mut game: CoupGame<Wait>,
+    handles: ChannelHandles<'_>,
+) -> Result<CoupGameState> {
     let actions: Vec<Action> = game.actions().all().cloned().collect();
     let current_player = game.info().current_player;
 
@@ -266,15 +431,28 @@ async fn handle_wait(game: CoupGame<Wait>, handles: ChannelHandles<'_>) -> Resul
         .expect("Must exist");
 
     tracing::trace!(actions = ?actions, "Sending choices to client");
-    sender.send(Choices::Actions(actions).into()).await?;
-
-    let choice = receivers
-        .action
-        .recv()
-        .await
-        .ok_or(PlayerCommunicationError)?;
+    let deadline = deadline(handles.settings.action_time);
+    sender
+        .send(Choices::Actions(actions, deadline).into())
+        .await?;
+    handles.broadcaster.send(BroadcastMessage::WaitingFor(
+        game.deciders(),
+        Some(deadline),
+    ))?;
+
+    // requests only come through in lobbies that allow undo
+    let choice = select! {
+        choice = receivers.action.recv() => choice.ok_or(PlayerCommunicationError)?,
+        Some(UndoRequest) = receivers.undo.recv() => {
+            return request_undo(game, current_player, handles).await;
+        },
+    };
     tracing::trace!(chosen_action = ?choice, "Received choice");
 
+    // turns are only checkpointed once they are played, so taking one back goes to the start of
+    // the previous turn instead of back to the same choice
+    game.checkpoint();
+
     use ActionKind as A;
     match game.play(choice) {
         A::Safe(coup_game) => handle_safe(coup_game, handles.broadcaster).await,
@@ -284,6 +462,71 @@ async fn handle_wait(game: CoupGame<Wait>, handles: ChannelHandles<'_>) -> Resul
     }
 }
 
+// takes back the last turn if every other player agrees to, otherwise the current player chooses an
+// action again
+async fn request_undo(
+    game: CoupGame<Wait>,
+    requester: PlayerId,
+    ChannelHandles {
+        player_channels,
+        broadcaster,
+        settings,
+    }: ChannelHandles<'_>,
+) -> Result<CoupGameState> {
+    tracing::debug!(requester = ?requester, depth = game.undo_depth(), "Undo requested");
+    if game.undo_depth() == 0 {
+        broadcaster.send(BroadcastMessage::Undo(false))?;
+        return Ok(CoupGameState::Wait(game));
+    }
+
+    let deadline = deadline(settings.reaction_time);
+    let mut voters: Vec<_> = player_channels
+        .keys()
+        .copied()
+        .filter(|id| *id != requester)
+        .collect();
+    voters.sort();
+    for id in &voters {
+        let (sender, _) = &player_channels[id];
+        sender
+            .send(GameMessage::ConfirmUndo(requester, deadline))
+            .await?;
+    }
+
+    // every vote is waited for (even after someone declines), so none are left over for later.
+    // Passing (or running out of time) declines
+    let votes = player_channels
+        .iter_mut()
+        .filter(|(id, _)| **id != requester)
+        .map(|(id, (_, receivers))| async move {
+            let agreed = select! {
+                Some(agreed) = receivers.allow_undo.recv() => agreed,
+                _ = receivers.pass.recv() => false,
+            };
+            (*id, agreed)
+        })
+        .collect();
+    let agreed = collect_responses(votes, voters, deadline, broadcaster)
+        .await?
+        .into_iter()
+        .all(|agreed| agreed);
+    tracing::debug!(agreed, "Undo vote finished");
+
+    if !agreed {
+        broadcaster.send(BroadcastMessage::Undo(false))?;
+        return Ok(CoupGameState::Wait(game));
+    }
+
+    match game.undo() {
+        Ok(Phase::Wait(game)) => {
+            broadcaster.send(BroadcastMessage::Undo(true))?;
+            Ok(CoupGameState::Wait(game))
+        }
+        Ok(_) => unreachable!("Only turns are checkpointed"),
+        Err(game) => Ok(CoupGameState::Wait(game)),
+    }
+}
+
 async fn handle_safe(
     game: CoupGame<Safe>,
     broadcaster: &broadcast::Sender<BroadcastMessage>,
@@ -296,101 +539,84 @@ async fn handle_safe(
 }
 
 async fn handle_challengeable(
-    game: CoupGame<OnlyChallengeable>,
+    mut game: CoupGame<OnlyChallengeable>,
     ChannelHandles {
         player_channels,
         broadcaster,
+        settings,
     }: ChannelHandles<'_>,
 ) -> Result<CoupGameState> {
     let challenges = game.challenges();
+    let deadline = deadline(settings.reaction_time);
 
     // send challenges to client handlers
     trace!("Sending challenges to client handlers");
-    send_challenges(challenges.all(), player_channels).await?;
-
-    let (challenges, passes): (Vec<_>, Vec<_>) = player_channels
-        .iter_mut()
-        .filter_map(|(id, channels)| (*id != challenges.actor()).then_some(channels))
-        .map(|(_, receivers)| {
-            (
-                Box::pin(receivers.challenge.recv()),
-                Box::pin(receivers.pass.recv()),
-            )
+    send_challenges(challenges.all(), deadline, player_channels).await?;
+
+    let challenges = gather_reactions(player_channels, broadcaster, game.deciders(), deadline)
+        .await?
+        .into_iter()
+        .filter_map(|reaction| match reaction {
+            Reaction::Challenge(challenge) => Some(challenge),
+            Reaction::Block(..) => None,
         })
         .collect();
 
-    let challenges = select_all(challenges);
-    let passes = join_all(passes);
-
-    select! {
-        // if someone challenges within the 10 second window
-        (Some(challenge), _, _) = challenges => {
+    match game.resolve(challenges) {
+        Some(challenge) => {
             let game = game.challenge(challenge);
             broadcaster.send(BroadcastMessage::Outcome(game.outcome()))?;
             Ok(game.advance())
-        },
+        }
         // all potential challengers have passed on challenging
-        _ = passes => {
+        None => {
             broadcaster.send(BroadcastMessage::Outcome(game.outcome()))?;
             Ok(game.advance())
-        },
+        }
     }
 }
 
 async fn handle_reactable(
-    game: CoupGame<Reactable>,
+    mut game: CoupGame<Reactable>,
     ChannelHandles {
         player_channels,
         broadcaster,
+        settings,
     }: ChannelHandles<'_>,
 ) -> Result<CoupGameState> {
-    let reactions = game.reactions();
-
-    // send client handlers all reactions
-    trace!("Sending reactions to client handlers");
-    send_reactions(reactions.all(), player_channels).await?;
-
-    let blocker = reactions.block().blocker();
-    let actor = reactions.actor();
+    let reactions = game.reactions().all();
+    let deadline = deadline(settings.reaction_time);
 
-    let mut block = None;
-    let (challenges, passes): (Vec<_>, Vec<_>) = player_channels
-        .iter_mut()
-        .filter(|(id, _)| **id != actor)
-        .flat_map(|(id, (_, receivers))| {
-            // no non-constant split mutable borrows of hashmap :(
-            if *id == blocker {
-                let fut = Box::pin(receivers.block.recv());
-                block = Some(fut);
-            }
-            Some((
-                Box::pin(receivers.challenge.recv()),
-                Box::pin(receivers.pass.recv()),
-            ))
-        })
-        .collect();
+    // send client handlers the reactions for this stage
+    trace!(stage = ?game.stage(), "Sending reactions to client handlers");
+    send_reactions(reactions, game.stage(), deadline, player_channels).await?;
 
-    let block = block.expect("Must be set");
-    let passes = join_all(passes);
-    let challenges = select_all(challenges);
+    let reactions =
+        gather_reactions(player_channels, broadcaster, game.deciders(), deadline).await?;
 
-    // race between the victim blocking, anyone challenging, and a 10 second timeout
-    select! {
-        // someone blocks within 10 second timeframe
-        Some(block) = block => {
+    match game.resolve(reactions) {
+        Some(Reaction::Block(block)) => {
             // FIXME: handle challenging a block
             let game = game.block(block);
             broadcaster.send(BroadcastMessage::Outcome(game.outcome()))?;
             Ok(CoupGameState::Wait(game.advance()))
-        },
-        // someone challenges within 10 second timeframe
-        (Some(challenge), _, _) = challenges => {
+        }
+        Some(Reaction::Challenge(challenge)) => {
             let game = game.challenge(challenge);
             broadcaster.send(BroadcastMessage::Outcome(game.outcome()))?;
             Ok(game.advance())
-        },
+        }
+        // nobody challenged the action, so the victim gets to block it
+        None if game.stage() == ReactionStage::Challenges => {
+            let handles = ChannelHandles {
+                player_channels,
+                broadcaster,
+                settings,
+            };
+            Box::pin(handle_reactable(game.open_blocks(), handles)).await
+        }
         // all potential reactors pass
-        _ = passes => {
+        None => {
             broadcaster.send(BroadcastMessage::Outcome(game.outcome()))?;
             Ok(game.advance())
         }
@@ -398,56 +624,115 @@ async fn handle_reactable(
 }
 
 async fn handle_blockable(
-    game: CoupGame<OnlyBlockable>,
+    mut game: CoupGame<OnlyBlockable>,
     ChannelHandles {
         player_channels,
         broadcaster,
+        settings,
     }: ChannelHandles<'_>,
 ) -> Result<CoupGameState> {
     let blocks = game.blocks();
+    let deadline = deadline(settings.reaction_time);
 
     // send client handlers the possible blocks
     trace!("Sending blocks to client handlers");
-    send_blocks(blocks.all(), player_channels).await?;
-
-    let (blocks, passes): (Vec<_>, Vec<_>) = player_channels
-        .iter_mut()
-        .filter_map(|(id, (_, receivers))| (*id != blocks.actor()).then_some(receivers))
-        .map(|receivers| {
-            (
-                Box::pin(receivers.block.recv()),
-                Box::pin(receivers.pass.recv()),
-            )
+    send_blocks(blocks.all(), deadline, player_channels).await?;
+
+    let blocks = gather_reactions(player_channels, broadcaster, game.deciders(), deadline)
+        .await?
+        .into_iter()
+        .filter_map(|reaction| match reaction {
+            Reaction::Block(block) => Some(block),
+            Reaction::Challenge(..) => None,
         })
         .collect();
 
-    let blocks = select_all(blocks);
-    let passes = join_all(passes);
-
-    // if someone blocks within the 10 second window
-    select! {
-        (Some(block), _, _) = blocks => {
+    match game.resolve(blocks) {
+        Some(block) => {
             // FIXME: handle challenging a block
             let game = game.block(block);
             broadcaster.send(BroadcastMessage::Outcome(game.outcome()))?;
 
             Ok(CoupGameState::Wait(game.advance()))
-        },
-        _ = passes => {
+        }
+        None => {
             broadcaster.send(BroadcastMessage::Outcome(game.outcome()))?;
             Ok(CoupGameState::Wait(game.advance()))
-        },
+        }
     }
 }
 
+// waits for every reactor to respond, rather than going with whichever reaction arrives first
+// (which would come down to latency). Client handlers always respond once, passing when they run
+// out of time, so this only takes as long as the reaction window. The game then picks between
+// the reactions by its ReactionPriority
+async fn gather_reactions(
+    player_channels: &mut HashMap<PlayerId, GameHalf>,
+    broadcaster: &broadcast::Sender<BroadcastMessage>,
+    reactors: Vec<PlayerId>,
+    deadline: Timestamp,
+) -> Result<Vec<Reaction>> {
+    let responses = player_channels
+        .iter_mut()
+        .filter(|(id, _)| reactors.contains(id))
+        .map(|(id, (_, receivers))| async move {
+            let response = select! {
+                challenge = receivers.challenge.recv() => challenge.map(|c| Some(Reaction::Challenge(c))),
+                block = receivers.block.recv() => block.map(|b| Some(Reaction::Block(b))),
+                pass = receivers.pass.recv() => pass.map(|_| None),
+            };
+            (*id, response)
+        })
+        .collect();
+
+    let responses: Option<Vec<_>> = collect_responses(responses, reactors, deadline, broadcaster)
+        .await?
+        .into_iter()
+        .collect();
+    let reactions = responses
+        .ok_or(PlayerCommunicationError)?
+        .into_iter()
+        .flatten()
+        .collect();
+    tracing::debug!(reactions = ?reactions, "Gathered reactions");
+    Ok(reactions)
+}
+
+// waits for a response from every player in pending, letting everyone know who is left (and how
+// long they have) as each one comes in
+async fn collect_responses<T>(
+    mut responses: FuturesUnordered<impl Future<Output = (PlayerId, T)>>,
+    mut pending: Vec<PlayerId>,
+    deadline: Timestamp,
+    broadcaster: &broadcast::Sender<BroadcastMessage>,
+) -> Result<Vec<T>> {
+    let mut collected = Vec::with_capacity(pending.len());
+    broadcaster.send(BroadcastMessage::WaitingFor(
+        pending.clone(),
+        Some(deadline),
+    ))?;
+
+    while let Some((id, response)) = responses.next().await {
+        collected.push(response);
+        pending.retain(|pending| *pending != id);
+        broadcaster.send(BroadcastMessage::WaitingFor(
+            pending.clone(),
+            Some(deadline),
+        ))?;
+    }
+
+    Ok(collected)
+}
+
 async fn send_blocks(
     blocks: &HashMap<PlayerId, Block>,
+    deadline: Timestamp,
     player_channels: &mut HashMap<PlayerId, GameHalf>,
 ) -> Result<()> {
     for (id, block) in blocks {
         let (sender, _) = player_channels.get_mut(id).expect("Must exist");
         sender
-            .send(Choices::Block(Blocks::Other(block.clone())).into())
+            .send(Choices::Block(Blocks::Other(block.clone()), deadline).into())
             .await?;
     }
 
@@ -456,12 +741,13 @@ async fn send_blocks(
 
 async fn send_challenges(
     challenges: &HashMap<PlayerId, Challenge>,
+    deadline: Timestamp,
     player_channels: &mut HashMap<PlayerId, GameHalf>,
 ) -> Result<()> {
     for (id, challenge) in challenges {
         let (sender, _) = player_channels.get_mut(id).expect("Must exist");
         sender
-            .send(Choices::Challenge(challenge.clone()).into())
+            .send(Choices::Challenge(challenge.clone(), deadline).into())
             .await?;
     }
 
@@ -470,11 +756,15 @@ async fn send_challenges(
 
 async fn send_reactions(
     reactions: HashMap<PlayerId, Vec<Reaction>>,
+    stage: ReactionStage,
+    deadline: Timestamp,
     player_channels: &mut HashMap<PlayerId, GameHalf>,
 ) -> Result<()> {
     for (id, reaction) in reactions {
         let (sender, _) = player_channels.get_mut(&id).expect("Must exist");
-        sender.send(Choices::Reactions(reaction).into()).await?;
+        sender
+            .send(Choices::Reactions(reaction, stage, deadline).into())
+            .await?;
     }
 
     Ok(())
@@ -1,20 +1,38 @@
+mod admin;
+mod archive;
+mod bot;
+mod chat;
 mod client;
 mod dispatcher;
 mod game;
+mod metrics;
+mod spectate;
 
+use admin::{GameDetails, GameOverview};
+use archive::Archive;
 use axum::{
-    Extension, Router,
-    extract::{ConnectInfo, ws::WebSocketUpgrade},
+    Extension, Json, Router,
+    extract::{
+        ConnectInfo, Path, Query,
+        ws::{WebSocket, WebSocketUpgrade},
+    },
     handler::Handler,
+    http::{StatusCode, header::CONTENT_TYPE},
     response::{Html, IntoResponse},
     routing::get,
 };
 use clap::Parser;
 use client::client_handler;
-use dispatcher::dispatcher;
-use overthrow_types::{ClientError, ClientMessage, ClientResponse};
+use dispatcher::{Inbox, Join, LobbySettings, Registration, Watching, dispatcher};
+use jiff::Timestamp;
+use metrics::METRICS;
+use overthrow_types::{ClientError, ClientMessage, ClientResponse, Difficulty, PlayerId, Replay};
 use schemars::schema_for;
-use std::{fs, net::SocketAddr};
+use serde::Deserialize;
+use spectate::{Watch, spectate_handler};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fs, net::SocketAddr, path::PathBuf};
 use tokio::sync::{
     mpsc::{self, Sender},
     oneshot,
@@ -22,19 +40,97 @@ use tokio::sync::{
 use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
 use uuid::Uuid;
 
-use crate::game::PlayerGameInfo;
-
 #[derive(Clone, Debug)]
 pub struct Disconnected {
     addr: SocketAddr,
     game_id: Uuid,
 }
 
+// entropy a player in a lobby wants mixed into the shuffle seed
+#[derive(Clone, Debug)]
+pub struct Entropy {
+    addr: SocketAddr,
+    game_id: Uuid,
+    entropy: String,
+}
+
+// something a member of a lobby said, to be sent to everyone else
+#[derive(Clone, Debug)]
+pub struct Chat {
+    game_id: Uuid,
+    member: Uuid,
+    text: String,
+}
+
+// something a player in a lobby wants before the game starts
+#[derive(Debug)]
+pub struct LobbyRequest {
+    addr: SocketAddr,
+    game_id: Uuid,
+    action: LobbyAction,
+}
+
+#[derive(Debug)]
+pub enum LobbyAction {
+    Ready(bool),
+    // replied to with why the game couldn't start, if it didn't
+    Start(oneshot::Sender<Result<(), ClientError>>),
+    // likewise replied to with why the bot couldn't be added or removed
+    AddBot(Difficulty, oneshot::Sender<Result<(), ClientError>>),
+    RemoveBot(Uuid, oneshot::Sender<Result<(), ClientError>>),
+}
+
+// a new connection wanting to take back the seat a resume token was given out for
+#[derive(Debug)]
+pub struct Resume {
+    token: Uuid,
+    // replied to with where to hand the connection over to, if the seat is still in a game
+    reply: oneshot::Sender<Option<Sender<WebSocket>>>,
+}
+
+// a player in a game losing their connection, or coming back to it
+#[derive(Clone, Debug)]
+pub struct Connection {
+    game_id: Uuid,
+    player: PlayerId,
+    // when the seat is given up on, if they lost their connection
+    given_up: Option<Timestamp>,
+}
+
+// someone wanting to watch a game, without a seat in it
+#[derive(Debug)]
+pub struct Spectate {
+    addr: SocketAddr,
+    game_id: Uuid,
+    omniscient: bool,
+    // replied to with what to pass on to them, or why they can't watch
+    reply: oneshot::Sender<Result<Watching, ClientError>>,
+}
+
+// something the server's operator wants to see or do, replied to with the answer (or whether there
+// was a game or connection to do it to)
+#[derive(Debug)]
+pub enum AdminRequest {
+    Games(oneshot::Sender<Vec<GameOverview>>),
+    Game(Uuid, oneshot::Sender<Option<GameDetails>>),
+    Cancel(Uuid, oneshot::Sender<bool>),
+    // stops the game at the start of the next turn, or lets it carry on
+    Pause(Uuid, bool, oneshot::Sender<bool>),
+    Kick(SocketAddr, oneshot::Sender<bool>),
+}
+
 #[derive(Clone, Debug)]
 struct AppState {
     // for registering a task/connection with the dispatcher
-    register: Sender<(oneshot::Sender<PlayerGameInfo>, oneshot::Sender<Uuid>)>,
+    register: Sender<Registration>,
     disconnected: Sender<Disconnected>,
+    entropy: Sender<Entropy>,
+    lobby: Sender<LobbyRequest>,
+    resume: Sender<Resume>,
+    connection: Sender<Connection>,
+    spectate: Sender<Spectate>,
+    chat: Sender<Chat>,
+    admin: Sender<AdminRequest>,
 }
 
 #[derive(Parser, Debug)]
@@ -45,6 +141,29 @@ struct Args {
 
     #[arg(short, long, default_value_t = 3000)]
     port: u16,
+
+    // where finished games are kept
+    #[arg(long, default_value = "games")]
+    data_dir: PathBuf,
+
+    // days finished games are kept for, 0 keeps them forever
+    #[arg(long, default_value_t = 30)]
+    retention_days: u64,
+
+    // bearer token for the endpoints under /admin, which are left out without one
+    #[arg(long, env = "OVERTHROW_ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+}
+
+// how many games /games lists, given as a query parameter
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct ListGames {
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    100
 }
 
 #[tokio::main]
@@ -67,21 +186,74 @@ async fn main() {
         )
         .init();
 
+    let retention =
+        (args.retention_days > 0).then(|| Duration::from_secs(args.retention_days * 24 * 60 * 60));
+    let archive = Archive::open(args.data_dir, retention)
+        .await
+        .expect("Should be able to open the game archive");
+    let archive = Arc::new(archive);
+    tokio::spawn(archive::prune_regularly(archive.clone()));
+
     // create channel for connections to register with dispatcher
     let (register, receiver) = mpsc::channel(10);
     let (disconnected_tx, disconnected_rx) = mpsc::channel(10);
-    tokio::spawn(dispatcher(receiver, disconnected_rx));
+    let (entropy_tx, entropy_rx) = mpsc::channel(10);
+    let (lobby_tx, lobby_rx) = mpsc::channel(10);
+    let (resume_tx, resume_rx) = mpsc::channel(10);
+    let (connection_tx, connection_rx) = mpsc::channel(10);
+    let (spectate_tx, spectate_rx) = mpsc::channel(10);
+    let (chat_tx, chat_rx) = mpsc::channel(10);
+    let (admin_tx, admin_rx) = mpsc::channel(10);
+    tokio::spawn(dispatcher(
+        Inbox {
+            registrations: receiver,
+            disconnected: disconnected_rx,
+            entropy: entropy_rx,
+            lobby_requests: lobby_rx,
+            resumes: resume_rx,
+            connections: connection_rx,
+            spectates: spectate_rx,
+            chats: chat_rx,
+            admin: admin_rx,
+        },
+        archive.clone(),
+    ));
 
     let app_state = AppState {
         register,
         disconnected: disconnected_tx,
+        entropy: entropy_tx,
+        lobby: lobby_tx,
+        resume: resume_tx,
+        connection: connection_tx,
+        spectate: spectate_tx,
+        chat: chat_tx,
+        admin: admin_tx,
     };
 
+    let admin = args.admin_token.filter(|token| !token.is_empty());
+    if admin.is_none() {
+        tracing::debug!("No admin token given, so the admin endpoints are left out");
+    }
+    let admin = admin.map(|token| admin::router(token, app_state.clone()));
+
+    let metrics_handler = metrics_handler.layer(Extension(app_state.clone()));
+    let spectator_handler = spectator_handler.layer(Extension(app_state.clone()));
     let websocket_handler = websocket_handler.layer(Extension(app_state));
 
     let app = Router::new()
         .route("/", get(index))
-        .route("/websocket", get(websocket_handler));
+        .route("/websocket", get(websocket_handler))
+        .route("/spectate", get(spectator_handler))
+        .route("/games", get(list_games))
+        .route("/games/{id}", get(get_game))
+        .route("/games/{id}/replay", get(get_replay))
+        .route("/metrics", get(metrics_handler))
+        .layer(Extension(archive));
+    let app = match admin {
+        Some(admin) => app.nest("/admin", admin),
+        None => app,
+    };
 
     // listen on all ports
     let listener = tokio::net::TcpListener::bind(("0.0.0.0", args.port))
@@ -98,10 +270,66 @@ async fn main() {
 
 async fn websocket_handler(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(settings): Query<LobbySettings>,
+    Query(join): Query<Join>,
     ws: WebSocketUpgrade,
     Extension(state): Extension<AppState>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| client_handler(addr, socket, state))
+    ws.on_upgrade(move |socket| client_handler(addr, socket, state, settings, join))
+}
+
+async fn spectator_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(watch): Query<Watch>,
+    ws: WebSocketUpgrade,
+    Extension(state): Extension<AppState>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| spectate_handler(addr, socket, state, watch))
+}
+
+// how the server is doing, in the Prometheus text format
+async fn metrics_handler(Extension(state): Extension<AppState>) -> impl IntoResponse {
+    let (reply, replied) = oneshot::channel();
+    state
+        .admin
+        .send(AdminRequest::Games(reply))
+        .await
+        .expect("Dispatcher should always be available");
+    let games = replied.await.expect("Should always reply");
+
+    let content_type = [(CONTENT_TYPE, "text/plain; version=0.0.4")];
+    (content_type, METRICS.render(&games))
+}
+
+// finished games, the latest to end first
+async fn list_games(
+    Query(ListGames { limit }): Query<ListGames>,
+    Extension(archive): Extension<Arc<Archive>>,
+) -> impl IntoResponse {
+    let mut games = archive.list().await;
+    games.truncate(limit);
+    Json(games)
+}
+
+async fn get_game(
+    Path(id): Path<Uuid>,
+    Extension(archive): Extension<Arc<Archive>>,
+) -> impl IntoResponse {
+    archive.get(id).await.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn get_replay(
+    Path(id): Path<Uuid>,
+    Extension(archive): Extension<Arc<Archive>>,
+) -> impl IntoResponse {
+    match archive.replay(id).await {
+        Ok(Some(replay)) => Ok(Json(replay)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!(game_id = %id, error = %e, "Failed to read replay");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
 }
 
 // Include utf-8 file at **compile** time.
@@ -132,4 +360,13 @@ fn generate_schemas() {
         serde_json::to_string_pretty(&schema).unwrap(),
     )
     .unwrap();
+
+    // also covers GameRecord, as served by /games
+    let schema = schema_for!(Replay);
+
+    fs::write(
+        "./replay.json",
+        serde_json::to_string_pretty(&schema).unwrap(),
+    )
+    .unwrap();
 }
@@ -0,0 +1,316 @@
+use crate::admin::{GameOverview, Stage};
+
+use overthrow_types::ClientMessage;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// Numbers for dashboards, served in the Prometheus text format at /metrics. Counters are kept here
+// as they happen, while how many lobbies, games and clients there are right now is taken from the
+// dispatcher when the metrics are asked for
+
+pub static METRICS: Metrics = Metrics::new();
+
+// upper bounds of the buckets response times are counted in, in seconds
+const RESPONSE_BUCKETS: [f64; 10] = [0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 15.0, 30.0, 60.0, 120.0];
+
+// what kind of choice a player was given, for telling timeouts apart
+#[derive(Debug, Clone, Copy)]
+pub enum ChoiceKind {
+    Action,
+    Reaction,
+    Card,
+    Undo,
+}
+
+impl ChoiceKind {
+    const ALL: [ChoiceKind; 4] = [
+        ChoiceKind::Action,
+        ChoiceKind::Reaction,
+        ChoiceKind::Card,
+        ChoiceKind::Undo,
+    ];
+
+    pub fn of(message: &ClientMessage) -> Option<ChoiceKind> {
+        use ClientMessage as Msg;
+        let kind = match message {
+            Msg::ActionChoices(..) => ChoiceKind::Action,
+            Msg::ChallengeChoice(..) | Msg::BlockChoices(..) | Msg::ReactionChoices(..) => {
+                ChoiceKind::Reaction
+            }
+            Msg::VictimChoices(..) | Msg::OneFromThreeChoices(..) | Msg::TwoFromFourChoices(..) => {
+                ChoiceKind::Card
+            }
+            Msg::UndoRequested(..) => ChoiceKind::Undo,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ChoiceKind::Action => "action",
+            ChoiceKind::Reaction => "reaction",
+            ChoiceKind::Card => "card",
+            ChoiceKind::Undo => "undo",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Metrics {
+    games_finished: AtomicU64,
+    games_cancelled: AtomicU64,
+    // indexed the same as ChoiceKind::ALL
+    timeouts: [AtomicU64; 4],
+    invalid_responses: AtomicU64,
+    websocket_errors: AtomicU64,
+    // how many responses took at most each bucket's bound (and more than the one before)
+    response_buckets: [AtomicU64; RESPONSE_BUCKETS.len()],
+    responses: AtomicU64,
+    response_micros: AtomicU64,
+}
+
+impl Metrics {
+    const fn new() -> Metrics {
+        Metrics {
+            games_finished: AtomicU64::new(0),
+            games_cancelled: AtomicU64::new(0),
+            timeouts: [const { AtomicU64::new(0) }; 4],
+            invalid_responses: AtomicU64::new(0),
+            websocket_errors: AtomicU64::new(0),
+            response_buckets: [const { AtomicU64::new(0) }; RESPONSE_BUCKETS.len()],
+            responses: AtomicU64::new(0),
+            response_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn game_ended(&self, finished: bool) {
+        let counter = if finished {
+            &self.games_finished
+        } else {
+            &self.games_cancelled
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn timed_out(&self, kind: ChoiceKind) {
+        self.timeouts[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn invalid_response(&self) {
+        self.invalid_responses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn websocket_error(&self) {
+        self.websocket_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // how long a player took to give a valid response to a choice
+    pub fn responded(&self, took: Duration) {
+        let seconds = took.as_secs_f64();
+        if let Some(bucket) = RESPONSE_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.response_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.responses.fetch_add(1, Ordering::Relaxed);
+        let micros = u64::try_from(took.as_micros()).unwrap_or(u64::MAX);
+        self.response_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    // every metric in the Prometheus text format, along with what the dispatcher has right now
+    pub fn render(&self, games: &[GameOverview]) -> String {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed) as f64;
+        let stage = |stage: Stage| {
+            let games = games.iter().filter(|game| game.stage == stage);
+            games.count() as f64
+        };
+        // players are sent away once their game is over
+        let players = games
+            .iter()
+            .filter(|game| game.stage != Stage::Over)
+            .flat_map(|game| &game.members)
+            .filter(|member| member.connected)
+            .count();
+        let spectators: usize = games.iter().map(|game| game.spectators.len()).sum();
+
+        let mut out = String::new();
+        family(
+            &mut out,
+            ("overthrow_connected_clients", "gauge"),
+            "Players and spectators connected right now",
+            [
+                ("{role=\"player\"}".into(), players as f64),
+                ("{role=\"spectator\"}".into(), spectators as f64),
+            ],
+        );
+        family(
+            &mut out,
+            ("overthrow_lobbies_waiting", "gauge"),
+            "Lobbies whose game hasn't started yet",
+            [(String::new(), stage(Stage::Lobby))],
+        );
+        family(
+            &mut out,
+            ("overthrow_games_running", "gauge"),
+            "Games being played right now",
+            [(String::new(), stage(Stage::Playing))],
+        );
+        family(
+            &mut out,
+            ("overthrow_games_ended_total", "counter"),
+            "Games that finished, or were cancelled before finishing",
+            [
+                ("{result=\"finished\"}".into(), load(&self.games_finished)),
+                ("{result=\"cancelled\"}".into(), load(&self.games_cancelled)),
+            ],
+        );
+
+        // buckets count every response up to their bound, not just those since the last bound
+        let mut cumulative = 0.0;
+        let buckets = RESPONSE_BUCKETS.iter().zip(&self.response_buckets);
+        let buckets = buckets.map(|(bound, bucket)| {
+            cumulative += load(bucket);
+            (format!("_bucket{{le=\"{bound}\"}}"), cumulative)
+        });
+        let responses = load(&self.responses);
+        let totals = [
+            ("_bucket{le=\"+Inf\"}".into(), responses),
+            ("_sum".into(), load(&self.response_micros) / 1_000_000.0),
+            ("_count".into(), responses),
+        ];
+        family(
+            &mut out,
+            ("overthrow_response_seconds", "histogram"),
+            "How long players took to give a valid response to a choice",
+            buckets.chain(totals),
+        );
+
+        let timeouts = ChoiceKind::ALL.iter().zip(&self.timeouts);
+        family(
+            &mut out,
+            ("overthrow_timeouts_total", "counter"),
+            "Choices made for players who ran out of time, by kind of choice",
+            timeouts
+                .map(|(kind, counter)| (format!("{{choice=\"{}\"}}", kind.label()), load(counter))),
+        );
+        family(
+            &mut out,
+            ("overthrow_invalid_responses_total", "counter"),
+            "Responses from clients that weren't valid for what they were asked",
+            [(String::new(), load(&self.invalid_responses))],
+        );
+        family(
+            &mut out,
+            ("overthrow_websocket_errors_total", "counter"),
+            "Errors sending to or receiving from WebSocket connections",
+            [(String::new(), load(&self.websocket_errors))],
+        );
+
+        out
+    }
+}
+
+// writes out a metric's help and type, then each of its samples, which are given as what goes
+// after the metric's name (a suffix and labels) along with their value
+fn family(
+    out: &mut String,
+    (name, kind): (&str, &str),
+    help: &str,
+    samples: impl IntoIterator<Item = (String, f64)>,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (after, value) in samples {
+        let _ = writeln!(out, "{name}{after} {value}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin::MemberOverview;
+    use jiff::Timestamp;
+    use std::net::SocketAddr;
+    use uuid::Uuid;
+
+    fn game(stage: Stage, connected: &[bool], spectators: usize) -> GameOverview {
+        let members = connected.iter().map(|&connected| MemberOverview {
+            id: Uuid::now_v7(),
+            name: "Dave".to_owned(),
+            ready: true,
+            left: false,
+            connected,
+            addr: None,
+            bot: None,
+        });
+        GameOverview {
+            id: Uuid::now_v7(),
+            code: "ABCDE".to_owned(),
+            stage,
+            paused: false,
+            created: Timestamp::now(),
+            members: members.collect(),
+            spectators: vec![SocketAddr::from(([127, 0, 0, 1], 1)); spectators],
+        }
+    }
+
+    #[test]
+    fn renders_text_format() {
+        let metrics = Metrics::new();
+        metrics.game_ended(true);
+        metrics.game_ended(false);
+        metrics.game_ended(false);
+        metrics.timed_out(ChoiceKind::Card);
+        metrics.responded(Duration::from_millis(300));
+        metrics.responded(Duration::from_secs(3));
+        metrics.responded(Duration::from_secs(600));
+
+        let games = [
+            game(Stage::Lobby, &[true, true], 0),
+            game(Stage::Playing, &[true, false, true], 2),
+            // players in games that are over aren't counted
+            game(Stage::Over, &[true], 1),
+        ];
+        let out = metrics.render(&games);
+        let lines: Vec<_> = out.lines().collect();
+
+        for expected in [
+            "# HELP overthrow_connected_clients Players and spectators connected right now",
+            "# TYPE overthrow_connected_clients gauge",
+            "overthrow_connected_clients{role=\"player\"} 4",
+            "overthrow_connected_clients{role=\"spectator\"} 3",
+            "overthrow_lobbies_waiting 1",
+            "overthrow_games_running 1",
+            "overthrow_games_ended_total{result=\"finished\"} 1",
+            "overthrow_games_ended_total{result=\"cancelled\"} 2",
+            "# TYPE overthrow_response_seconds histogram",
+            "overthrow_response_seconds_bucket{le=\"0.25\"} 0",
+            "overthrow_response_seconds_bucket{le=\"0.5\"} 1",
+            "overthrow_response_seconds_bucket{le=\"5\"} 2",
+            "overthrow_response_seconds_bucket{le=\"120\"} 2",
+            "overthrow_response_seconds_bucket{le=\"+Inf\"} 3",
+            "overthrow_response_seconds_sum 603.3",
+            "overthrow_response_seconds_count 3",
+            "overthrow_timeouts_total{choice=\"action\"} 0",
+            "overthrow_timeouts_total{choice=\"card\"} 1",
+            "overthrow_invalid_responses_total 0",
+            "overthrow_websocket_errors_total 0",
+        ] {
+            assert!(lines.contains(&expected), "missing {expected:?} in:\n{out}");
+        }
+
+        // each family has both a help and a type line
+        let families = lines
+            .iter()
+            .filter(|line| line.starts_with("# TYPE"))
+            .count();
+        assert_eq!(families, 8);
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|line| line.starts_with("# HELP"))
+                .count(),
+            families
+        );
+    }
+}
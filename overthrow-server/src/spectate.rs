@@ -0,0 +1,252 @@
+use crate::client::serialize;
+use crate::dispatcher::{MAX_TIME, Watching};
+use crate::game::{BroadcastMessage, View};
+use crate::metrics::METRICS;
+use crate::{AppState, Disconnected, Spectate};
+
+use axum::extract::ws::{Message, WebSocket};
+use overthrow_types::{ClientError, ClientMessage};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::oneshot;
+use tokio::time::{Instant, sleep_until};
+use tracing::instrument;
+use uuid::Uuid;
+
+// which game someone wants to watch and how, given as query parameters when connecting
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Watch {
+    pub game: Uuid,
+    // seconds to be kept behind the game, on top of what the lobby asks for
+    #[serde(default)]
+    pub delay: u32,
+    // every hand showing, only for lobbies that allow it or games that are over
+    #[serde(default)]
+    pub omniscient: bool,
+}
+
+#[instrument(skip(socket, state))]
+pub async fn spectate_handler(
+    addr: SocketAddr,
+    mut socket: WebSocket,
+    state: AppState,
+    watch: Watch,
+) {
+    let (reply, watching) = oneshot::channel();
+    state
+        .spectate
+        .send(Spectate {
+            addr,
+            game_id: watch.game,
+            omniscient: watch.omniscient,
+            reply,
+        })
+        .await
+        .expect("Dispatcher should always be available");
+
+    let watching = match watching.await.expect("Should always reply") {
+        Ok(watching) => watching,
+        Err(e) => {
+            tracing::debug!(error = %e, "Spectator couldn't watch game");
+            let _ = socket.send(Message::Text(serialize(e))).await;
+            let _ = socket.send(Message::Close(None)).await;
+            return;
+        }
+    };
+
+    spectate(&mut socket, watching, watch).await;
+
+    tracing::debug!("Spectator is done watching");
+    let _ = socket.send(Message::Close(None)).await;
+    state
+        .disconnected
+        .send(Disconnected {
+            addr,
+            game_id: watch.game,
+        })
+        .await
+        .expect("Dispatcher should always be available");
+}
+
+// passes on everything public about the game, each message held back until the delay has passed
+// since it happened. The connection is only read from to see when it closes
+async fn spectate(socket: &mut WebSocket, mut watching: Watching, watch: Watch) {
+    let delay = watch.delay.max(watching.delay).min(MAX_TIME);
+    let delay = Duration::from_secs(delay.into());
+    let info = |view: &View| {
+        let info = if watch.omniscient {
+            &view.omniscient
+        } else {
+            &view.public
+        };
+        ClientMessage::Info(info.clone())
+    };
+
+    // catch up on where the game is at, which may already be over
+    let mut queue = VecDeque::new();
+    let mut over = false;
+    if let Some(view) = watching.view.borrow_and_update().as_ref() {
+        queue.push_back((Instant::now() + delay, info(view)));
+        if let Some((summary, reveal)) = &view.end {
+            queue.push_back((
+                Instant::now() + delay,
+                ClientMessage::Reveal(reveal.clone()),
+            ));
+            queue.push_back((Instant::now() + delay, ClientMessage::End(*summary)));
+            over = true;
+        }
+    }
+
+    loop {
+        let due = queue.front().map(|(due, _)| *due);
+        select! {
+            // broadcasts are taken before views, since a turn's outcome is broadcast before the
+            // next turn's view is
+            biased;
+            _ = sleep_until(due.unwrap_or_else(Instant::now)), if due.is_some() => {
+                let (_, message) = queue.pop_front().expect("Something is due");
+                let last = matches!(message, ClientMessage::End(..) | ClientMessage::GameCancelled);
+                if socket.send(Message::Text(serialize(message))).await.is_err() {
+                    METRICS.websocket_error();
+                    break;
+                }
+                if last {
+                    break;
+                }
+            },
+            Ok(broadcast) = watching.broadcasts.recv(), if !over => {
+                let messages = match broadcast {
+                    // the view is updated right before the end is broadcast, so the last one is
+                    // sent with it
+                    BroadcastMessage::End(summary, reveal) => {
+                        over = true;
+                        let view = watching.view.borrow_and_update().as_ref().map(info);
+                        view.into_iter()
+                            .chain([ClientMessage::Reveal(reveal), ClientMessage::End(summary)])
+                            .collect()
+                    }
+                    BroadcastMessage::GameCancelled(reveal) => {
+                        over = true;
+                        reveal
+                            .map(ClientMessage::Reveal)
+                            .into_iter()
+                            .chain([ClientMessage::GameCancelled])
+                            .collect()
+                    }
+                    BroadcastMessage::Chat(chat) if watching.chat => vec![ClientMessage::Chat(chat)],
+                    broadcast => spectated(broadcast).into_iter().collect(),
+                };
+                let due = Instant::now() + delay;
+                queue.extend(messages.into_iter().map(|message| (due, message)));
+            },
+            Some(()) = watching.kicks.recv() => {
+                tracing::debug!("Spectator was kicked");
+                let _ = socket.send(Message::Text(serialize(ClientError::Kicked))).await;
+                break;
+            },
+            Ok(()) = watching.view.changed(), if !over => {
+                let view = watching.view.borrow_and_update().as_ref().map(info);
+                queue.extend(view.map(|info| (Instant::now() + delay, info)));
+            },
+            message = socket.recv() => match message {
+                Some(Err(_)) => {
+                    METRICS.websocket_error();
+                    break;
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                // nothing spectators send is taken
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+// what spectators are shown of a broadcast, which is everything public except the lobby (and
+// chat, depending on the lobby)
+pub fn spectated(broadcast: BroadcastMessage) -> Option<ClientMessage> {
+    let message = match broadcast {
+        BroadcastMessage::Outcome(outcome) => ClientMessage::Outcome(outcome),
+        BroadcastMessage::Declared(action) => ClientMessage::ActionDeclared(action),
+        BroadcastMessage::Reacted(reaction) => ClientMessage::ReactionDeclared(reaction),
+        BroadcastMessage::Undo(true) => ClientMessage::Undone,
+        BroadcastMessage::Undo(false) => ClientMessage::UndoDeclined,
+        BroadcastMessage::WaitingFor(deciders, deadline) => {
+            ClientMessage::WaitingFor(deciders, deadline)
+        }
+        BroadcastMessage::Disconnected(player, given_up) => {
+            ClientMessage::PlayerDisconnected(player, given_up)
+        }
+        BroadcastMessage::Reconnected(player) => ClientMessage::PlayerReconnected(player),
+        BroadcastMessage::Left(player) => ClientMessage::PlayerLeft(player),
+        BroadcastMessage::Paused(true) => ClientMessage::GamePaused,
+        BroadcastMessage::Paused(false) => ClientMessage::GameResumed,
+        // only passed on if the lobby allows it
+        BroadcastMessage::Lobby(..)
+        | BroadcastMessage::Chat(..)
+        | BroadcastMessage::End(..)
+        | BroadcastMessage::GameCancelled(..) => {
+            return None;
+        }
+    };
+
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::Timestamp;
+    use overthrow_engine::commitment::{Reveal, Secret};
+    use overthrow_types::{ChatMessage, PlayerId, Summary};
+
+    #[test]
+    fn public_broadcasts_are_passed_on() {
+        assert!(matches!(
+            spectated(BroadcastMessage::Left(PlayerId::Two)),
+            Some(ClientMessage::PlayerLeft(PlayerId::Two))
+        ));
+        assert!(matches!(
+            spectated(BroadcastMessage::Undo(true)),
+            Some(ClientMessage::Undone)
+        ));
+        assert!(matches!(
+            spectated(BroadcastMessage::Undo(false)),
+            Some(ClientMessage::UndoDeclined)
+        ));
+        assert!(matches!(
+            spectated(BroadcastMessage::Paused(true)),
+            Some(ClientMessage::GamePaused)
+        ));
+        assert!(matches!(
+            spectated(BroadcastMessage::WaitingFor(vec![PlayerId::One], None)),
+            Some(ClientMessage::WaitingFor(players, None)) if players == [PlayerId::One]
+        ));
+    }
+
+    // chat and the end of the game are up to the spectator's lobby and view
+    #[test]
+    fn the_rest_is_held_back() {
+        let chat = ChatMessage {
+            sender: Uuid::now_v7(),
+            name: "Dave".to_owned(),
+            sent: Timestamp::now(),
+            text: "hi".to_owned(),
+        };
+        let reveal = Reveal::new(Secret::random(), Vec::new());
+
+        assert!(spectated(BroadcastMessage::Chat(chat)).is_none());
+        assert!(spectated(BroadcastMessage::GameCancelled(None)).is_none());
+        assert!(
+            spectated(BroadcastMessage::End(
+                Summary {
+                    winner: PlayerId::One
+                },
+                reveal
+            ))
+            .is_none()
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use clap::ValueEnum;
+use overthrow_engine::action::Act;
+use overthrow_engine::deck::{Card, Hand};
+use overthrow_engine::driver::Decision;
+use overthrow_engine::machine::GameInfo;
+use overthrow_engine::player_map::Player;
+use overthrow_engine::players::PlayerId;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// An agent makes every decision for a single seat. Options are never empty, and in reaction
+// windows Decision::Pass is always one of them
+pub trait Agent: Send {
+    fn decide(&mut self, me: PlayerId, info: &GameInfo, options: &[Decision]) -> Decision;
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentKind {
+    // picks uniformly between all options
+    Random,
+    // only claims cards it holds and never challenges
+    Honest,
+    // claims whatever is best for it, blocks everything and challenges now and then
+    Bluffer,
+}
+
+impl fmt::Display for AgentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentKind::Random => write!(f, "random"),
+            AgentKind::Honest => write!(f, "honest"),
+            AgentKind::Bluffer => write!(f, "bluffer"),
+        }
+    }
+}
+
+impl AgentKind {
+    pub fn build(self, seed: u64) -> Box<dyn Agent> {
+        let rng = ChaCha8Rng::seed_from_u64(seed);
+        match self {
+            AgentKind::Random => Box::new(RandomAgent { rng }),
+            AgentKind::Honest => Box::new(HonestAgent { rng }),
+            AgentKind::Bluffer => Box::new(BlufferAgent { rng }),
+        }
+    }
+}
+
+// the hand of a player that is still alive
+fn hand_of(me: PlayerId, info: &GameInfo) -> Hand {
+    let Player::Alive(data) = &info.players[me] else {
+        unreachable!("Dead players never decide")
+    };
+    data.hand()
+}
+
+fn coins_of(id: PlayerId, info: &GameInfo) -> u8 {
+    match &info.players[id] {
+        Player::Alive(data) => data.coins().amount(),
+        Player::Dead(..) => 0,
+    }
+}
+
+// the claim behind a decision, if it makes one
+fn claim_of(decision: &Decision) -> Option<Card> {
+    match decision {
+        Decision::Act(action) => action.claim(),
+        Decision::Block(block) => Some(block.claim()),
+        _ => None,
+    }
+}
+
+fn victim_of(act: Act) -> Option<PlayerId> {
+    match act {
+        Act::Steal { victim } | Act::Assassinate { victim } | Act::Coup { victim } => Some(victim),
+        _ => None,
+    }
+}
+
+// prefers the strongest action, then the richest victim
+fn best_action<'a>(
+    info: &GameInfo,
+    options: impl Iterator<Item = &'a Decision>,
+) -> Option<&'a Decision> {
+    options.max_by_key(|decision| {
+        let Decision::Act(action) = decision else {
+            return (0, 0);
+        };
+
+        let strength = match action.kind() {
+            Act::Coup { .. } => 7,
+            Act::Assassinate { .. } => 6,
+            Act::Tax => 5,
+            Act::Steal { .. } => 4,
+            Act::Exchange => 3,
+            Act::ForeignAid => 2,
+            Act::Income => 1,
+        };
+        let victim_coins = victim_of(action.kind()).map_or(0, |victim| coins_of(victim, info));
+
+        (strength, victim_coins)
+    })
+}
+
+fn choose_random(rng: &mut ChaCha8Rng, options: &[Decision]) -> Decision {
+    options
+        .choose(rng)
+        .cloned()
+        .expect("Agents are never given empty options")
+}
+
+#[derive(Debug)]
+struct RandomAgent {
+    rng: ChaCha8Rng,
+}
+
+impl Agent for RandomAgent {
+    fn decide(&mut self, _: PlayerId, _: &GameInfo, options: &[Decision]) -> Decision {
+        choose_random(&mut self.rng, options)
+    }
+}
+
+#[derive(Debug)]
+struct HonestAgent {
+    rng: ChaCha8Rng,
+}
+
+impl Agent for HonestAgent {
+    fn decide(&mut self, me: PlayerId, info: &GameInfo, options: &[Decision]) -> Decision {
+        let hand = hand_of(me, info);
+        let mut truthful = options
+            .iter()
+            .filter(|decision| !matches!(decision, Decision::Challenge(..)))
+            .filter(|decision| claim_of(decision).is_none_or(|card| hand.has_card(card)));
+
+        match &options[0] {
+            Decision::Act(..) => best_action(info, truthful)
+                .cloned()
+                .expect("Income is honest"),
+            // blocks only with the right card, otherwise lets it go
+            Decision::Challenge(..) | Decision::Block(..) | Decision::Pass => truthful
+                .find(|decision| matches!(decision, Decision::Block(..)))
+                .cloned()
+                .unwrap_or(Decision::Pass),
+            _ => choose_random(&mut self.rng, options),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BlufferAgent {
+    rng: ChaCha8Rng,
+}
+
+impl Agent for BlufferAgent {
+    fn decide(&mut self, me: PlayerId, info: &GameInfo, options: &[Decision]) -> Decision {
+        let hand = hand_of(me, info);
+
+        match &options[0] {
+            Decision::Act(..) => best_action(info, options.iter())
+                .cloned()
+                .expect("Agents are never given empty options"),
+            Decision::Challenge(..) | Decision::Block(..) | Decision::Pass => {
+                if let Some(block) = options.iter().find(|d| matches!(d, Decision::Block(..))) {
+                    return block.clone();
+                }
+
+                // more likely to call out a claim on a card it is holding itself
+                let challenge = options.iter().find_map(|decision| match decision {
+                    Decision::Challenge(challenge) => {
+                        Some((decision, Card::from(challenge.kind())))
+                    }
+                    _ => None,
+                });
+                match challenge {
+                    Some((decision, card)) => {
+                        let odds = if hand.has_card(card) { 0.5 } else { 0.15 };
+                        if self.rng.gen_bool(odds) {
+                            decision.clone()
+                        } else {
+                            Decision::Pass
+                        }
+                    }
+                    None => Decision::Pass,
+                }
+            }
+            _ => choose_random(&mut self.rng, options),
+        }
+    }
+}
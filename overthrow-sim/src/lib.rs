@@ -0,0 +1,3 @@
+pub mod agent;
+pub mod runner;
+pub mod stats;
@@ -0,0 +1,54 @@
+use clap::Parser;
+use overthrow_sim::agent::AgentKind;
+use overthrow_sim::runner::{self, Config};
+use overthrow_sim::stats::Report;
+use std::path::PathBuf;
+use std::{fs, panic};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, default_value_t = 1000)]
+    games: u64,
+
+    #[arg(short, long, default_value_t = 4)]
+    players: usize,
+
+    // comma separated list of agents, seated round robin
+    #[arg(short, long, value_delimiter = ',', default_value = "random")]
+    agents: Vec<AgentKind>,
+
+    #[arg(short, long, default_value_t = 0)]
+    seed: u64,
+
+    #[arg(long, default_value_t = 500)]
+    max_turns: u32,
+
+    // also write the report as JSON to this path
+    #[arg(short, long)]
+    json: Option<PathBuf>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let config = Config {
+        games: args.games,
+        players: args.players,
+        agents: args.agents,
+        seed: args.seed,
+        max_turns: args.max_turns,
+    };
+
+    // panics are collected into the report, so we don't want them spamming the terminal
+    panic::set_hook(Box::new(|_| {}));
+    let records = runner::run(&config);
+    let _ = panic::take_hook();
+
+    let report = Report::from_records(&records);
+    println!("{report}");
+
+    if let Some(path) = args.json {
+        let json = serde_json::to_string_pretty(&report).expect("Report should always serialize");
+        fs::write(&path, json).expect("Should be able to write report");
+    }
+}
@@ -0,0 +1,281 @@
+use crate::agent::{Agent, AgentKind};
+use overthrow_engine::action::Act;
+use overthrow_engine::deck::{Card, Hand};
+use overthrow_engine::driver::{Decision, Phase};
+use overthrow_engine::machine::{CoupGame, EndState, WaitState};
+use overthrow_engine::player_map::Player;
+use overthrow_engine::players::PlayerId;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub games: u64,
+    pub players: usize,
+    // agents are seated round robin, and rotate by one seat every game
+    pub agents: Vec<AgentKind>,
+    pub seed: u64,
+    // games that go on for longer than this are counted as stalled
+    pub max_turns: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum GameResult {
+    Won(PlayerId),
+    Stalled,
+    Panicked(String),
+}
+
+// everything that happened in a single simulated game
+#[derive(Debug, Clone, Serialize)]
+pub struct GameRecord {
+    pub seed: u64,
+    pub seats: Vec<AgentKind>,
+    pub starting_hands: Vec<[Card; 2]>,
+    pub result: GameResult,
+    pub turns: u32,
+    pub acts: BTreeMap<&'static str, u32>,
+    pub claims: u32,
+    pub bluffs: u32,
+    pub bluffs_caught: u32,
+    pub challenges: u32,
+    pub successful_challenges: u32,
+}
+
+impl GameRecord {
+    fn new(seed: u64, seats: Vec<AgentKind>) -> GameRecord {
+        GameRecord {
+            seed,
+            seats,
+            starting_hands: Vec::new(),
+            result: GameResult::Stalled,
+            turns: 0,
+            acts: BTreeMap::new(),
+            claims: 0,
+            bluffs: 0,
+            bluffs_caught: 0,
+            challenges: 0,
+            successful_challenges: 0,
+        }
+    }
+
+    // tallies up a decision before it is applied to the game
+    fn record(&mut self, phase: &Phase, decision: &Decision) {
+        let info = phase.info();
+        let holds = |id: PlayerId, card: Card| match &info.players[id] {
+            Player::Alive(data) => data.hand().has_card(card),
+            Player::Dead(..) => false,
+        };
+
+        let claim = match decision {
+            Decision::Act(action) => {
+                *self.acts.entry(act_name(action.kind())).or_default() += 1;
+                action.claim().map(|card| (action.actor(), card))
+            }
+            Decision::Block(block) => Some((block.blocker(), block.claim())),
+            Decision::Challenge(challenge) => {
+                self.challenges += 1;
+                if !holds(challenge.actor(), challenge.kind().into()) {
+                    self.successful_challenges += 1;
+                    self.bluffs_caught += 1;
+                }
+                None
+            }
+            _ => None,
+        };
+
+        if let Some((claimant, card)) = claim {
+            self.claims += 1;
+            if !holds(claimant, card) {
+                self.bluffs += 1;
+            }
+        }
+    }
+}
+
+pub fn act_name(act: Act) -> &'static str {
+    match act {
+        Act::Income => "Income",
+        Act::ForeignAid => "ForeignAid",
+        Act::Tax => "Tax",
+        Act::Exchange => "Exchange",
+        Act::Steal { .. } => "Steal",
+        Act::Assassinate { .. } => "Assassinate",
+        Act::Coup { .. } => "Coup",
+    }
+}
+
+// agents for each seat in the given game
+pub fn seats_for(config: &Config, game: u64) -> Vec<AgentKind> {
+    let rotation = game as usize % config.agents.len();
+    config
+        .agents
+        .iter()
+        .cycle()
+        .skip(rotation)
+        .take(config.players)
+        .copied()
+        .collect()
+}
+
+// asks every player that can react (in turn order) until one of them does
+fn reaction_window(phase: &Phase, agents: &mut [Box<dyn Agent>]) -> Decision {
+    let info = phase.info();
+    for id in phase.deciders() {
+        let mut options = phase.options_for(id);
+        options.push(Decision::Pass);
+
+        let decision = agents[id as usize - 1].decide(id, &info, &options);
+        if decision != Decision::Pass {
+            return decision;
+        }
+    }
+
+    Decision::Pass
+}
+
+pub fn play_game(seed: u64, seats: Vec<AgentKind>, max_turns: u32) -> GameRecord {
+    let mut record = GameRecord::new(seed, seats);
+    let mut agents: Vec<_> = record
+        .seats
+        .iter()
+        .zip(1..)
+        .map(|(kind, seat)| kind.build(seed.wrapping_mul(31).wrapping_add(seat)))
+        .collect();
+
+    let names = record
+        .seats
+        .iter()
+        .zip(1..)
+        .map(|(kind, seat)| format!("{kind} {seat}"));
+    let mut phase = Phase::from(CoupGame::with_seed(names, seed));
+
+    record.starting_hands = phase
+        .info()
+        .players
+        .alive()
+        .map(|(_, player)| match player.hand() {
+            Hand::Full(c1, c2) => [c1, c2],
+            Hand::Last { .. } => unreachable!("Everyone starts with two cards"),
+        })
+        .collect();
+
+    loop {
+        let decision = match &phase {
+            Phase::End(..) => break,
+            Phase::Wait(..) if record.turns >= max_turns => return record,
+            Phase::OnlyChallengeable(..)
+            | Phase::OnlyBlockable(..)
+            | Phase::Reactable(..)
+            | Phase::Block(..) => reaction_window(&phase, &mut agents),
+            _ => {
+                if matches!(phase, Phase::Wait(..)) {
+                    record.turns += 1;
+                }
+                let options = phase.options();
+                let id = phase
+                    .decider(&options[0])
+                    .expect("Only reaction windows can be passed");
+                agents[id as usize - 1].decide(id, &phase.info(), &options)
+            }
+        };
+
+        record.record(&phase, &decision);
+        phase = match phase.decide(decision) {
+            Ok(step) => step.phase,
+            Err(invalid) => panic!("Agent made an invalid decision: {invalid}"),
+        };
+    }
+
+    let Phase::End(game) = phase else {
+        unreachable!("Loop only ends when the game is over")
+    };
+    record.result = GameResult::Won(game.summary().winner);
+    record
+}
+
+// same as play_game, but engine panics are caught and recorded instead
+pub fn simulate_game(seed: u64, seats: Vec<AgentKind>, max_turns: u32) -> GameRecord {
+    let fallback = GameRecord::new(seed, seats.clone());
+    panic::catch_unwind(AssertUnwindSafe(|| play_game(seed, seats, max_turns))).unwrap_or_else(
+        |payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Unknown panic".to_owned());
+
+            GameRecord {
+                result: GameResult::Panicked(message),
+                ..fallback
+            }
+        },
+    )
+}
+
+// runs every game of the simulation in parallel, each game gets its own seed derived from the
+// config's seed so any single game can be replayed on its own
+pub fn run(config: &Config) -> Vec<GameRecord> {
+    assert!((2..=6).contains(&config.players));
+    assert!(!config.agents.is_empty());
+
+    (0..config.games)
+        .into_par_iter()
+        .map(|game| {
+            let seed = config.seed.wrapping_add(game);
+            simulate_game(seed, seats_for(config, game), config.max_turns)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(agents: Vec<AgentKind>) -> Config {
+        Config {
+            games: 50,
+            players: 4,
+            agents,
+            seed: 0,
+            max_turns: 500,
+        }
+    }
+
+    #[test]
+    fn seats_rotate() {
+        let config = config(vec![AgentKind::Random, AgentKind::Honest]);
+        use AgentKind::*;
+        assert_eq!(seats_for(&config, 0), [Random, Honest, Random, Honest]);
+        assert_eq!(seats_for(&config, 1), [Honest, Random, Honest, Random]);
+    }
+
+    #[test]
+    fn games_are_reproducible() {
+        let seats = vec![AgentKind::Random, AgentKind::Bluffer, AgentKind::Honest];
+        let first = play_game(11, seats.clone(), 500);
+        let second = play_game(11, seats, 500);
+
+        assert_eq!(first.turns, second.turns);
+        assert_eq!(first.acts, second.acts);
+    }
+
+    #[test]
+    fn all_agents_finish_games() {
+        let config = config(vec![
+            AgentKind::Random,
+            AgentKind::Honest,
+            AgentKind::Bluffer,
+        ]);
+        for record in run(&config) {
+            assert!(
+                !matches!(record.result, GameResult::Panicked(..)),
+                "game with seed {} panicked: {:?}",
+                record.seed,
+                record.result
+            );
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::agent::AgentKind;
+use crate::runner::{GameRecord, GameResult};
+use itertools::Itertools;
+use overthrow_engine::deck::Card;
+use overthrow_engine::players::PlayerId;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct WinRate {
+    pub games: u64,
+    pub wins: u64,
+}
+
+impl WinRate {
+    fn add(&mut self, won: bool) {
+        self.games += 1;
+        self.wins += won as u64;
+    }
+
+    pub fn rate(&self) -> f64 {
+        ratio(self.wins, self.games)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct Bluffs {
+    // every claimed card (actions and blocks)
+    pub claims: u64,
+    // claims made without holding the card
+    pub bluffs: u64,
+    // bluffs that were challenged
+    pub caught: u64,
+}
+
+impl Bluffs {
+    // share of bluffs that got away with it
+    pub fn success_rate(&self) -> f64 {
+        ratio(self.bluffs - self.caught, self.bluffs)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct Challenges {
+    pub made: u64,
+    // challenges that caught a bluff
+    pub successful: u64,
+}
+
+impl Challenges {
+    pub fn success_rate(&self) -> f64 {
+        ratio(self.successful, self.made)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PanicReport {
+    pub seed: u64,
+    pub message: String,
+}
+
+// aggregated statistics over a batch of games. Only finished games count towards win rates
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Report {
+    pub games: u64,
+    pub finished: u64,
+    pub stalled: u64,
+    pub panics: Vec<PanicReport>,
+    pub average_turns: f64,
+    pub seat_wins: BTreeMap<PlayerId, WinRate>,
+    pub agent_wins: BTreeMap<AgentKind, WinRate>,
+    pub acts: BTreeMap<&'static str, u64>,
+    pub bluffs: Bluffs,
+    pub challenges: Challenges,
+    pub starting_hands: BTreeMap<String, WinRate>,
+}
+
+fn ratio(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+// order of the cards in a hand doesn't matter
+fn hand_name([c1, c2]: [Card; 2]) -> String {
+    [c1, c2]
+        .into_iter()
+        .map(|card| card.to_string())
+        .sorted()
+        .join(" + ")
+}
+
+impl Report {
+    pub fn from_records(records: &[GameRecord]) -> Report {
+        let mut report = Report {
+            games: records.len() as u64,
+            ..Default::default()
+        };
+        let mut turns = 0;
+
+        for record in records {
+            let winner = match &record.result {
+                GameResult::Won(winner) => *winner,
+                GameResult::Stalled => {
+                    report.stalled += 1;
+                    continue;
+                }
+                GameResult::Panicked(message) => {
+                    report.panics.push(PanicReport {
+                        seed: record.seed,
+                        message: message.clone(),
+                    });
+                    continue;
+                }
+            };
+
+            report.finished += 1;
+            turns += record.turns as u64;
+
+            let seats = PlayerId::iter()
+                .zip(&record.seats)
+                .zip(&record.starting_hands);
+            for ((id, agent), hand) in seats {
+                let won = id == winner;
+                report.seat_wins.entry(id).or_default().add(won);
+                report.agent_wins.entry(*agent).or_default().add(won);
+                report
+                    .starting_hands
+                    .entry(hand_name(*hand))
+                    .or_default()
+                    .add(won);
+            }
+
+            for (act, count) in &record.acts {
+                *report.acts.entry(act).or_default() += *count as u64;
+            }
+
+            report.bluffs.claims += record.claims as u64;
+            report.bluffs.bluffs += record.bluffs as u64;
+            report.bluffs.caught += record.bluffs_caught as u64;
+            report.challenges.made += record.challenges as u64;
+            report.challenges.successful += record.successful_challenges as u64;
+        }
+
+        report.average_turns = ratio(turns, report.finished);
+        report
+    }
+}
+
+fn percent(rate: f64) -> String {
+    format!("{:.1}%", rate * 100.0)
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Games:         {}", self.games)?;
+        writeln!(f, "Finished:      {}", self.finished)?;
+        writeln!(f, "Stalled:       {}", self.stalled)?;
+        writeln!(f, "Panicked:      {}", self.panics.len())?;
+        writeln!(f, "Average turns: {:.2}", self.average_turns)?;
+
+        writeln!(
+            f,
+            "\n{:<24} {:>8} {:>8} {:>8}",
+            "Seat", "Games", "Wins", "Rate"
+        )?;
+        for (id, wins) in &self.seat_wins {
+            let seat = format!("Player {id}");
+            writeln!(
+                f,
+                "{seat:<24} {:>8} {:>8} {:>8}",
+                wins.games,
+                wins.wins,
+                percent(wins.rate())
+            )?;
+        }
+
+        writeln!(
+            f,
+            "\n{:<24} {:>8} {:>8} {:>8}",
+            "Agent", "Games", "Wins", "Rate"
+        )?;
+        for (agent, wins) in &self.agent_wins {
+            writeln!(
+                f,
+                "{:<24} {:>8} {:>8} {:>8}",
+                agent.to_string(),
+                wins.games,
+                wins.wins,
+                percent(wins.rate())
+            )?;
+        }
+
+        let total_acts: u64 = self.acts.values().sum();
+        writeln!(f, "\n{:<24} {:>8} {:>8}", "Act", "Count", "Share")?;
+        for (act, count) in &self.acts {
+            let share = percent(ratio(*count, total_acts));
+            writeln!(f, "{act:<24} {count:>8} {share:>8}")?;
+        }
+
+        writeln!(f, "\n{:<24} {:>8} {:>8}", "Claims", "Count", "Rate")?;
+        let Bluffs {
+            claims,
+            bluffs,
+            caught,
+        } = self.bluffs;
+        writeln!(f, "{:<24} {claims:>8} {:>8}", "Claims made", "")?;
+        writeln!(
+            f,
+            "{:<24} {bluffs:>8} {:>8}",
+            "Bluffs",
+            percent(ratio(bluffs, claims))
+        )?;
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>8}",
+            "Bluffs unchallenged",
+            bluffs - caught,
+            percent(self.bluffs.success_rate())
+        )?;
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>8}",
+            "Challenges", self.challenges.made, ""
+        )?;
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>8}",
+            "Challenges successful",
+            self.challenges.successful,
+            percent(self.challenges.success_rate())
+        )?;
+
+        writeln!(
+            f,
+            "\n{:<24} {:>8} {:>8} {:>8}",
+            "Starting hand", "Games", "Wins", "Rate"
+        )?;
+        for (hand, wins) in &self.starting_hands {
+            writeln!(
+                f,
+                "{hand:<24} {:>8} {:>8} {:>8}",
+                wins.games,
+                wins.wins,
+                percent(wins.rate())
+            )?;
+        }
+
+        for PanicReport { seed, message } in &self.panics {
+            writeln!(f, "\nPanic with seed {seed}: {message}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,9 +1,12 @@
 use jiff::Timestamp;
 use overthrow_engine::deck::Hand;
+use overthrow_engine::machine::GameInfo;
 pub use overthrow_engine::{
-    action::{Action, Block, Blocks, Challenge, Reaction},
+    action::{Action, Block, Blocks, Challenge, Reaction, ReactionStage},
+    commitment::{Commitment, Reveal},
     deck::Card,
     machine::{Outcome, Summary},
+    player_map::{ChallengeResult, Claim},
     players::PlayerId,
 };
 use schemars::JsonSchema;
@@ -16,18 +19,57 @@ use uuid::Uuid;
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub enum ClientMessage {
     GameId(Uuid),
+    // id of this connection within its lobby, as listed in LobbyState
+    MemberId(Uuid),
+    // secret for taking this seat back after losing the connection mid-game, by connecting with
+    // it as the resume query parameter
+    ResumeToken(Uuid),
+    // sent to every member whenever someone joins, leaves or changes whether they are ready
+    Lobby(LobbyState),
+    // commitment to the shuffle seed, sent on joining a lobby (before any entropy is taken)
+    Commitment(Commitment),
     PlayerId(PlayerId),
     Info(Info),
     End(Summary),
+    // sent right before End (or GameCancelled, once the game has started), to check the deals
+    // against the commitment
+    Reveal(Reveal),
     GameCancelled,
     Outcome(Outcome),
-    ActionChoices(Vec<Action>),
+    // what the current player claims to do, and how others react to it, before the outcome
+    ActionDeclared(Action),
+    ReactionDeclared(Reaction),
+    // table talk, from the lobby until the game is over
+    Chat(ChatMessage),
+    // every choice comes with a deadline, after which one is made for the player
+    ActionChoices(Vec<Action>, Timestamp),
     ChallengeChoice(Challenge, Timestamp),
     BlockChoices(Blocks, Timestamp),
-    ReactionChoices(Vec<Reaction>, Timestamp),
-    VictimChoices([Card; 2]),
-    OneFromThreeChoices([Card; 3]),
-    TwoFromFourChoices([Card; 4]),
+    // along with which stage of the reaction window they are for
+    ReactionChoices(Vec<Reaction>, ReactionStage, Timestamp),
+    VictimChoices([Card; 2], Timestamp),
+    OneFromThreeChoices([Card; 3], Timestamp),
+    TwoFromFourChoices([Card; 4], Timestamp),
+    // another player asked to take back the last turn, answered with AllowUndo or Pass
+    UndoRequested(PlayerId, Timestamp),
+    // the last turn was taken back, and Info is sent again
+    Undone,
+    // someone declined (or there was nothing to take back)
+    UndoDeclined,
+    // players the game is waiting on, in turn order, and when their time runs out. Sent again as
+    // each of them responds
+    WaitingFor(Vec<PlayerId>, Option<Timestamp>),
+    // a player lost their connection, and has until the timestamp to come back before the game is
+    // given up on. Their choices are still made for them when they run out of time
+    PlayerDisconnected(PlayerId, Timestamp),
+    PlayerReconnected(PlayerId),
+    // a player didn't come back in time, and is taken out of the game at the start of the next
+    // turn (or has a bot play for them, depending on the lobby)
+    PlayerLeft(PlayerId),
+    // the server's operator stopped the game, which happens once the turn in progress is over, or
+    // let it carry on
+    GamePaused,
+    GameResumed,
 }
 
 // TODO: remove redundant information from responses to simplify schema
@@ -41,6 +83,20 @@ pub enum ClientResponse {
     ChooseVictim(Card),
     ExchangeOne(Card),
     ExchangeTwo([Card; 2]),
+    // mixed into the shuffle seed, only taken while waiting in a lobby
+    Entropy(String),
+    // instead of acting, only in lobbies that allow undo
+    Undo,
+    AllowUndo(bool),
+    // whether we are ready for the game to start, only taken while waiting in a lobby
+    Ready(bool),
+    // starts the game, only taken from the host once everyone in the lobby is ready
+    Start,
+    // seats a bot, or takes it out again by its member id, only taken from the host
+    AddBot(Difficulty),
+    RemoveBot(Uuid),
+    // says something to everyone else, taken at any time (even while a choice is expected)
+    Chat(String),
 }
 
 #[derive(Debug, Clone, Error, Deserialize, Serialize, JsonSchema)]
@@ -49,6 +105,131 @@ pub enum ClientError {
     NotReady,
     #[error("Response from client is not in the correct format, or does not contain valid values")]
     InvalidResponse,
+    #[error("There is no lobby waiting to start with that join code")]
+    LobbyNotFound,
+    #[error("Lobby already has the most players a game can have")]
+    LobbyFull,
+    #[error("Only the host can start the game, or add and remove bots")]
+    NotHost,
+    #[error("Games need between 2 and 6 players, who must all be ready")]
+    PlayersNotReady,
+    #[error("There is no game waiting for a player with that resume token")]
+    CannotResume,
+    #[error("There is no game that has started with that id")]
+    GameNotFound,
+    #[error("Every hand can only be watched once the game is over, or in lobbies that allow it")]
+    NotOmniscient,
+    #[error("Chat messages can't be empty, or longer than 280 characters")]
+    InvalidChat,
+    #[error("Too many chat messages sent, wait a moment before sending more")]
+    ChatTooFast,
+    #[error("Removed from the game by the server's operator")]
+    Kicked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LobbyState {
+    // short code other players join the lobby with
+    pub code: String,
+    pub host: Uuid,
+    // in the order they joined, which is also the order they are given PlayerIds in
+    pub members: Vec<Member>,
+    // whether turns can be taken back, as chosen by whoever created the lobby (as are the timers)
+    pub undo: bool,
+    // seconds players have to react, choose an action and choose cards
+    pub reaction_time: u32,
+    pub action_time: u32,
+    pub choice_time: u32,
+    pub leave: Leave,
+    // whether spectators can see every hand, and how many seconds behind the game they are at least
+    pub omniscient: bool,
+    pub spectator_delay: u32,
+    // whether spectators are sent chat
+    pub spectator_chat: bool,
+    // when bots take the empty seats and the game starts, only for quick matches
+    pub fill_at: Option<Timestamp>,
+}
+
+// what happens to the seat of a player who leaves a game for good
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Leave {
+    // their cards are turned face up and their coins go back to the pile
+    #[default]
+    Resign,
+    // a bot takes the same moves for them as when players run out of time
+    Bot,
+}
+
+// something said by a member of the lobby, who can be told apart by their member id
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChatMessage {
+    pub sender: Uuid,
+    pub name: String,
+    pub sent: Timestamp,
+    pub text: String,
+}
+
+// a game that is over, as kept by the server (served at /games)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GameRecord {
+    pub id: Uuid,
+    // names of the players, in seat order
+    pub players: Vec<String>,
+    pub seed: u64,
+    // what the seed was made from, to check it against the commitment players were sent
+    pub reveal: Reveal,
+    pub created: Timestamp,
+    pub started: Timestamp,
+    pub ended: Timestamp,
+    pub result: GameResult,
+    // how many events the server missed by falling behind the game, which are left out of the
+    // replay. It is only complete when this is 0
+    #[serde(default)]
+    pub missed: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub enum GameResult {
+    Finished(Summary),
+    // every player left before the game was over
+    Cancelled,
+}
+
+// everything that happened in a game, in order, with every hand showing (served at
+// /games/{id}/replay)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Replay {
+    pub game: GameRecord,
+    pub events: Vec<ReplayEvent>,
+}
+
+// something that happened in a game, sent the same way it is to spectators
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ReplayEvent {
+    pub at: Timestamp,
+    pub message: ClientMessage,
+}
+
+// how well a bot seated by the server plays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    // picks any of its choices at random
+    Easy,
+    // only claims and blocks with cards it has, and never challenges
+    Medium,
+    // claims whatever is best, blocks everything, and challenges now and then
+    Hard,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Member {
+    pub id: Uuid,
+    pub name: String,
+    pub ready: bool,
+    // set for seats the server plays
+    pub bot: Option<Difficulty>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -57,11 +238,13 @@ pub enum PlayerView {
         name: String,
         coins: u8,
         revealed_cards: Vec<Card>,
+        claims: Vec<Claim>,
     },
     Me {
         name: String,
         coins: u8,
         hand: Hand,
+        claims: Vec<Claim>,
     },
 }
 
@@ -71,3 +254,59 @@ pub struct Info {
     pub current_player: PlayerId,
     pub coins_remaining: u8,
 }
+
+impl Info {
+    // the game as seen by player_id, only their own hand is visible
+    pub fn new(player_id: PlayerId, info: &GameInfo) -> Info {
+        Info::showing(info, |id| id == player_id)
+    }
+
+    // the game as seen from outside it, with every hand showing when omniscient (as Me views)
+    pub fn spectator(info: &GameInfo, omniscient: bool) -> Info {
+        Info::showing(info, |_| omniscient)
+    }
+
+    fn showing(info: &GameInfo, shown: impl Fn(PlayerId) -> bool) -> Info {
+        let players = info.players;
+        let alive_views = players.alive().map(|(id, player)| {
+            let revealed_cards = match player.hand() {
+                Hand::Full(..) => Vec::new(),
+                Hand::Last { dead, .. } => vec![dead],
+            };
+
+            let view = if shown(id) {
+                PlayerView::Me {
+                    name: player.name().to_owned(),
+                    coins: player.coins().amount(),
+                    hand: player.hand().clone(),
+                    claims: players.claims(id).to_vec(),
+                }
+            } else {
+                PlayerView::Other {
+                    name: player.name().to_owned(),
+                    coins: player.coins().amount(),
+                    revealed_cards,
+                    claims: players.claims(id).to_vec(),
+                }
+            };
+
+            (id, view)
+        });
+
+        let dead_views = players.dead().map(|(id, player)| {
+            let view = PlayerView::Other {
+                name: player.name().to_owned(),
+                coins: 0,
+                revealed_cards: player.revealed().into(),
+                claims: players.claims(id).to_vec(),
+            };
+            (id, view)
+        });
+
+        Info {
+            player_views: alive_views.chain(dead_views).collect(),
+            current_player: info.current_player,
+            coins_remaining: info.coins_remaining,
+        }
+    }
+}